@@ -0,0 +1,58 @@
+use std::collections::HashMap;
+use std::hint::black_box;
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+use golinks::models::Routes;
+
+/// Builds routes with `count` links spread over a hundred team namespaces, along with a
+/// link for each team itself.
+fn routes(count: usize) -> Routes {
+    let mut links: HashMap<String, String> = (0..count)
+        .map(|i| {
+            (
+                format!("team{}/link{}", i % 100, i),
+                format!("https://example.com/{}", i),
+            )
+        })
+        .collect();
+    links.extend((0..100).map(|team| {
+        (
+            format!("team{}", team),
+            format!("https://teams.example.com/{}", team),
+        )
+    }));
+
+    Routes::with_routes(links)
+}
+
+fn resolve(c: &mut Criterion) {
+    let mut group = c.benchmark_group("resolve");
+
+    for count in [10, 10_000, 500_000] {
+        let routes = routes(count);
+        let last = count - 1;
+        let exact = format!("team{}/link{}", last % 100, last);
+        let prefix = format!("{}/deeply/nested/path", exact);
+        let ancestor = format!("team{}/missing/path", last % 100);
+
+        group.bench_with_input(BenchmarkId::new("exact", count), &exact, |b, path| {
+            b.iter(|| routes.resolve(black_box(path)))
+        });
+        group.bench_with_input(BenchmarkId::new("prefix", count), &prefix, |b, path| {
+            b.iter(|| routes.resolve(black_box(path)))
+        });
+        group.bench_with_input(BenchmarkId::new("ancestor", count), &ancestor, |b, path| {
+            b.iter(|| routes.resolve(black_box(path)))
+        });
+        group.bench_with_input(
+            BenchmarkId::new("miss", count),
+            "unknown/path",
+            |b, path| b.iter(|| routes.resolve(black_box(path))),
+        );
+    }
+
+    group.finish();
+}
+
+criterion_group!(benches, resolve);
+criterion_main!(benches);
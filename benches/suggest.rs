@@ -0,0 +1,36 @@
+use std::hint::black_box;
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+use golinks::models::Routes;
+use golinks::suggest;
+
+/// Builds routes with `count` links spread over a hundred team namespaces.
+fn routes(count: usize) -> Routes {
+    Routes::with_routes(
+        (0..count)
+            .map(|i| {
+                (
+                    format!("team{}/link{}", i % 100, i),
+                    format!("https://example.com/{}", i),
+                )
+            })
+            .collect(),
+    )
+}
+
+fn suggest(c: &mut Criterion) {
+    let mut group = c.benchmark_group("suggest");
+
+    for count in [10, 10_000, 50_000] {
+        let routes = routes(count);
+
+        group.bench_with_input(BenchmarkId::new("typo", count), "team7/lnik7", |b, path| {
+            b.iter(|| suggest::suggest(&routes, black_box(path)))
+        });
+    }
+
+    group.finish();
+}
+
+criterion_group!(benches, suggest);
+criterion_main!(benches);
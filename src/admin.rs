@@ -0,0 +1,557 @@
+use std::path::PathBuf;
+
+use rocket::http::{Header, Status};
+use rocket::request::{FromRequest, Outcome, Request};
+use rocket::serde::json::Json;
+use rocket::serde::Deserialize;
+use rocket::{Catcher, Route, State};
+use sha2::{Digest, Sha256};
+
+use crate::api::{rejected, LinkEntry, Tagged};
+use crate::config::AppConfig;
+use crate::edit::LinkEdit;
+use crate::link::Link;
+use crate::models::{ApiResponse, StatusMessage};
+use crate::reload::{ReloadOutcome, ReloadStatus, Reloader, Trigger};
+use crate::store::{SharedStore, StoreError, StoreKind};
+
+/// The path the admin endpoints are mounted at.
+pub const BASE: &str = "/_/admin";
+
+/// Request guard for the admin endpoints, which succeeds when the request carries the
+/// admin token as a bearer token. The admin endpoints do not exist unless an admin
+/// token is configured.
+pub struct AdminToken;
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for AdminToken {
+    type Error = &'static str;
+
+    async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        let Some(expected) = request
+            .rocket()
+            .state::<AppConfig>()
+            .and_then(AppConfig::admin_token)
+        else {
+            return Outcome::Error((Status::NotFound, "The admin endpoints are disabled"));
+        };
+
+        let token = request
+            .headers()
+            .get_one("Authorization")
+            .and_then(|value| value.strip_prefix("Bearer "));
+
+        match token {
+            Some(token) if constant_time_eq(token.trim().as_bytes(), expected.as_bytes()) => {
+                Outcome::Success(AdminToken)
+            }
+            _ => Outcome::Error((Status::Unauthorized, "Missing or invalid admin token")),
+        }
+    }
+}
+
+/// Compares two tokens in a time that depends neither on where they differ nor on their
+/// lengths, by comparing their fixed-length digests.
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    let (a, b) = (Sha256::digest(a), Sha256::digest(b));
+    a.iter()
+        .zip(b.iter())
+        .fold(0, |diff, (a, b)| diff | (a ^ b))
+        == 0
+}
+
+/// The response to an admin request without a valid token.
+#[derive(Responder)]
+#[response(status = 401, content_type = "json")]
+struct Unauthorized {
+    body: Json<StatusMessage>,
+    authenticate: Header<'static>,
+}
+
+#[catch(401)]
+fn unauthorized() -> Unauthorized {
+    Unauthorized {
+        body: Json(StatusMessage {
+            status: "error".to_string(),
+            message: "Missing or invalid admin token".to_string(),
+        }),
+        authenticate: Header::new("WWW-Authenticate", "Bearer"),
+    }
+}
+
+#[catch(404)]
+fn not_found() -> Json<StatusMessage> {
+    Json(StatusMessage {
+        status: "error".to_string(),
+        message: "Not found".to_string(),
+    })
+}
+
+fn invalid_body() -> Json<StatusMessage> {
+    Json(StatusMessage {
+        status: "error".to_string(),
+        message: "The request body is not a valid link".to_string(),
+    })
+}
+
+#[catch(400)]
+fn bad_request() -> Json<StatusMessage> {
+    invalid_body()
+}
+
+#[catch(422)]
+fn unprocessable() -> Json<StatusMessage> {
+    invalid_body()
+}
+
+#[catch(412)]
+fn precondition_failed() -> Json<StatusMessage> {
+    Json(StatusMessage {
+        status: "error".to_string(),
+        message: "If-Match must be * or ETags of the link".to_string(),
+    })
+}
+
+#[catch(428)]
+fn precondition_required() -> Json<StatusMessage> {
+    Json(StatusMessage {
+        status: "error".to_string(),
+        message: "Changes to a link must carry the ETag of the link in If-Match".to_string(),
+    })
+}
+
+/// Request guard for the `If-Match` header, which changes to existing links must carry.
+/// Holds the revisions the change can be made against, any of which must match, or
+/// `None` for `*`, which applies the change whatever the revision of the link.
+struct IfMatch(Option<Vec<u64>>);
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for IfMatch {
+    type Error = &'static str;
+
+    async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        let Some(value) = request.headers().get_one("If-Match") else {
+            return Outcome::Error((Status::PreconditionRequired, "Missing If-Match"));
+        };
+
+        let value = value.trim();
+        if value == "*" {
+            return Outcome::Success(IfMatch(None));
+        }
+
+        // Only the strong ETags this service hands out can match a link, so other
+        // entries of the list never match.
+        let revisions: Vec<u64> = value
+            .split(',')
+            .filter_map(|tag| {
+                tag.trim()
+                    .strip_prefix('"')
+                    .and_then(|tag| tag.strip_suffix('"'))
+                    .and_then(|tag| tag.parse().ok())
+            })
+            .collect();
+        if revisions.is_empty() {
+            return Outcome::Error((Status::PreconditionFailed, "Invalid If-Match"));
+        }
+        Outcome::Success(IfMatch(Some(revisions)))
+    }
+}
+
+type Rejected = (Status, Json<StatusMessage>);
+
+type ReloadResult = Result<(Status, Json<ApiResponse<ReloadOutcome>>), Rejected>;
+
+/// Reloads the links files, the same way the file watcher and `SIGHUP` do, responding
+/// with the outcome. A reload that was rejected responds with 422. Links kept in a
+/// store other than the links files are never reloaded, which responds with 409.
+#[post("/reload")]
+async fn reload(
+    _token: AdminToken,
+    configs: &State<AppConfig>,
+    reloader: &State<Reloader>,
+) -> ReloadResult {
+    if configs.store() != StoreKind::File {
+        return Err(rejected(
+            Status::Conflict,
+            "The links are not stored in links files, so there is nothing to reload".to_string(),
+        ));
+    }
+
+    let outcome = reloader.reload(Trigger::Admin).await;
+
+    let message = outcome.message.clone();
+    match outcome.status {
+        ReloadStatus::Failed => Ok((
+            Status::UnprocessableEntity,
+            Json(ApiResponse::error(message, outcome)),
+        )),
+        _ => Ok((Status::Ok, Json(ApiResponse::ok(message, outcome)))),
+    }
+}
+
+/// Struct for deserializing a link to create.
+#[derive(Deserialize)]
+#[serde(crate = "rocket::serde", deny_unknown_fields)]
+struct NewLink {
+    key: String,
+    link: Link,
+}
+
+type EditResult = Result<(Status, Tagged<Json<ApiResponse<LinkEntry>>>), Rejected>;
+
+/// Applies a change to a link in the store, made against any of the `expected` revisions
+/// of the link if any, and responds with the link or with the reason the change was
+/// rejected. Rejected changes leave the store and the active routes untouched.
+async fn edit(
+    store: &SharedStore,
+    edit: LinkEdit,
+    expected: Option<&[u64]>,
+) -> Result<LinkEntry, Rejected> {
+    let key = edit.key().trim_matches('/').to_string();
+
+    // The store checks a single revision, so the change is made against the one the
+    // link is at, if it is listed. The store still rejects the change if the link
+    // changes in the meantime.
+    let expected = match expected {
+        Some([revision]) => Some(*revision),
+        Some(revisions) => {
+            let current = store.get(&key).await.map_err(store_rejected)?;
+            current
+                .map(|stored| stored.revision)
+                .filter(|revision| revisions.contains(revision))
+                .or(revisions.first().copied())
+        }
+        None => None,
+    };
+
+    match store.apply(edit, expected).await {
+        Ok(stored) => Ok(LinkEntry::stored(&key, stored)),
+        Err(err) => Err(store_rejected(err)),
+    }
+}
+
+/// Responds with the reason a store rejected a request.
+fn store_rejected(err: StoreError) -> Rejected {
+    let status = match &err {
+        StoreError::NotFound(_) => Status::NotFound,
+        StoreError::Exists(_) | StoreError::ReadOnly(_) => Status::Conflict,
+        StoreError::Conflict { .. } => Status::PreconditionFailed,
+        StoreError::Invalid(_) => Status::UnprocessableEntity,
+        StoreError::Backend(_) => Status::InternalServerError,
+    };
+    rejected(status, err.to_string())
+}
+
+/// Responds with a link that was created or changed, tagged with its new revision.
+fn changed(status: Status, message: &str, entry: LinkEntry) -> EditResult {
+    let revision = entry.revision.unwrap_or_default();
+    Ok((
+        status,
+        Tagged::new(Json(ApiResponse::ok(message, entry)), revision),
+    ))
+}
+
+/// Adds a link to the store, responding with 409 if the key is taken.
+#[post("/links", data = "<new>")]
+async fn create_link(
+    _token: AdminToken,
+    new: Json<NewLink>,
+    store: &State<SharedStore>,
+) -> EditResult {
+    let NewLink { key, link } = new.into_inner();
+    let entry = edit(store, LinkEdit::Create(key, link), None).await?;
+    changed(Status::Created, "Created link", entry)
+}
+
+/// Replaces a link in the store. The request must carry the ETag of the link in
+/// `If-Match`, and responds with 412 if the link changed since.
+#[put("/links/<key..>", data = "<link>")]
+async fn update_link(
+    _token: AdminToken,
+    if_match: IfMatch,
+    key: PathBuf,
+    link: Json<Link>,
+    store: &State<SharedStore>,
+) -> EditResult {
+    let key = key.to_string_lossy().to_string();
+    let entry = edit(
+        store,
+        LinkEdit::Update(key, link.into_inner()),
+        if_match.0.as_deref(),
+    )
+    .await?;
+    changed(Status::Ok, "Updated link", entry)
+}
+
+/// Removes a link from the store, responding with the link that was removed. Like
+/// updates, the request must carry the ETag of the link in `If-Match`.
+#[delete("/links/<key..>")]
+async fn delete_link(
+    _token: AdminToken,
+    if_match: IfMatch,
+    key: PathBuf,
+    store: &State<SharedStore>,
+) -> Result<Json<ApiResponse<LinkEntry>>, Rejected> {
+    let key = key.to_string_lossy().to_string();
+    let entry = edit(store, LinkEdit::Delete(key), if_match.0.as_deref()).await?;
+    Ok(Json(ApiResponse::ok("Deleted link", entry)))
+}
+
+/// Gets the admin endpoints, to be mounted at `BASE`.
+pub fn routes() -> Vec<Route> {
+    routes![reload, create_link, update_link, delete_link]
+}
+
+/// Gets the catchers of the admin endpoints, to be registered at `BASE`.
+pub fn catchers() -> Vec<Catcher> {
+    catchers![
+        unauthorized,
+        not_found,
+        bad_request,
+        precondition_failed,
+        unprocessable,
+        precondition_required
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    use rocket::local::blocking::{Client, LocalRequest};
+    use rocket::serde::json::Value;
+
+    use crate::loader;
+    use crate::models::SharedRoutes;
+    use crate::store;
+
+    fn client(name: &str, token: Option<&str>) -> (Client, std::path::PathBuf) {
+        let path = std::env::temp_dir().join(format!("golinks-{}-{}", std::process::id(), name));
+        fs::write(&path, "routes:\n  test: https://example.com\n").unwrap();
+
+        let mut configs = AppConfig::default();
+        configs.set_links_file(path.to_str().unwrap());
+        if let Some(token) = token {
+            configs.set_admin_token(token);
+        }
+
+        let shared = SharedRoutes::new(
+            loader::load_routes(configs.links_file(), configs.conflicts(), None).unwrap(),
+        );
+        let reloader = Reloader::new(configs.clone(), shared);
+        let store = store::open(&configs, &reloader).unwrap();
+        let rocket = rocket::build()
+            .manage(reloader)
+            .manage(store)
+            .manage(configs)
+            .mount(BASE, routes())
+            .register(BASE, catchers());
+
+        (
+            Client::tracked(rocket).expect("valid rocket instance"),
+            path,
+        )
+    }
+
+    fn post_reload(client: &Client, token: Option<&str>) -> (Status, Option<String>, Value) {
+        let mut request = client.post(format!("{}/reload", BASE));
+        if let Some(token) = token {
+            request = request.header(Header::new("Authorization", format!("Bearer {}", token)));
+        }
+
+        let response = request.dispatch();
+        let status = response.status();
+        let authenticate = response
+            .headers()
+            .get_one("WWW-Authenticate")
+            .map(str::to_string);
+        (status, authenticate, response.into_json().unwrap())
+    }
+
+    #[test]
+    fn test_reload_requires_token() {
+        let (client, _) = client("admin-token.yaml", Some("s3cret"));
+
+        let (status, authenticate, body) = post_reload(&client, None);
+        assert_eq!(status, Status::Unauthorized);
+        assert_eq!(authenticate.as_deref(), Some("Bearer"));
+        assert_eq!(body["status"], "error");
+
+        let (status, _, _) = post_reload(&client, Some("wrong"));
+        assert_eq!(status, Status::Unauthorized);
+    }
+
+    #[test]
+    fn test_reload_disabled_without_token() {
+        let (client, _) = client("admin-disabled.yaml", None);
+
+        let (status, _, _) = post_reload(&client, Some("s3cret"));
+        assert_eq!(status, Status::NotFound);
+    }
+
+    fn send(
+        request: LocalRequest<'_>,
+        if_match: Option<&str>,
+        body: Option<&str>,
+    ) -> (Status, Option<String>, Value) {
+        let mut request = request.header(Header::new("Authorization", "Bearer s3cret"));
+        if let Some(if_match) = if_match {
+            request = request.header(Header::new("If-Match", if_match.to_string()));
+        }
+        if let Some(body) = body {
+            request = request.header(rocket::http::ContentType::JSON).body(body);
+        }
+
+        let response = request.dispatch();
+        let etag = response.headers().get_one("ETag").map(str::to_string);
+        (response.status(), etag, response.into_json().unwrap())
+    }
+
+    #[test]
+    fn test_edit_links() {
+        let (client, path) = client("admin-edit.yaml", Some("s3cret"));
+        let links = format!("{}/links", BASE);
+
+        let (status, etag, body) = send(
+            client.post(&links),
+            None,
+            Some(r#"{"key": "new", "link": {"url": "https://new.example.com", "tags": ["a"]}}"#),
+        );
+        assert_eq!(status, Status::Created);
+        assert_eq!(body["data"]["key"], "new");
+        assert_eq!(body["data"]["tags"][0], "a");
+        assert_eq!(etag, Some(format!("\"{}\"", body["data"]["revision"])));
+        assert_eq!(
+            fs::read_to_string(&path).unwrap(),
+            "routes:\n  test: https://example.com\n  new:\n    url: https://new.example.com\n    tags:\n    - a\n"
+        );
+
+        let (status, _, _) = send(
+            client.post(&links),
+            None,
+            Some(r#"{"key": "test", "link": "https://other.example.com"}"#),
+        );
+        assert_eq!(status, Status::Conflict);
+
+        let (status, _, body) = send(
+            client.put(format!("{}/new", links)),
+            None,
+            Some(r#""https://newer.example.com""#),
+        );
+        assert_eq!(status, Status::PreconditionRequired);
+        assert_eq!(body["status"], "error");
+
+        let (status, _, _) = send(
+            client.put(format!("{}/new", links)),
+            Some("W/\"1\""),
+            Some(r#""https://newer.example.com""#),
+        );
+        assert_eq!(status, Status::PreconditionFailed);
+
+        let (status, newer, body) = send(
+            client.put(format!("{}/new", links)),
+            etag.as_deref(),
+            Some(r#""https://newer.example.com""#),
+        );
+        assert_eq!(status, Status::Ok);
+        assert_eq!(body["data"]["url"], "https://newer.example.com");
+        assert_ne!(newer, etag);
+
+        // The link changed since the first ETag, so changes made against it are rejected.
+        let (status, _, body) = send(
+            client.put(format!("{}/new", links)),
+            etag.as_deref(),
+            Some(r#""https://newest.example.com""#),
+        );
+        assert_eq!(status, Status::PreconditionFailed);
+        assert_eq!(body["status"], "error");
+        let (status, _, _) = send(
+            client.delete(format!("{}/new", links)),
+            etag.as_deref(),
+            None,
+        );
+        assert_eq!(status, Status::PreconditionFailed);
+
+        // A list of ETags matches if any of them does.
+        let (etag, newer) = (etag.unwrap(), newer.unwrap());
+        let (status, _, _) = send(
+            client.put(format!("{}/new", links)),
+            Some(&format!("{}, W/\"1\"", etag)),
+            Some(r#""https://newest.example.com""#),
+        );
+        assert_eq!(status, Status::PreconditionFailed);
+        let (status, newest, body) = send(
+            client.put(format!("{}/new", links)),
+            Some(&format!("{}, {}", etag, newer)),
+            Some(r#""https://newer.example.com""#),
+        );
+        assert_eq!(status, Status::Ok);
+        assert_eq!(body["data"]["url"], "https://newer.example.com");
+        assert_ne!(newest, Some(newer));
+
+        let (status, _, _) = send(
+            client.put(format!("{}/missing", links)),
+            Some("*"),
+            Some(r#""https://newer.example.com""#),
+        );
+        assert_eq!(status, Status::NotFound);
+
+        let (status, _, body) = send(
+            client.put(format!("{}/new", links)),
+            Some("*"),
+            Some(r#"{"url": "https://newer.example.com", "unknown": 1}"#),
+        );
+        assert_eq!(status, Status::UnprocessableEntity);
+        assert_eq!(body["status"], "error");
+
+        let (status, _, _) = send(client.delete(format!("{}/test", links)), None, None);
+        assert_eq!(status, Status::PreconditionRequired);
+
+        let (status, _, body) = send(client.delete(format!("{}/test", links)), Some("*"), None);
+        assert_eq!(status, Status::Ok);
+        assert_eq!(body["data"]["url"], "https://example.com");
+        assert_eq!(
+            fs::read_to_string(&path).unwrap(),
+            "routes:\n  new: https://newer.example.com\n"
+        );
+
+        let (status, _, _) = send(client.delete(format!("{}/test", links)), Some("*"), None);
+        assert_eq!(status, Status::NotFound);
+
+        let response = client
+            .delete(format!("{}/new", links))
+            .header(Header::new("Authorization", "Bearer wrong"))
+            .header(Header::new("If-Match", "*"))
+            .dispatch();
+        assert_eq!(response.status(), Status::Unauthorized);
+    }
+
+    #[test]
+    fn test_reload() {
+        let (client, path) = client("admin-reload.yaml", Some("s3cret"));
+
+        let (status, _, body) = post_reload(&client, Some("s3cret"));
+        assert_eq!(status, Status::Ok);
+        assert_eq!(body["data"]["status"], "unchanged");
+        assert_eq!(body["data"]["hash"].as_str().map(str::len), Some(64));
+
+        fs::write(
+            &path,
+            "routes:\n  test: https://example.com\n  new: https://new.example.com\n",
+        )
+        .unwrap();
+        let (status, _, body) = post_reload(&client, Some("s3cret"));
+        assert_eq!(status, Status::Ok);
+        assert_eq!(body["data"]["status"], "reloaded");
+        assert_eq!(body["data"]["routes"], 2);
+        assert_eq!(body["data"]["changes"]["added"][0], "new");
+
+        fs::write(&path, "routes: [\n").unwrap();
+        let (status, _, body) = post_reload(&client, Some("s3cret"));
+        assert_eq!(status, Status::UnprocessableEntity);
+        assert_eq!(body["status"], "error");
+        assert_eq!(body["data"]["routes"], 2);
+        assert!(body["data"]["failure"]["line"].is_number());
+    }
+}
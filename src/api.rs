@@ -0,0 +1,329 @@
+use std::path::PathBuf;
+
+use rocket::http::uri::Origin;
+use rocket::http::{Header, Status};
+use rocket::response::status::NotFound;
+use rocket::serde::json::Json;
+use rocket::serde::Serialize;
+use rocket::{Route, State};
+
+use crate::link::{self, Link};
+use crate::models::{ApiResponse, MatchRule, SharedRoutes, StatusMessage};
+use crate::store::{SharedStore, StoreError, StoredLink};
+
+/// The path the version 1 API is mounted at.
+pub const BASE: &str = "/_/api/v1";
+
+const DEFAULT_PER_PAGE: usize = 50;
+const MAX_PER_PAGE: usize = 500;
+
+/// Struct for serializing a link along with its key.
+#[derive(Serialize)]
+#[serde(crate = "rocket::serde")]
+pub struct LinkEntry {
+    pub key: String,
+    #[serde(flatten)]
+    pub link: Link,
+    /// The revision the link was last changed at, for links read from the store.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub revision: Option<u64>,
+}
+
+impl LinkEntry {
+    /// Creates the entry of a link read from the store.
+    pub fn stored(key: &str, stored: StoredLink) -> Self {
+        Self {
+            key: key.to_string(),
+            link: stored.link,
+            revision: Some(stored.revision),
+        }
+    }
+}
+
+/// Struct for serializing a page of links.
+#[derive(Serialize)]
+#[serde(crate = "rocket::serde")]
+pub struct LinkPage {
+    pub links: Vec<LinkEntry>,
+    pub page: usize,
+    pub per_page: usize,
+    pub total: usize,
+}
+
+/// Struct for serializing the index of a prefix that has links under it but no link of
+/// its own.
+#[derive(Serialize)]
+#[serde(crate = "rocket::serde")]
+pub struct LinkIndex {
+    pub prefix: String,
+    pub links: Vec<LinkEntry>,
+}
+
+impl LinkIndex {
+    /// Creates the index of `prefix` from the links under it.
+    pub fn new(prefix: &str, links: &[(&str, &Link)]) -> Self {
+        Self {
+            prefix: prefix.trim_matches('/').to_string(),
+            links: links
+                .iter()
+                .map(|(key, link)| LinkEntry {
+                    key: key.to_string(),
+                    link: (*link).clone(),
+                    revision: None,
+                })
+                .collect(),
+        }
+    }
+}
+
+/// Struct for serializing what a path resolves to.
+#[derive(Serialize)]
+#[serde(crate = "rocket::serde")]
+pub struct ResolvedLink {
+    pub path: String,
+    pub key: String,
+    pub rule: MatchRule,
+    pub url: String,
+    pub status: u16,
+}
+
+/// A response carrying the revision of what it holds as its `ETag`.
+#[derive(Responder)]
+pub struct Tagged<R> {
+    body: R,
+    etag: Header<'static>,
+}
+
+impl<R> Tagged<R> {
+    /// Tags `body` with `revision`.
+    pub fn new(body: R, revision: u64) -> Self {
+        Self {
+            body,
+            etag: Header::new("ETag", format!("\"{}\"", revision)),
+        }
+    }
+}
+
+type ApiResult<T> = Result<Json<ApiResponse<T>>, NotFound<Json<StatusMessage>>>;
+
+type StoreResult<T> = Result<Tagged<Json<ApiResponse<T>>>, (Status, Json<StatusMessage>)>;
+
+fn not_found(message: String) -> NotFound<Json<StatusMessage>> {
+    NotFound(Json(StatusMessage {
+        status: "error".to_string(),
+        message,
+    }))
+}
+
+/// Builds the response to a request that could not be served.
+pub(crate) fn rejected(status: Status, message: String) -> (Status, Json<StatusMessage>) {
+    (
+        status,
+        Json(StatusMessage {
+            status: "error".to_string(),
+            message,
+        }),
+    )
+}
+
+/// Lists the links sorted by key, optionally only the ones starting with `prefix`. The
+/// response is tagged with the revision of the store.
+#[get("/links?<prefix>&<page>&<per_page>")]
+async fn list_links(
+    prefix: Option<&str>,
+    page: Option<usize>,
+    per_page: Option<usize>,
+    store: &State<SharedStore>,
+) -> StoreResult<LinkPage> {
+    let page = page.unwrap_or(1).max(1);
+    let per_page = per_page.unwrap_or(DEFAULT_PER_PAGE).clamp(1, MAX_PER_PAGE);
+
+    // The revision is read first, so that the links are at least as recent as it.
+    let failed = |err: StoreError| rejected(Status::InternalServerError, err.to_string());
+    let revision = store.revision().await.map_err(failed)?;
+    let links = store
+        .scan(prefix.unwrap_or_default())
+        .await
+        .map_err(failed)?;
+
+    let total = links.len();
+    let links = links
+        .into_iter()
+        .skip((page - 1).saturating_mul(per_page))
+        .take(per_page)
+        .map(|(key, stored)| LinkEntry::stored(&key, stored))
+        .collect();
+
+    Ok(Tagged::new(
+        Json(ApiResponse::ok(
+            format!("Found {} links", total),
+            LinkPage {
+                links,
+                page,
+                per_page,
+                total,
+            },
+        )),
+        revision,
+    ))
+}
+
+/// Fetches a single link and its metadata. The response is tagged with the revision of
+/// the link, which changes to it are made against.
+#[get("/links/<key..>")]
+async fn get_link(key: PathBuf, store: &State<SharedStore>) -> StoreResult<LinkEntry> {
+    let key = key.to_string_lossy();
+    let stored = store
+        .get(&key)
+        .await
+        .map_err(|err| rejected(Status::InternalServerError, err.to_string()))?
+        .ok_or_else(|| rejected(Status::NotFound, format!("'{}' is not a known link.", key)))?;
+
+    let revision = stored.revision;
+    Ok(Tagged::new(
+        Json(ApiResponse::ok(
+            "Found link",
+            LinkEntry::stored(&key, stored),
+        )),
+        revision,
+    ))
+}
+
+/// Reports what a path (and query string) would redirect to, using the same rules as
+/// the redirects themselves.
+#[get("/resolve/<path..>")]
+fn resolve(
+    path: PathBuf,
+    uri: &Origin<'_>,
+    routes: &State<SharedRoutes>,
+) -> ApiResult<ResolvedLink> {
+    let path = path.to_string_lossy();
+    let query = uri
+        .query()
+        .map(|query| link::query_params(query.as_str()))
+        .unwrap_or_default();
+
+    let routes_map = routes.load();
+    let resolution = routes_map
+        .resolve(&path)
+        .ok_or_else(|| not_found(format!("'{}' does not match any link.", path)))?;
+
+    Ok(Json(ApiResponse::ok(
+        format!("'{}' matched '{}'", path, resolution.key),
+        ResolvedLink {
+            path: path.to_string(),
+            key: resolution.key.to_string(),
+            rule: resolution.rule,
+            url: resolution.destination(&query),
+            status: resolution.link.status.code(),
+        },
+    )))
+}
+
+/// Gets the routes of the API, to be mounted at `BASE`.
+pub fn routes() -> Vec<Route> {
+    routes![list_links, get_link, resolve]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+    use std::sync::Arc;
+
+    use rocket::local::blocking::Client;
+    use rocket::serde::json::{serde_json, Value};
+
+    use crate::models::Routes;
+    use crate::store::MemoryStore;
+
+    fn client() -> Client {
+        let links: HashMap<String, Link> = (0..120)
+            .map(|i| {
+                (
+                    format!("l/{:03}", i),
+                    Link::new(format!("https://{}.com", i)),
+                )
+            })
+            .chain([(
+                "gh".to_string(),
+                serde_yaml::from_str("{ url: 'https://github.com/{1}', owners: [me] }").unwrap(),
+            )])
+            .collect();
+
+        let store: SharedStore = Arc::new(MemoryStore::new(links.clone()));
+        let rocket = rocket::build()
+            .manage(SharedRoutes::new(Routes::with_links(links)))
+            .manage(store)
+            .mount(BASE, routes());
+
+        Client::tracked(rocket).expect("valid rocket instance")
+    }
+
+    fn get(client: &Client, uri: &str) -> (Status, Value) {
+        let (status, _, body) = get_tagged(client, uri);
+        (status, body)
+    }
+
+    fn get_tagged(client: &Client, uri: &str) -> (Status, Option<String>, Value) {
+        let response = client.get(uri).dispatch();
+        let status = response.status();
+        let etag = response.headers().get_one("ETag").map(str::to_string);
+        (status, etag, response.into_json().unwrap())
+    }
+
+    #[test]
+    fn test_list_links_paginates() {
+        let client = client();
+        let (status, etag, body) =
+            get_tagged(&client, "/_/api/v1/links?prefix=l/&page=3&per_page=50");
+
+        assert_eq!(status, Status::Ok);
+        assert!(etag.is_some());
+        assert_eq!(body["status"], "ok");
+        assert_eq!(body["data"]["total"], 120);
+        assert_eq!(body["data"]["links"].as_array().unwrap().len(), 20);
+        assert_eq!(body["data"]["links"][0]["key"], "l/100");
+        assert_eq!(body["data"]["links"][0]["url"], "https://100.com");
+    }
+
+    #[test]
+    fn test_get_link() {
+        let client = client();
+        let (status, etag, body) = get_tagged(&client, "/_/api/v1/links/gh");
+
+        assert_eq!(status, Status::Ok);
+        assert_eq!(etag, Some(format!("\"{}\"", body["data"]["revision"])));
+        assert_eq!(body["data"]["key"], "gh");
+        assert_eq!(body["data"]["owners"], serde_json::json!(["me"]));
+
+        let (status, body) = get(&client, "/_/api/v1/links/missing");
+
+        assert_eq!(status, Status::NotFound);
+        assert_eq!(body["status"], "error");
+    }
+
+    #[test]
+    fn test_resolve() {
+        let client = client();
+        let (status, body) = get(&client, "/_/api/v1/resolve/gh/rust-lang?tab=repos");
+
+        assert_eq!(status, Status::Ok);
+        assert_eq!(body["data"]["key"], "gh");
+        assert_eq!(body["data"]["rule"], "prefix");
+        assert_eq!(
+            body["data"]["url"],
+            "https://github.com/rust-lang?tab=repos"
+        );
+        assert_eq!(body["data"]["status"], 307);
+
+        let (status, body) = get(&client, "/_/api/v1/resolve/l/001");
+
+        assert_eq!(status, Status::Ok);
+        assert_eq!(body["data"]["rule"], "exact");
+
+        let (status, _) = get(&client, "/_/api/v1/resolve/nothing/here");
+
+        assert_eq!(status, Status::NotFound);
+    }
+}
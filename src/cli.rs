@@ -0,0 +1,518 @@
+use std::fmt::Write;
+
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use rocket::http::uri::Origin;
+use rocket::serde::json;
+use rocket::serde::Serialize;
+
+use crate::api::LinkEntry;
+use crate::config::AppConfig;
+use crate::link::{self, Link};
+use crate::lint::Diagnostic;
+use crate::loader::{self, LoadError};
+use crate::models::{ConflictPolicy, LinksFormat, Routes};
+use crate::pattern::Pattern;
+use crate::store::StoreKind;
+use crate::utils;
+
+/// The command line of the golinks binary.
+#[derive(Debug, Parser)]
+#[command(name = "golinks", version, about = "A simple go links service")]
+pub struct Cli {
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+impl Cli {
+    /// Gets the subcommand to run. Running the binary without one serves the links, so
+    /// existing deployments keep working unchanged.
+    pub fn command(self) -> Command {
+        self.command
+            .unwrap_or_else(|| Command::Serve(ServeArgs::default()))
+    }
+}
+
+#[derive(Debug, Subcommand)]
+pub enum Command {
+    /// Serve the links (the default). Flags override the GOLINKS_* environment variables.
+    Serve(ServeArgs),
+    /// Check that links files are valid, exiting with an error if they are not
+    Validate(LinksArgs),
+    /// Print what a path would redirect to
+    Resolve {
+        #[command(flatten)]
+        links: LinksArgs,
+        /// The path to resolve, optionally with a query string
+        path: String,
+    },
+    /// List the links in links files
+    List {
+        #[command(flatten)]
+        links: LinksArgs,
+        /// How to print the links
+        #[arg(long, value_enum, default_value_t)]
+        format: OutputFormat,
+    },
+}
+
+/// The links files a subcommand loads, the same way the server does.
+#[derive(Debug, Args)]
+pub struct LinksArgs {
+    /// The links files to load, as a comma-separated list of files, directories and globs
+    links: String,
+
+    /// How to handle a link defined in more than one links file
+    #[arg(long, value_enum, default_value_t)]
+    conflicts: Conflicts,
+
+    /// The format of the links files, instead of detecting it from their extension
+    #[arg(long, value_enum)]
+    links_format: Option<FileFormat>,
+}
+
+impl LinksArgs {
+    fn load(&self) -> Result<(Routes, Vec<Diagnostic>), String> {
+        loader::load_and_lint(
+            &self.links,
+            self.conflicts.into(),
+            self.links_format.map(Into::into),
+        )
+        .map_err(|err| match (&err, err.location()) {
+            (LoadError::Parse { .. }, Some((line, column))) => {
+                format!("{} (line {}, column {})", err, line, column)
+            }
+            _ => err.to_string(),
+        })
+    }
+}
+
+/// Flags of the `serve` subcommand. Every flag is optional and only overrides the
+/// configuration from the environment when given.
+#[derive(Debug, Default, Args)]
+pub struct ServeArgs {
+    /// The links files, as a comma-separated list of files, directories and globs [env: GOLINKS_ROUTES]
+    #[arg(long)]
+    links_file: Option<String>,
+
+    /// Reload the links when the links file changes [env: GOLINKS_WATCH]
+    #[arg(long, num_args = 0..=1, require_equals = true, default_missing_value = "true")]
+    watch: Option<bool>,
+
+    /// How long to wait for changes to the links files to settle before reloading them, in milliseconds [env: GOLINKS_DEBOUNCE_MS]
+    #[arg(long)]
+    debounce_ms: Option<u64>,
+
+    /// A URL to poll links from, alongside the links files [env: GOLINKS_REMOTE_URL]
+    #[arg(long)]
+    remote_url: Option<String>,
+
+    /// How often to poll the remote links, in seconds [env: GOLINKS_REMOTE_INTERVAL_SECS]
+    #[arg(long)]
+    remote_interval_secs: Option<u64>,
+
+    /// A checkout of a git repository to read links files from, alongside the links files [env: GOLINKS_GIT_REPO]
+    #[arg(long)]
+    git_repo: Option<String>,
+
+    /// The ref of the git repository to read the links files at [env: GOLINKS_GIT_REF]
+    #[arg(long)]
+    git_ref: Option<String>,
+
+    /// The links files in the git repository, as a comma-separated list of files, directories and globs [env: GOLINKS_GIT_PATHS]
+    #[arg(long)]
+    git_paths: Option<String>,
+
+    /// How often to check the git ref for a new commit, in seconds [env: GOLINKS_GIT_INTERVAL_SECS]
+    #[arg(long)]
+    git_interval_secs: Option<u64>,
+
+    /// Fetch the git repository before checking the ref [env: GOLINKS_GIT_FETCH]
+    #[arg(long, num_args = 0..=1, require_equals = true, default_missing_value = "true")]
+    git_fetch: Option<bool>,
+
+    /// Where the links are stored [env: GOLINKS_STORE]
+    #[arg(long, value_enum)]
+    store: Option<Store>,
+
+    /// The SQLite database the links are stored in, with `--store sqlite` [env: GOLINKS_DATABASE]
+    #[arg(long)]
+    database: Option<String>,
+
+    /// Add the duration of every request to its response [env: GOLINKS_PROFILING]
+    #[arg(long, num_args = 0..=1, require_equals = true, default_missing_value = "true")]
+    profiling: Option<bool>,
+
+    /// The most verbose level to log at [env: GOLINKS_LOG_LEVEL]
+    #[arg(long, value_parser = ["off", "error", "warn", "info", "debug", "trace"])]
+    log_level: Option<String>,
+
+    /// How to handle a link defined in more than one links file [env: GOLINKS_CONFLICTS]
+    #[arg(long, value_enum)]
+    conflicts: Option<Conflicts>,
+
+    /// The format of the links files, instead of detecting it from their extension [env: GOLINKS_FORMAT]
+    #[arg(long, value_enum)]
+    links_format: Option<FileFormat>,
+
+    /// Include logs from dependencies [env: GOLINKS_LOG_ALL]
+    #[arg(long, num_args = 0..=1, require_equals = true, default_missing_value = "true")]
+    log_all: Option<bool>,
+
+    /// The format of timestamps in the logs [env: GOLINKS_TIME_FORMAT]
+    #[arg(long)]
+    time_format: Option<String>,
+}
+
+impl ServeArgs {
+    /// Applies the flags that were given on top of `configs`.
+    pub fn apply(&self, configs: &mut AppConfig) {
+        if let Some(links_file) = &self.links_file {
+            configs.set_links_file(links_file);
+        }
+        if let Some(conflicts) = self.conflicts {
+            configs.set_conflicts(conflicts.into());
+        }
+        if let Some(format) = self.links_format {
+            configs.set_format(format.into());
+        }
+        if let Some(watch) = self.watch {
+            configs.enable_watch(watch);
+        }
+        if let Some(debounce_ms) = self.debounce_ms {
+            configs.set_debounce_ms(debounce_ms);
+        }
+        if let Some(remote_url) = &self.remote_url {
+            configs.set_remote_url(remote_url);
+        }
+        if let Some(remote_interval_secs) = self.remote_interval_secs {
+            configs.set_remote_interval_secs(remote_interval_secs);
+        }
+        if let Some(git_repo) = &self.git_repo {
+            configs.set_git_repo(git_repo);
+        }
+        if let Some(git_ref) = &self.git_ref {
+            configs.set_git_ref(git_ref);
+        }
+        if let Some(git_paths) = &self.git_paths {
+            configs.set_git_paths(git_paths);
+        }
+        if let Some(git_interval_secs) = self.git_interval_secs {
+            configs.set_git_interval_secs(git_interval_secs);
+        }
+        if let Some(git_fetch) = self.git_fetch {
+            configs.enable_git_fetch(git_fetch);
+        }
+        if let Some(store) = self.store {
+            configs.set_store(store.into());
+        }
+        if let Some(database) = &self.database {
+            configs.set_database(database);
+        }
+        if let Some(profiling) = self.profiling {
+            configs.enable_profiling(profiling);
+        }
+        if let Some(log_level) = &self.log_level {
+            configs.set_log_level(log_level);
+        }
+        if let Some(log_all) = self.log_all {
+            configs.enable_log_all(log_all);
+        }
+        if let Some(time_format) = &self.time_format {
+            configs.set_time_format(time_format);
+        }
+    }
+}
+
+/// How a link defined in more than one links file is handled, as given on the command
+/// line.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum Conflicts {
+    /// The links files are rejected.
+    #[default]
+    Error,
+    /// The definition from the file loaded first is used.
+    FirstWins,
+    /// The definition from the file loaded last is used.
+    LastWins,
+}
+
+impl From<Conflicts> for ConflictPolicy {
+    fn from(conflicts: Conflicts) -> Self {
+        match conflicts {
+            Conflicts::Error => ConflictPolicy::Error,
+            Conflicts::FirstWins => ConflictPolicy::FirstWins,
+            Conflicts::LastWins => ConflictPolicy::LastWins,
+        }
+    }
+}
+
+/// The format of the links files, as given on the command line.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum FileFormat {
+    Yaml,
+    Json,
+    Toml,
+}
+
+impl From<FileFormat> for LinksFormat {
+    fn from(format: FileFormat) -> Self {
+        match format {
+            FileFormat::Yaml => LinksFormat::Yaml,
+            FileFormat::Json => LinksFormat::Json,
+            FileFormat::Toml => LinksFormat::Toml,
+        }
+    }
+}
+
+/// Where the links are stored, as given on the command line.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum Store {
+    /// The links files. Changes are written back to the links file of the link.
+    File,
+    /// An SQLite database.
+    Sqlite,
+    /// Memory, starting with the links of the links files. Changes are lost when the
+    /// service stops.
+    Memory,
+}
+
+impl From<Store> for StoreKind {
+    fn from(store: Store) -> Self {
+        match store {
+            Store::File => StoreKind::File,
+            Store::Sqlite => StoreKind::Sqlite,
+            Store::Memory => StoreKind::Memory,
+        }
+    }
+}
+
+/// How the `list` subcommand prints the links.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum OutputFormat {
+    #[default]
+    Table,
+    Json,
+}
+
+/// Struct for serializing every link of a links file.
+#[derive(Serialize)]
+#[serde(crate = "rocket::serde")]
+struct LinkList<'a> {
+    links: Vec<LinkEntry>,
+    patterns: &'a [Pattern],
+}
+
+/// Loads links files the same way the server does, describing the links in them along
+/// with any warnings about them.
+pub fn validate(links: &LinksArgs) -> Result<String, String> {
+    let (routes, warnings) = links.load()?;
+
+    let mut output = String::new();
+    for warning in &warnings {
+        let _ = writeln!(output, "{}", warning);
+    }
+    let _ = write!(
+        output,
+        "{} is valid: {} links, {} warnings",
+        links.links,
+        routes.len(),
+        warnings.len()
+    );
+
+    Ok(output)
+}
+
+/// Describes what `path` redirects to with the links in `links`.
+pub fn resolve(links: &LinksArgs, path: &str) -> Result<String, String> {
+    describe_resolution(&links.load()?.0, path)
+}
+
+/// Prints the links in `links`, sorted by key, followed by the pattern links.
+pub fn list(links: &LinksArgs, format: OutputFormat) -> Result<String, String> {
+    list_links(&links.load()?.0, format)
+}
+
+/// Describes what `path` redirects to. The path is decoded the way the server decodes
+/// the paths of requests, so that it resolves to what the server would redirect to.
+fn describe_resolution(routes: &Routes, path: &str) -> Result<String, String> {
+    let uri = Origin::parse_owned(format!("/{}", path.trim_start_matches('/')))
+        .map_err(|err| format!("'{}' is not a valid path: {}", path, err))?;
+    let query = uri
+        .query()
+        .map(|query| link::query_params(query.as_str()))
+        .unwrap_or_default();
+
+    let decoded = utils::decode_path(&uri);
+    let resolution = decoded
+        .as_deref()
+        .and_then(|decoded| routes.resolve(decoded))
+        .ok_or_else(|| format!("'{}' is not a known link", path))?;
+
+    Ok(format!(
+        "url:    {}\nstatus: {}\nkey:    {}\nrule:   {}",
+        resolution.destination(&query),
+        resolution.link.status.code(),
+        resolution.key,
+        resolution.rule,
+    ))
+}
+
+fn list_links(routes: &Routes, format: OutputFormat) -> Result<String, String> {
+    let mut links: Vec<_> = routes.links().collect();
+    links.sort_by_key(|(key, _)| *key);
+
+    if format == OutputFormat::Json {
+        let list = LinkList {
+            links: links
+                .into_iter()
+                .map(|(key, link)| LinkEntry {
+                    key: key.to_string(),
+                    link: link.clone(),
+                    revision: None,
+                })
+                .collect(),
+            patterns: routes.patterns(),
+        };
+
+        return json::to_pretty_string(&list).map_err(|err| err.to_string());
+    }
+
+    let row = |key: &str, link: &Link| {
+        [
+            key.to_string(),
+            link.status.code().to_string(),
+            link.source.clone().unwrap_or_default(),
+            link.url.clone(),
+        ]
+    };
+
+    let mut rows: Vec<[String; 4]> = vec![["KEY", "STATUS", "SOURCE", "TARGET"].map(String::from)];
+    rows.extend(links.into_iter().map(|(key, link)| row(key, link)));
+    rows.extend(
+        routes
+            .patterns()
+            .iter()
+            .map(|pattern| row(pattern.source(), pattern.link())),
+    );
+
+    let widths = [0, 1, 2].map(|column| {
+        rows.iter()
+            .map(|row| row[column].chars().count())
+            .max()
+            .unwrap_or(0)
+    });
+
+    let mut table = String::new();
+    for [key, status, source, target] in &rows {
+        let _ = writeln!(
+            table,
+            "{:key_width$}  {:status_width$}  {:source_width$}  {}",
+            key,
+            status,
+            source,
+            target,
+            key_width = widths[0],
+            status_width = widths[1],
+            source_width = widths[2],
+        );
+    }
+
+    Ok(table.trim_end().to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn routes() -> Routes {
+        Routes::with_routes(HashMap::from([
+            ("e".to_string(), "https://example.com".to_string()),
+            (
+                "search".to_string(),
+                "https://google.com/search?q={q}".to_string(),
+            ),
+        ]))
+        .with_patterns(vec![Pattern::regex(
+            r"pr/(\d+)",
+            "https://github.com/org/repo/pull/{1}",
+        )
+        .unwrap()])
+    }
+
+    #[test]
+    fn test_no_subcommand_serves() {
+        let cli = Cli::try_parse_from(["golinks"]).unwrap();
+        assert!(matches!(cli.command(), Command::Serve(_)));
+    }
+
+    #[test]
+    fn test_serve_flags_override_configs() {
+        let cli = Cli::try_parse_from([
+            "golinks",
+            "serve",
+            "--links-file",
+            "other.yaml",
+            "--watch",
+            "--profiling=false",
+            "--conflicts",
+            "last-wins",
+        ])
+        .unwrap();
+        let Command::Serve(args) = cli.command() else {
+            panic!("Expected the serve subcommand");
+        };
+
+        let mut configs = AppConfig::default();
+        configs.enable_profiling(true);
+        args.apply(&mut configs);
+
+        assert_eq!(configs.links_file(), "other.yaml");
+        assert!(configs.watch());
+        assert!(!configs.profiling_enabled());
+        assert_eq!(configs.conflicts(), ConflictPolicy::LastWins);
+    }
+
+    #[test]
+    fn test_describe_resolution() {
+        let description = describe_resolution(&routes(), "search?q=rust").unwrap();
+        assert!(description.contains("url:    https://google.com/search?q=rust"));
+        assert!(description.contains("rule:   exact"));
+
+        let description = describe_resolution(&routes(), "/pr/42").unwrap();
+        assert!(description.contains("https://github.com/org/repo/pull/42"));
+        assert!(description.contains("rule:   pattern"));
+
+        assert!(describe_resolution(&routes(), "missing").is_err());
+
+        // Paths are decoded the way the server decodes them.
+        let description = describe_resolution(&routes(), "e/a%20b/c").unwrap();
+        assert!(description.contains("url:    https://example.com/a%20b/c"));
+        let description = describe_resolution(&routes(), "%65").unwrap();
+        assert!(description.contains("rule:   exact"));
+        assert!(describe_resolution(&routes(), "e/a%2Fb").is_err());
+    }
+
+    #[test]
+    fn test_list_table() {
+        let table = list_links(&routes(), OutputFormat::Table).unwrap();
+        let lines: Vec<&str> = table.lines().collect();
+
+        assert_eq!(lines[0], "KEY       STATUS  SOURCE  TARGET");
+        assert_eq!(lines[1], "e         307             https://example.com");
+        assert_eq!(
+            lines[3],
+            r"pr/(\d+)  307             https://github.com/org/repo/pull/{1}"
+        );
+    }
+
+    #[test]
+    fn test_list_json() {
+        let output = list_links(&routes(), OutputFormat::Json).unwrap();
+        let value: json::Value = json::from_str(&output).unwrap();
+
+        assert_eq!(value["links"][0]["key"], "e");
+        assert_eq!(value["links"][1]["url"], "https://google.com/search?q={q}");
+        assert_eq!(value["patterns"][0]["regex"], r"pr/(\d+)");
+    }
+}
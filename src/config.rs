@@ -1,6 +1,9 @@
 use config::{Config, ConfigError, Environment};
 use serde::{Deserialize, Serialize};
 
+use crate::models::{ConflictPolicy, LinksFormat};
+use crate::store::StoreKind;
+
 /// A struct defining default behaviour and deserialization
 /// of values for configuring the application.
 #[derive(Clone, Debug, Default, Serialize, Deserialize, PartialEq, Eq)]
@@ -20,8 +23,47 @@ pub struct AppConfig {
     #[serde(default = "_default_links_file")]
     routes: String,
 
+    #[serde(default)]
+    conflicts: ConflictPolicy,
+
+    #[serde(default)]
+    format: Option<LinksFormat>,
+
     #[serde(default = "_default_false")]
     watch: bool,
+
+    #[serde(default = "_default_debounce_ms")]
+    debounce_ms: u64,
+
+    #[serde(default)]
+    remote_url: Option<String>,
+
+    #[serde(default = "_default_remote_interval_secs")]
+    remote_interval_secs: u64,
+
+    #[serde(default)]
+    git_repo: Option<String>,
+
+    #[serde(default = "_default_git_ref")]
+    git_ref: String,
+
+    #[serde(default = "_default_links_file")]
+    git_paths: String,
+
+    #[serde(default = "_default_remote_interval_secs")]
+    git_interval_secs: u64,
+
+    #[serde(default = "_default_false")]
+    git_fetch: bool,
+
+    #[serde(default)]
+    store: StoreKind,
+
+    #[serde(default = "_default_database")]
+    database: String,
+
+    #[serde(default, skip_serializing)]
+    admin_token: Option<String>,
 }
 
 impl AppConfig {
@@ -51,6 +93,99 @@ impl AppConfig {
         self.profiling = val;
     }
 
+    /// Sets the flag to enable/disable watching the links file
+    pub fn enable_watch(&mut self, val: bool) {
+        self.watch = val;
+    }
+
+    /// Sets the token admin requests must carry
+    pub fn set_admin_token(&mut self, val: &str) {
+        self.admin_token = Some(val.to_string());
+    }
+
+    /// Sets how long to wait for changes to the links files to
+    /// settle before reloading them, in milliseconds
+    pub fn set_debounce_ms(&mut self, val: u64) {
+        self.debounce_ms = val;
+    }
+
+    /// Sets the URL to poll links from, alongside the links files
+    pub fn set_remote_url(&mut self, val: &str) {
+        self.remote_url = Some(val.to_string());
+    }
+
+    /// Sets how often the remote links are polled, in seconds
+    pub fn set_remote_interval_secs(&mut self, val: u64) {
+        self.remote_interval_secs = val;
+    }
+
+    /// Sets the checkout of the git repository to read links files from
+    pub fn set_git_repo(&mut self, val: &str) {
+        self.git_repo = Some(val.to_string());
+    }
+
+    /// Sets the ref of the git repository to read the links files at
+    pub fn set_git_ref(&mut self, val: &str) {
+        self.git_ref = val.to_string();
+    }
+
+    /// Sets the links files in the git repository
+    pub fn set_git_paths(&mut self, val: &str) {
+        self.git_paths = val.to_string();
+    }
+
+    /// Sets how often the git ref is checked for a new commit, in seconds
+    pub fn set_git_interval_secs(&mut self, val: u64) {
+        self.git_interval_secs = val;
+    }
+
+    /// Sets the flag to enable/disable fetching the git repository
+    /// before checking the ref
+    pub fn enable_git_fetch(&mut self, val: bool) {
+        self.git_fetch = val;
+    }
+
+    /// Sets where the links are stored
+    pub fn set_store(&mut self, val: StoreKind) {
+        self.store = val;
+    }
+
+    /// Sets the SQLite database the links are stored in
+    pub fn set_database(&mut self, val: &str) {
+        self.database = val.to_string();
+    }
+
+    /// Sets the flag to include/exclude logs from library dependencies
+    pub fn enable_log_all(&mut self, val: bool) {
+        self.log_all = val;
+    }
+
+    /// Sets the format that times should appear with in the logs
+    pub fn set_time_format(&mut self, val: &str) {
+        self.time_format = val.to_string();
+    }
+
+    /// Sets the log level filter to use for logging
+    pub fn set_log_level(&mut self, val: &str) {
+        self.log_level = val.to_string();
+    }
+
+    /// Sets the files containing the links
+    pub fn set_links_file(&mut self, val: &str) {
+        self.routes = val.to_string();
+    }
+
+    /// Sets how links defined in more than one links file are handled
+    pub fn set_conflicts(&mut self, val: ConflictPolicy) {
+        self.conflicts = val;
+    }
+
+    /// Sets the format every links file is read in, instead of
+    /// detecting it from the extension of the file
+    pub fn set_format(&mut self, val: LinksFormat) {
+        self.format = Some(val);
+    }
+
     /// Gets a value specifying the format that times should appear
     /// with in the logs
     pub fn time_format(&self) -> &str {
@@ -71,12 +206,90 @@ impl AppConfig {
         }
     }
 
-    /// Gets the path to the file containing the links
-    /// to use for redirection
+    /// Gets the files containing the links to use for
+    /// redirection, as a comma-separated list of files,
+    /// directories and globs
     pub fn links_file(&self) -> &str {
         &self.routes
     }
 
+    /// Gets a value indicating how links that are defined
+    /// in more than one links file are handled
+    pub fn conflicts(&self) -> ConflictPolicy {
+        self.conflicts
+    }
+
+    /// Gets the token admin requests must carry, if the admin
+    /// endpoints are enabled
+    pub fn admin_token(&self) -> Option<&str> {
+        self.admin_token
+            .as_deref()
+            .filter(|token| !token.is_empty())
+    }
+
+    /// Gets how long to wait for changes to the links files
+    /// to settle before reloading them
+    pub fn debounce(&self) -> std::time::Duration {
+        std::time::Duration::from_millis(self.debounce_ms)
+    }
+
+    /// Gets the URL to poll links from, alongside the links
+    /// files, if any
+    pub fn remote_url(&self) -> Option<&str> {
+        self.remote_url.as_deref().filter(|url| !url.is_empty())
+    }
+
+    /// Gets how long to wait between polls of the remote links
+    pub fn remote_interval(&self) -> std::time::Duration {
+        std::time::Duration::from_secs(self.remote_interval_secs)
+    }
+
+    /// Gets the checkout of the git repository to read links
+    /// files from, if any
+    pub fn git_repo(&self) -> Option<&str> {
+        self.git_repo.as_deref().filter(|repo| !repo.is_empty())
+    }
+
+    /// Gets the ref of the git repository the links files are
+    /// read at
+    pub fn git_ref(&self) -> &str {
+        &self.git_ref
+    }
+
+    /// Gets the links files in the git repository, as a
+    /// comma-separated list of files, directories and globs
+    pub fn git_paths(&self) -> &str {
+        &self.git_paths
+    }
+
+    /// Gets how long to wait between checks of the git ref
+    pub fn git_interval(&self) -> std::time::Duration {
+        std::time::Duration::from_secs(self.git_interval_secs)
+    }
+
+    /// Gets a value indicating whether the git repository is
+    /// fetched before checking the ref
+    pub fn git_fetch(&self) -> bool {
+        self.git_fetch
+    }
+
+    /// Gets where the links are stored
+    pub fn store(&self) -> StoreKind {
+        self.store
+    }
+
+    /// Gets the SQLite database the links are stored in, when
+    /// they are stored in SQLite
+    pub fn database(&self) -> &str {
+        &self.database
+    }
+
+    /// Gets the format every links file is read in, if it
+    /// should not be detected from the extension of the file
+    pub fn format(&self) -> Option<LinksFormat> {
+        self.format
+    }
+
     /// Gets a value indicating whether logs should be filtered to
     /// only be emitted from the service (false), or if all logs generated
     /// by library dependencies should be included
@@ -101,6 +314,22 @@ fn _default_links_file() -> String {
     "links.yaml".to_string()
 }
 
+fn _default_debounce_ms() -> u64 {
+    500
+}
+
+fn _default_remote_interval_secs() -> u64 {
+    60
+}
+
+fn _default_database() -> String {
+    "links.db".to_string()
+}
+
+fn _default_git_ref() -> String {
+    "HEAD".to_string()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -146,4 +375,106 @@ mod tests {
         assert!(configs.profiling_enabled());
         assert!(configs.profiling);
     }
+
+    #[test]
+    #[serial]
+    fn test_links_files() {
+        let values = HashMap::from([
+            ("GOLINKS_ROUTES", "/conf/infra.yaml,/conf/teams"),
+            ("GOLINKS_CONFLICTS", "last-wins"),
+            ("GOLINKS_FORMAT", "toml"),
+        ]);
+
+        let configs = with_env_vars(values, AppConfig::build);
+
+        assert_eq!(configs.links_file(), "/conf/infra.yaml,/conf/teams");
+        assert_eq!(configs.conflicts(), ConflictPolicy::LastWins);
+        assert_eq!(configs.format(), Some(LinksFormat::Toml));
+        assert_eq!(AppConfig::default().format(), None);
+    }
+
+    #[test]
+    #[serial]
+    fn test_debounce() {
+        let configs = with_env_vars(HashMap::new(), AppConfig::build);
+        assert_eq!(configs.debounce().as_millis(), 500);
+
+        let values = HashMap::from([("GOLINKS_DEBOUNCE_MS", "2000")]);
+        let configs = with_env_vars(values, AppConfig::build);
+        assert_eq!(configs.debounce().as_secs(), 2);
+    }
+
+    #[test]
+    #[serial]
+    fn test_admin_token() {
+        let configs = with_env_vars(HashMap::new(), AppConfig::build);
+        assert_eq!(configs.admin_token(), None);
+
+        let values = HashMap::from([("GOLINKS_ADMIN_TOKEN", "s3cret")]);
+        let configs = with_env_vars(values, AppConfig::build);
+        assert_eq!(configs.admin_token(), Some("s3cret"));
+
+        let serialized = rocket::serde::json::to_string(&configs).unwrap();
+        assert!(!serialized.contains("s3cret"));
+    }
+
+    #[test]
+    #[serial]
+    fn test_remote() {
+        let configs = with_env_vars(HashMap::new(), AppConfig::build);
+        assert_eq!(configs.remote_url(), None);
+        assert_eq!(configs.remote_interval().as_secs(), 60);
+
+        let values = HashMap::from([
+            ("GOLINKS_REMOTE_URL", "https://links.example.com/links.yaml"),
+            ("GOLINKS_REMOTE_INTERVAL_SECS", "300"),
+        ]);
+        let configs = with_env_vars(values, AppConfig::build);
+        assert_eq!(
+            configs.remote_url(),
+            Some("https://links.example.com/links.yaml")
+        );
+        assert_eq!(configs.remote_interval().as_secs(), 300);
+    }
+
+    #[test]
+    #[serial]
+    fn test_git() {
+        let configs = with_env_vars(HashMap::new(), AppConfig::build);
+        assert_eq!(configs.git_repo(), None);
+        assert_eq!(configs.git_ref(), "HEAD");
+        assert_eq!(configs.git_paths(), "links.yaml");
+        assert_eq!(configs.git_interval().as_secs(), 60);
+        assert!(!configs.git_fetch());
+
+        let values = HashMap::from([
+            ("GOLINKS_GIT_REPO", "/srv/links"),
+            ("GOLINKS_GIT_REF", "origin/main"),
+            ("GOLINKS_GIT_PATHS", "links.yaml,infra=teams/infra/"),
+            ("GOLINKS_GIT_INTERVAL_SECS", "30"),
+            ("GOLINKS_GIT_FETCH", "true"),
+        ]);
+        let configs = with_env_vars(values, AppConfig::build);
+        assert_eq!(configs.git_repo(), Some("/srv/links"));
+        assert_eq!(configs.git_ref(), "origin/main");
+        assert_eq!(configs.git_paths(), "links.yaml,infra=teams/infra/");
+        assert_eq!(configs.git_interval().as_secs(), 30);
+        assert!(configs.git_fetch());
+    }
+
+    #[test]
+    #[serial]
+    fn test_store() {
+        let configs = with_env_vars(HashMap::new(), AppConfig::build);
+        assert_eq!(configs.store(), StoreKind::File);
+        assert_eq!(configs.database(), "links.db");
+
+        let values = HashMap::from([
+            ("GOLINKS_STORE", "sqlite"),
+            ("GOLINKS_DATABASE", "/data/links.db"),
+        ]);
+        let configs = with_env_vars(values, AppConfig::build);
+        assert_eq!(configs.store(), StoreKind::Sqlite);
+        assert_eq!(configs.database(), "/data/links.db");
+    }
 }
@@ -0,0 +1,839 @@
+use std::fmt;
+use std::fs;
+use std::io::{self, Write};
+use std::path::Path;
+
+use indexmap::IndexMap;
+use rocket::serde::json::serde_json::{self, Value};
+use rocket::serde::{Deserialize, Serialize};
+
+use crate::link::Link;
+use crate::lint::is_within;
+use crate::loader::{self, LinksDocument, LoadError};
+use crate::models::{ConflictPolicy, LinksFormat, Routes};
+
+/// A change to one link, made through the admin endpoints.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum LinkEdit {
+    /// Adds a link that does not exist yet.
+    Create(String, Link),
+    /// Replaces an existing link.
+    Update(String, Link),
+    /// Replaces a link, adding it if it does not exist yet.
+    Upsert(String, Link),
+    /// Removes an existing link.
+    Delete(String),
+}
+
+impl LinkEdit {
+    /// Gets the key of the link being changed.
+    pub fn key(&self) -> &str {
+        match self {
+            LinkEdit::Create(key, _)
+            | LinkEdit::Update(key, _)
+            | LinkEdit::Upsert(key, _)
+            | LinkEdit::Delete(key) => key,
+        }
+    }
+
+    /// Gets the new definition of the link, if it is not being removed.
+    pub fn link(&self) -> Option<&Link> {
+        match self {
+            LinkEdit::Create(_, link) | LinkEdit::Update(_, link) | LinkEdit::Upsert(_, link) => {
+                Some(link)
+            }
+            LinkEdit::Delete(_) => None,
+        }
+    }
+}
+
+/// Errors that can occur while changing a link.
+#[derive(Debug)]
+pub enum EditError {
+    /// The link to change does not exist.
+    NotFound(String),
+    /// The link to create already exists.
+    Exists(String),
+    /// The link was changed since the revision the change was made against.
+    Conflict {
+        key: String,
+        expected: u64,
+        actual: u64,
+    },
+    /// The link was not loaded from a links file on disk, such as remote links or links
+    /// read from a git repository.
+    ReadOnly { key: String, source: String },
+    /// There is no links file the new link can be written to.
+    NoLinksFile(String),
+    /// The links files could not be loaded, either before the change or with it.
+    Load(LoadError),
+    /// The links file is written in a way the link cannot be changed in without
+    /// writing the whole file again, which would lose its comments and formatting.
+    Unsupported { path: String, message: String },
+    /// The links file could not be changed, or written back.
+    Write { path: String, message: String },
+}
+
+impl fmt::Display for EditError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EditError::NotFound(key) => write!(f, "No link is registered for '{}'", key),
+            EditError::Exists(key) => write!(f, "A link is already registered for '{}'", key),
+            EditError::Conflict {
+                key,
+                expected,
+                actual,
+            } => write!(
+                f,
+                "The link '{}' was changed since revision {}, it is at revision {}",
+                key, expected, actual
+            ),
+            EditError::ReadOnly { key, source } => write!(
+                f,
+                "The link '{}' comes from {}, which is not a links file that can be changed",
+                key, source
+            ),
+            EditError::NoLinksFile(key) => {
+                write!(f, "There is no links file to add the link '{}' to", key)
+            }
+            EditError::Load(err) => write!(f, "{}", err),
+            EditError::Unsupported { path, message } => {
+                write!(f, "Unable to change {} in place: {}", path, message)
+            }
+            EditError::Write { path, message } => {
+                write!(f, "Unable to write {}: {}", path, message)
+            }
+        }
+    }
+}
+
+impl std::error::Error for EditError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            EditError::Load(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<LoadError> for EditError {
+    fn from(err: LoadError) -> Self {
+        EditError::Load(err)
+    }
+}
+
+/// Why a link could not be changed in the contents of a links file.
+#[derive(Debug, PartialEq, Eq)]
+pub enum ChangeError {
+    /// The links file could not be parsed, or has no routes.
+    Invalid(String),
+    /// The link cannot be changed without writing the whole file again.
+    Unsupported(String),
+}
+
+/// The links after a change was written back to its links file.
+#[derive(Debug)]
+pub struct Applied {
+    /// The key of the changed link, without leading or trailing slashes.
+    pub key: String,
+    /// The link as it is served after the change, or as it was served before it was
+    /// removed.
+    pub link: Link,
+    pub routes: Routes,
+    /// The content hash of the links files, along with `extra`, after the change.
+    pub hash: String,
+    /// The links file that was changed.
+    pub path: String,
+}
+
+/// Applies a change to the links files in `sources`, which are loaded along with the
+/// `extra` documents of remote and git sources. The changed links file is only written
+/// back, replacing it atomically, once all the links load with the change.
+///
+/// Links are added to the links file whose namespace is the longest prefix of their
+/// key, or to the first links file without a namespace.
+pub fn apply(
+    sources: &str,
+    format: Option<LinksFormat>,
+    conflicts: ConflictPolicy,
+    extra: &[LinksDocument],
+    edit: &LinkEdit,
+) -> Result<Applied, EditError> {
+    let files = loader::read_documents(sources, format)?;
+    let mut documents: Vec<LinksDocument> = files.iter().chain(extra).cloned().collect();
+    let (current, _) = loader::load_documents(&documents, conflicts, sources)?;
+
+    let key = edit.key().trim_matches('/').to_string();
+    let existing = current.fetch(&key).cloned();
+    let index = match (edit, existing) {
+        (LinkEdit::Create(..), Some(_)) => return Err(EditError::Exists(key)),
+        (LinkEdit::Create(..) | LinkEdit::Upsert(..), None) => target_file(&files, &key)?,
+        (_, None) => return Err(EditError::NotFound(key)),
+        (_, Some(link)) => {
+            let source = link.source.unwrap_or_default();
+            files
+                .iter()
+                .position(|file| file.path == source)
+                .ok_or(EditError::ReadOnly {
+                    key: key.clone(),
+                    source,
+                })?
+        }
+    };
+
+    let document = &documents[index];
+    let file_key = match loader::document_namespace(document)? {
+        Some(namespace) if key == namespace => String::new(),
+        Some(namespace) => key[namespace.len() + 1..].to_string(),
+        None => key.clone(),
+    };
+
+    let contents = change(document.format, &document.contents, &file_key, edit.link()).map_err(
+        |err| match err {
+            ChangeError::Invalid(message) => EditError::Write {
+                path: document.path.clone(),
+                message,
+            },
+            ChangeError::Unsupported(message) => EditError::Unsupported {
+                path: document.path.clone(),
+                message,
+            },
+        },
+    )?;
+
+    let path = document.path.clone();
+    documents[index].contents = contents;
+    let (routes, _) = loader::load_documents(&documents, conflicts, sources)?;
+    let link = match routes.fetch(&key) {
+        Some(link) => link.clone(),
+        None => current.fetch(&key).cloned().unwrap_or_default(),
+    };
+
+    write_atomically(Path::new(&path), &documents[index].contents).map_err(|err| {
+        EditError::Write {
+            path: path.clone(),
+            message: err.to_string(),
+        }
+    })?;
+
+    Ok(Applied {
+        key,
+        link,
+        routes,
+        hash: loader::documents_hash(&documents),
+        path,
+    })
+}
+
+/// Finds the links file a new link with `key` is added to.
+fn target_file(files: &[LinksDocument], key: &str) -> Result<usize, EditError> {
+    let mut target: Option<(usize, usize)> = None;
+
+    for (index, file) in files.iter().enumerate() {
+        let length = match loader::document_namespace(file)? {
+            Some(namespace) if is_within(key, &namespace) => namespace.len() + 1,
+            Some(_) => continue,
+            None => 0,
+        };
+        if target.is_none_or(|(_, longest)| length > longest) {
+            target = Some((index, length));
+        }
+    }
+
+    target
+        .map(|(index, _)| index)
+        .ok_or_else(|| EditError::NoLinksFile(key.to_string()))
+}
+
+/// Replaces `path` with `contents` by writing them to a temporary file next to it and
+/// renaming it over `path`, so that readers never see a partly written file. A `path`
+/// that is a symlink keeps pointing to the same file.
+fn write_atomically(path: &Path, contents: &str) -> io::Result<()> {
+    let target = fs::canonicalize(path)?;
+    let name = target
+        .file_name()
+        .and_then(|name| name.to_str())
+        .unwrap_or("links");
+    let temporary = target.with_file_name(format!(".{}.{}.tmp", name, std::process::id()));
+
+    let written = (|| {
+        let mut file = fs::File::create(&temporary)?;
+        file.write_all(contents.as_bytes())?;
+        file.sync_all()?;
+        fs::set_permissions(&temporary, fs::metadata(&target)?.permissions())?;
+        fs::rename(&temporary, &target)
+    })();
+
+    if written.is_err() {
+        let _ = fs::remove_file(&temporary);
+    }
+    written
+}
+
+/// A JSON value whose objects keep the order of their keys, which `Value` does not, so
+/// that links files are written back in the order they were written in.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(crate = "rocket::serde", untagged)]
+enum OrderedValue {
+    Object(IndexMap<String, OrderedValue>),
+    Array(Vec<OrderedValue>),
+    Other(Value),
+}
+
+impl OrderedValue {
+    /// Serializes `value` to JSON, keeping the order its fields are serialized in.
+    fn of<T: Serialize>(value: &T) -> Self {
+        serde_json::to_string(value)
+            .and_then(|json| serde_json::from_str(&json))
+            .unwrap_or(OrderedValue::Other(Value::Null))
+    }
+}
+
+/// Gets how a link is written in a links file: the bare URL when every other setting
+/// has its default value, or else the object form without the settings that do.
+fn file_value(link: &Link) -> OrderedValue {
+    let defaults = match OrderedValue::of(&Link::default()) {
+        OrderedValue::Object(defaults) => defaults,
+        _ => IndexMap::new(),
+    };
+    let mut value = OrderedValue::of(&Link {
+        source: None,
+        ..link.clone()
+    });
+
+    if let OrderedValue::Object(fields) = &mut value {
+        fields.retain(|name, value| name == "url" || defaults.get(name) != Some(value));
+        if fields.len() == 1 {
+            if let Some(url) = fields.shift_remove("url") {
+                return url;
+            }
+        }
+    }
+    value
+}
+
+/// Changes the link `key` of a links file written in `format`, setting it to `link`,
+/// or removing it. Comments, formatting and the order of the other links are kept
+/// where the format allows it.
+pub fn change(
+    format: LinksFormat,
+    contents: &str,
+    key: &str,
+    link: Option<&Link>,
+) -> Result<String, ChangeError> {
+    let value = link.map(file_value);
+    match format {
+        LinksFormat::Yaml => change_yaml(contents, key, value.as_ref()),
+        LinksFormat::Json => change_json(contents, key, value).map_err(ChangeError::Invalid),
+        LinksFormat::Toml => {
+            change_toml(contents, key, value.as_ref()).map_err(ChangeError::Invalid)
+        }
+    }
+}
+
+/// Changes a YAML links file line by line, which keeps its comments. Files the lines of
+/// cannot be changed safely, such as ones with a flow mapping of routes, are not
+/// changed, since writing them again would lose their comments and formatting.
+fn change_yaml(
+    contents: &str,
+    key: &str,
+    value: Option<&OrderedValue>,
+) -> Result<String, ChangeError> {
+    let expected = changed_yaml(contents, key, value).map_err(ChangeError::Invalid)?;
+
+    match change_yaml_lines(contents, key, value) {
+        // The edited lines must mean the same as the file with the link changed.
+        Some(edited) if parse_yaml(&edited).ok().as_ref() == Some(&expected) => Ok(edited),
+        _ => Err(ChangeError::Unsupported(
+            "the routes can only be changed when they are written as a block mapping".to_string(),
+        )),
+    }
+}
+
+/// Parses a YAML links file, treating empty routes as a mapping without any link.
+fn parse_yaml(contents: &str) -> Result<serde_yaml::Value, String> {
+    let mut file: serde_yaml::Value = serde_yaml::from_str(contents).map_err(|e| e.to_string())?;
+    if let Some(routes) = file.get_mut("routes").filter(|routes| routes.is_null()) {
+        *routes = serde_yaml::Value::Mapping(serde_yaml::Mapping::new());
+    }
+    Ok(file)
+}
+
+/// Parses a YAML links file and changes the link `key` in it.
+fn changed_yaml(
+    contents: &str,
+    key: &str,
+    value: Option<&OrderedValue>,
+) -> Result<serde_yaml::Value, String> {
+    let mut file = parse_yaml(contents)?;
+    let routes = file
+        .get_mut("routes")
+        .and_then(serde_yaml::Value::as_mapping_mut)
+        .ok_or("The links file has no routes")?;
+
+    let key = serde_yaml::Value::String(key.to_string());
+    match value {
+        Some(value) => {
+            let value = serde_yaml::to_value(value).map_err(|e| e.to_string())?;
+            routes.insert(key, value);
+        }
+        None => {
+            routes.shift_remove(&key);
+        }
+    }
+
+    Ok(file)
+}
+
+/// Changes the lines of the link `key` in the block mapping of the routes of a YAML
+/// links file. Gets `None` if the lines of the file cannot be changed safely.
+fn change_yaml_lines(contents: &str, key: &str, value: Option<&OrderedValue>) -> Option<String> {
+    let lines: Vec<&str> = contents.split_inclusive('\n').collect();
+    let is_content = |line: &str| {
+        let line = line.trim();
+        !line.is_empty() && !line.starts_with('#')
+    };
+    let indent = |line: &str| line.len() - line.trim_start_matches(' ').len();
+
+    let start = lines.iter().position(|line| {
+        line.strip_prefix("routes:")
+            .is_some_and(|rest| !is_content(rest))
+    })?;
+    let end = (start + 1..lines.len())
+        .find(|&i| is_content(lines[i]) && indent(lines[i]) == 0)
+        .unwrap_or(lines.len());
+    let entry_indent = (start + 1..end)
+        .find(|&i| is_content(lines[i]))
+        .map_or(2, |i| indent(lines[i]));
+    let entries: Vec<usize> = (start + 1..end)
+        .filter(|&i| is_content(lines[i]) && indent(lines[i]) == entry_indent)
+        .collect();
+
+    // Every entry spans from its key to the last line with content before the next one.
+    let mut found = None;
+    for (position, &first) in entries.iter().enumerate() {
+        let next = entries.get(position + 1).copied().unwrap_or(end);
+        let last = (first..next).rev().find(|&i| is_content(lines[i]))?;
+
+        let entry: serde_yaml::Mapping = serde_yaml::from_str(lines[first].trim()).ok()?;
+        let (entry_key, _) = entry.into_iter().next()?;
+        if entry_key.as_str()? == key {
+            found = Some((first, last));
+        }
+    }
+
+    let rendered = match value {
+        Some(value) => {
+            let mut entry = serde_yaml::Mapping::new();
+            entry.insert(
+                serde_yaml::Value::String(key.to_string()),
+                serde_yaml::to_value(value).ok()?,
+            );
+            serde_yaml::to_string(&entry)
+                .ok()?
+                .lines()
+                .map(|line| format!("{}{}\n", " ".repeat(entry_indent), line))
+                .collect()
+        }
+        None => String::new(),
+    };
+
+    let (replaced, at) = match found {
+        Some((first, last)) => (first..last + 1, first),
+        None if value.is_some() => {
+            let after = entries
+                .last()
+                .and_then(|_| (start..end).rev().find(|&i| is_content(lines[i])))
+                .unwrap_or(start);
+            (after + 1..after + 1, after + 1)
+        }
+        None => return None,
+    };
+
+    let mut edited = String::with_capacity(contents.len() + rendered.len());
+    for line in &lines[..at] {
+        edited.push_str(line);
+    }
+    if !edited.is_empty() && !edited.ends_with('\n') {
+        edited.push('\n');
+    }
+    edited.push_str(&rendered);
+    for line in &lines[replaced.end..] {
+        edited.push_str(line);
+    }
+
+    Some(edited)
+}
+
+/// Changes a JSON links file, keeping the order of its keys and the indentation of its
+/// first line.
+fn change_json(contents: &str, key: &str, value: Option<OrderedValue>) -> Result<String, String> {
+    let mut file: OrderedValue = serde_json::from_str(contents).map_err(|e| e.to_string())?;
+    let routes = match &mut file {
+        OrderedValue::Object(file) => file.get_mut("routes"),
+        _ => None,
+    };
+    let Some(OrderedValue::Object(routes)) = routes else {
+        return Err("The links file has no routes".to_string());
+    };
+
+    match value {
+        Some(value) => {
+            routes.insert(key.to_string(), value);
+        }
+        None => {
+            routes.shift_remove(key);
+        }
+    }
+
+    let indent = contents
+        .lines()
+        .nth(1)
+        .map(|line| &line[..line.len() - line.trim_start().len()])
+        .filter(|indent| !indent.is_empty())
+        .unwrap_or("  ");
+
+    let mut written = Vec::new();
+    let formatter = serde_json::ser::PrettyFormatter::with_indent(indent.as_bytes());
+    let mut serializer = serde_json::Serializer::with_formatter(&mut written, formatter);
+    rocket::serde::Serialize::serialize(&file, &mut serializer).map_err(|e| e.to_string())?;
+
+    let mut written = String::from_utf8(written).map_err(|e| e.to_string())?;
+    if contents.ends_with('\n') {
+        written.push('\n');
+    }
+    Ok(written)
+}
+
+/// Changes a TOML links file, which keeps its comments and formatting.
+fn change_toml(contents: &str, key: &str, value: Option<&OrderedValue>) -> Result<String, String> {
+    let mut file: toml_edit::DocumentMut = contents.parse().map_err(|e| format!("{}", e))?;
+    let routes = file
+        .get_mut("routes")
+        .and_then(toml_edit::Item::as_table_like_mut)
+        .ok_or("The links file has no routes")?;
+
+    match value {
+        Some(value) => {
+            let value =
+                rocket::serde::Serialize::serialize(value, toml_edit::ser::ValueSerializer::new())
+                    .map_err(|e| e.to_string())?;
+            routes.insert(key, toml_edit::Item::Value(value));
+        }
+        None => {
+            routes.remove(key);
+        }
+    }
+
+    Ok(file.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Creates a uniquely named directory in the temp directory holding `files`.
+    fn links_dir(name: &str, files: &[(&str, &str)]) -> std::path::PathBuf {
+        let path = std::env::temp_dir().join(format!("golinks-{}-{}", std::process::id(), name));
+        let _ = fs::remove_dir_all(&path);
+        fs::create_dir_all(&path).expect("Could not create links directory");
+        for (file, contents) in files {
+            fs::write(path.join(file), contents).expect("Could not write links file");
+        }
+        path
+    }
+
+    fn described(url: &str, description: &str) -> Link {
+        Link {
+            description: Some(description.to_string()),
+            ..Link::new(url)
+        }
+    }
+
+    #[test]
+    fn test_change_yaml() {
+        let contents = "\
+# Team links
+routes:
+  # The docs
+  docs: https://docs.example.com
+  wiki:
+    url: https://wiki.example.com
+    tags: [team]
+
+  chat: https://chat.example.com # Slack
+patterns:
+  - glob: 'gh/*'
+    url: https://github.com/{1}
+";
+
+        let changed = change(
+            LinksFormat::Yaml,
+            contents,
+            "wiki",
+            Some(&Link::new("https://new-wiki.example.com")),
+        )
+        .unwrap();
+        assert_eq!(
+            changed,
+            contents.replace(
+                "  wiki:\n    url: https://wiki.example.com\n    tags: [team]\n",
+                "  wiki: https://new-wiki.example.com\n"
+            )
+        );
+
+        let changed = change(LinksFormat::Yaml, contents, "docs", None).unwrap();
+        assert_eq!(
+            changed,
+            contents.replace("  docs: https://docs.example.com\n", "")
+        );
+
+        let changed = change(
+            LinksFormat::Yaml,
+            contents,
+            "new",
+            Some(&described("https://new.example.com", "Something new")),
+        )
+        .unwrap();
+        assert_eq!(
+            changed,
+            contents.replace(
+                "  chat: https://chat.example.com # Slack\n",
+                "  chat: https://chat.example.com # Slack\n  new:\n    url: https://new.example.com\n    description: Something new\n"
+            )
+        );
+    }
+
+    #[test]
+    fn test_change_yaml_flow() {
+        // Flow mappings cannot be changed line by line, and writing them again would lose
+        // the comments of the file.
+        let contents = "# Team links\nroutes: {docs: https://docs.example.com, chat: https://chat.example.com}\n";
+
+        let changed = change(LinksFormat::Yaml, contents, "docs", None);
+        assert!(matches!(changed, Err(ChangeError::Unsupported(_))));
+    }
+
+    #[test]
+    fn test_change_yaml_empty_routes() {
+        let contents = "# Team links\nroutes:\n";
+
+        let changed = change(
+            LinksFormat::Yaml,
+            contents,
+            "docs",
+            Some(&Link::new("https://docs.example.com")),
+        )
+        .unwrap();
+        assert_eq!(
+            changed,
+            "# Team links\nroutes:\n  docs: https://docs.example.com\n"
+        );
+    }
+
+    #[test]
+    fn test_change_json() {
+        let contents = "{\n    \"routes\": {\n        \"zoo\": \"https://zoo.example.com\",\n        \"abc\": \"https://abc.example.com\"\n    }\n}\n";
+
+        let changed = change(
+            LinksFormat::Json,
+            contents,
+            "mid",
+            Some(&Link::new("https://mid.example.com")),
+        )
+        .unwrap();
+        assert_eq!(
+            changed,
+            contents.replace(
+                "\"https://abc.example.com\"\n",
+                "\"https://abc.example.com\",\n        \"mid\": \"https://mid.example.com\"\n"
+            )
+        );
+
+        let changed = change(LinksFormat::Json, contents, "zoo", None).unwrap();
+        assert_eq!(
+            changed,
+            "{\n    \"routes\": {\n        \"abc\": \"https://abc.example.com\"\n    }\n}\n"
+        );
+    }
+
+    #[test]
+    fn test_change_json_keeps_nested_order() {
+        let contents = "{\n  \"routes\": {\n    \"zoo\": {\n      \"url\": \"https://zoo.example.com\",\n      \"status\": 302\n    }\n  },\n  \"indexes\": {\n    \"disabled\": []\n  }\n}\n";
+
+        let changed = change(
+            LinksFormat::Json,
+            contents,
+            "chat",
+            Some(&described("https://chat.example.com", "Chat")),
+        )
+        .unwrap();
+        assert_eq!(
+            changed,
+            contents.replace(
+                "302\n    }\n",
+                "302\n    },\n    \"chat\": {\n      \"url\": \"https://chat.example.com\",\n      \"description\": \"Chat\"\n    }\n"
+            )
+        );
+    }
+
+    #[test]
+    fn test_change_toml() {
+        let contents = "\
+# Team links
+[routes]
+docs = \"https://docs.example.com\" # The docs
+chat = \"https://chat.example.com\"
+";
+
+        let changed = change(
+            LinksFormat::Toml,
+            contents,
+            "chat",
+            Some(&described("https://chat.example.com", "Chat")),
+        )
+        .unwrap();
+        assert_eq!(
+            changed,
+            contents.replace(
+                "chat = \"https://chat.example.com\"",
+                "chat = { url = \"https://chat.example.com\", description = \"Chat\" }"
+            )
+        );
+
+        let changed = change(LinksFormat::Toml, contents, "docs", None).unwrap();
+        assert_eq!(
+            changed,
+            "# Team links\n[routes]\nchat = \"https://chat.example.com\"\n"
+        );
+    }
+
+    #[test]
+    fn test_apply() {
+        let dir = links_dir(
+            "edit-apply",
+            &[
+                ("main.yaml", "routes:\n  docs: https://docs.example.com\n"),
+                (
+                    "team.yaml",
+                    "namespace: team\nroutes:\n  wiki: https://wiki.example.com\n",
+                ),
+            ],
+        );
+        let main = dir.join("main.yaml").display().to_string();
+        let team = dir.join("team.yaml").display().to_string();
+        let sources = format!("{},{}", main, team);
+        let apply = |edit: &LinkEdit| apply(&sources, None, ConflictPolicy::Error, &[], edit);
+
+        let applied = apply(&LinkEdit::Create(
+            "team/chat".to_string(),
+            Link::new("https://chat.example.com"),
+        ))
+        .unwrap();
+        assert_eq!(applied.path, team);
+        assert_eq!(applied.link.source.as_deref(), Some(team.as_str()));
+        assert_eq!(applied.routes.len(), 3);
+        assert_eq!(
+            applied.hash,
+            loader::content_hash(&sources).expect("Could not hash links files")
+        );
+        assert_eq!(
+            fs::read_to_string(&team).unwrap(),
+            "namespace: team\nroutes:\n  wiki: https://wiki.example.com\n  chat: https://chat.example.com\n"
+        );
+
+        let applied = apply(&LinkEdit::Create(
+            "/new/".to_string(),
+            Link::new("https://new.example.com"),
+        ))
+        .unwrap();
+        assert_eq!(applied.key, "new");
+        assert_eq!(applied.path, main);
+
+        let applied = apply(&LinkEdit::Upsert(
+            "team/chat".to_string(),
+            Link::new("https://new-chat.example.com"),
+        ))
+        .unwrap();
+        assert_eq!(applied.path, team);
+        assert_eq!(applied.routes.len(), 4);
+
+        let applied = apply(&LinkEdit::Delete("team/wiki".to_string())).unwrap();
+        assert_eq!(applied.link.url, "https://wiki.example.com");
+        assert!(applied.routes.fetch("team/wiki").is_none());
+
+        assert!(matches!(
+            apply(&LinkEdit::Create(
+                "docs".to_string(),
+                Link::new("https://x.example.com")
+            )),
+            Err(EditError::Exists(_))
+        ));
+        assert!(matches!(
+            apply(&LinkEdit::Update(
+                "missing".to_string(),
+                Link::new("https://x.example.com")
+            )),
+            Err(EditError::NotFound(_))
+        ));
+        assert!(matches!(
+            apply(&LinkEdit::Delete("missing".to_string())),
+            Err(EditError::NotFound(_))
+        ));
+    }
+
+    #[test]
+    fn test_apply_rejected() {
+        let dir = links_dir(
+            "edit-rejected",
+            &[("links.yaml", "routes:\n  docs: https://docs.example.com\n")],
+        );
+        let path = dir.join("links.yaml");
+        let sources = path.display().to_string();
+        let apply = |edit: &LinkEdit| apply(&sources, None, ConflictPolicy::Error, &[], edit);
+
+        // Invalid links are never written.
+        let result = apply(&LinkEdit::Create(
+            "_/admin".to_string(),
+            Link::new("https://x.example.com"),
+        ));
+        assert!(matches!(result, Err(EditError::Load(_))), "{:?}", result);
+
+        // Neither are links when the links file cannot be replaced.
+        let temporary = dir.join(format!(".links.yaml.{}.tmp", std::process::id()));
+        fs::create_dir(&temporary).unwrap();
+        let result = apply(&LinkEdit::Delete("docs".to_string()));
+        assert!(
+            matches!(result, Err(EditError::Write { .. })),
+            "{:?}",
+            result
+        );
+        fs::remove_dir(&temporary).unwrap();
+
+        assert_eq!(
+            fs::read_to_string(&path).unwrap(),
+            "routes:\n  docs: https://docs.example.com\n"
+        );
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 1);
+
+        // Links from remote or git sources cannot be changed.
+        let remote = LinksDocument {
+            path: "https://example.com/links.yaml".to_string(),
+            namespace: None,
+            format: LinksFormat::Yaml,
+            contents: "routes:\n  remote: https://remote.example.com\n".to_string(),
+        };
+        let result = super::apply(
+            &sources,
+            None,
+            ConflictPolicy::Error,
+            &[remote],
+            &LinkEdit::Delete("remote".to_string()),
+        );
+        assert!(
+            matches!(result, Err(EditError::ReadOnly { .. })),
+            "{:?}",
+            result
+        );
+    }
+}
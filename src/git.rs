@@ -0,0 +1,476 @@
+use std::fmt;
+use std::io;
+use std::path::PathBuf;
+use std::process::Command;
+use std::time::Duration;
+
+use log::{debug, info, warn};
+
+use crate::loader::{self, LinksDocument};
+use crate::models::LinksFormat;
+use crate::reload::{ReloadOutcome, Reloader};
+use crate::remote::Backoff;
+
+/// Errors that can occur while reading links from a git repository.
+#[derive(Debug)]
+pub enum GitError {
+    /// `git` could not be run.
+    Spawn { command: String, source: io::Error },
+    /// `git` ran but failed, such as for a ref or a file that does not exist.
+    Failed { command: String, message: String },
+}
+
+impl fmt::Display for GitError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GitError::Spawn { command, source } => {
+                write!(f, "Unable to run '{}': {}", command, source)
+            }
+            GitError::Failed { command, message } => write!(f, "'{}' failed: {}", command, message),
+        }
+    }
+}
+
+impl std::error::Error for GitError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            GitError::Spawn { source, .. } => Some(source),
+            GitError::Failed { .. } => None,
+        }
+    }
+}
+
+/// Links files read from a checkout of a git repository at the commit a ref points to,
+/// with the `git` command line.
+#[derive(Clone, Debug)]
+pub struct GitSource {
+    repository: PathBuf,
+    reference: String,
+    /// The links files in the repository, as a comma-separated list of files,
+    /// directories and globs on file names, each optionally mounted as `namespace=path`.
+    paths: String,
+    format: Option<LinksFormat>,
+    fetch: bool,
+}
+
+impl GitSource {
+    /// Creates a source for the links files at `paths` in the repository checked out at
+    /// `repository`, at the commit `reference` points to. Each file is read in `format`,
+    /// or in the format its extension stands for.
+    pub fn new(
+        repository: &str,
+        reference: &str,
+        paths: &str,
+        format: Option<LinksFormat>,
+    ) -> Self {
+        Self {
+            repository: PathBuf::from(repository),
+            reference: reference.to_string(),
+            paths: paths.to_string(),
+            format,
+            fetch: false,
+        }
+    }
+
+    /// Sets whether to fetch from the default remote of the repository before resolving
+    /// the ref, instead of leaving updating the checkout to something else.
+    pub fn with_fetch(mut self, fetch: bool) -> Self {
+        self.fetch = fetch;
+        self
+    }
+
+    /// Runs `git` in the repository, returning what it printed.
+    fn git(&self, args: &[&str]) -> Result<String, GitError> {
+        let command = format!("git {}", args.join(" "));
+        debug!("Running '{}' in {}...", command, self.repository.display());
+
+        let output = Command::new("git")
+            .arg("-C")
+            .arg(&self.repository)
+            .args(args)
+            .output()
+            .map_err(|source| GitError::Spawn {
+                command: command.clone(),
+                source,
+            })?;
+
+        if !output.status.success() {
+            return Err(GitError::Failed {
+                command,
+                message: String::from_utf8_lossy(&output.stderr).trim().to_string(),
+            });
+        }
+
+        String::from_utf8(output.stdout).map_err(|_| GitError::Failed {
+            command,
+            message: "The output is not valid UTF-8".to_string(),
+        })
+    }
+
+    /// Fetches from the default remote of the repository, if enabled.
+    pub fn update(&self) -> Result<(), GitError> {
+        if self.fetch {
+            self.git(&["fetch", "--quiet"])?;
+        }
+        Ok(())
+    }
+
+    /// Gets the SHA of the commit the ref points to.
+    pub fn resolve(&self) -> Result<String, GitError> {
+        let revision = format!("{}^{{commit}}", self.reference);
+        Ok(self
+            .git(&["rev-parse", "--verify", "--end-of-options", &revision])?
+            .trim()
+            .to_string())
+    }
+
+    /// Reads the links files at `commit`. A file listed more than once is only read the
+    /// first time.
+    pub fn read(&self, commit: &str) -> Result<Vec<LinksDocument>, GitError> {
+        let mut documents: Vec<LinksDocument> = Vec::new();
+
+        for (namespace, path) in self.entries() {
+            for file in self.files(commit, path)? {
+                let display = format!(
+                    "{}@{}",
+                    self.repository.join(&file).display(),
+                    self.reference
+                );
+                if documents.iter().any(|document| document.path == display) {
+                    continue;
+                }
+
+                let contents = self.git(&["show", &format!("{}:{}", commit, file)])?;
+                documents.push(LinksDocument {
+                    format: self
+                        .format
+                        .or_else(|| LinksFormat::from_path(&file))
+                        .unwrap_or(LinksFormat::Yaml),
+                    path: display,
+                    namespace: namespace.map(str::to_string),
+                    contents,
+                });
+            }
+        }
+
+        Ok(documents)
+    }
+
+    /// Splits the list of links files into its entries the way the loader does, with
+    /// paths relative to the root of the repository.
+    fn entries(&self) -> impl Iterator<Item = (Option<&str>, &str)> {
+        loader::source_entries(&self.paths)
+            .map(|(namespace, path)| (namespace, path.trim_start_matches("./")))
+    }
+
+    /// Expands an entry of the list of links files into the files it stands for at
+    /// `commit`, like the links files on disk: a directory stands for the links files
+    /// directly inside it, and a glob for the files of its directory matching it.
+    fn files(&self, commit: &str, path: &str) -> Result<Vec<String>, GitError> {
+        let path = path.trim_end_matches('/');
+        let (directory, name) = match path.rsplit_once('/') {
+            Some((directory, name)) => (directory, name),
+            None => ("", path),
+        };
+
+        let glob = name.contains(['*', '?']).then_some(name);
+        let directory = match glob {
+            Some(_) => directory,
+            None => {
+                let kind = self.git(&["cat-file", "-t", &format!("{}:{}", commit, path)])?;
+                if kind.trim() != "tree" {
+                    return Ok(vec![path.to_string()]);
+                }
+                path
+            }
+        };
+
+        let filter = loader::name_filter(glob).map_err(|message| GitError::Failed {
+            command: format!("git ls-tree {}:{}", commit, directory),
+            message,
+        })?;
+        let listing = self.git(&[
+            "ls-tree",
+            "--name-only",
+            &format!("{}:{}", commit, directory),
+        ])?;
+
+        Ok(listing
+            .lines()
+            .filter(|name| loader::is_links_file(name, filter.as_ref()))
+            .map(|name| match directory {
+                "" => name.to_string(),
+                directory => format!("{}/{}", directory, name),
+            })
+            .collect())
+    }
+}
+
+/// Polls a git repository, reloading the links every time the tracked ref moves to
+/// another commit.
+pub struct GitPoller {
+    source: GitSource,
+    reloader: Reloader,
+    /// The commit the links were last read at, even if they were rejected, so that they
+    /// are not read again until the ref moves.
+    commit: Option<String>,
+    backoff: Backoff,
+}
+
+impl GitPoller {
+    /// Creates a poller checking the ref of `source` every `interval`, and loading the
+    /// links with `reloader`.
+    pub fn new(source: GitSource, reloader: Reloader, interval: Duration) -> Self {
+        Self {
+            source,
+            reloader,
+            commit: None,
+            backoff: Backoff::new(interval),
+        }
+    }
+
+    /// Checks the ref once, returning the outcome of reloading the links if it moved.
+    /// The active routes are kept when the links cannot be read.
+    pub async fn poll(&mut self) -> Result<Option<ReloadOutcome>, GitError> {
+        let source = self.source.clone();
+        let previous = self.commit.clone();
+        let read = tokio::task::spawn_blocking(move || {
+            source.update()?;
+            let commit = source.resolve()?;
+            if previous.as_ref() == Some(&commit) {
+                return Ok(None);
+            }
+            let documents = source.read(&commit)?;
+            Ok(Some((commit, documents)))
+        })
+        .await
+        .unwrap_or_else(|err| std::panic::resume_unwind(err.into_panic()));
+
+        let (commit, documents) = match read {
+            Ok(Some(read)) => read,
+            Ok(None) => {
+                self.backoff.succeed();
+                debug!("{} did not move", self.source.reference);
+                return Ok(None);
+            }
+            Err(err) => {
+                self.backoff.fail();
+                warn!(
+                    "Could not read the links at {}: {}. Trying again in {}s",
+                    self.source.reference,
+                    err,
+                    self.backoff.delay().as_secs()
+                );
+                return Err(err);
+            }
+        };
+        self.backoff.succeed();
+
+        info!(
+            "{} is at commit {}! Reloading routes...",
+            self.source.reference, commit
+        );
+        self.commit = Some(commit.clone());
+        Ok(Some(self.reloader.reload_git(commit, documents).await))
+    }
+
+    /// Checks the ref forever, waiting for the poll interval between checks, or longer
+    /// after checks failed.
+    pub async fn run(mut self) {
+        loop {
+            rocket::tokio::time::sleep(self.backoff.delay()).await;
+            let _ = self.poll().await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use std::path::Path;
+
+    use crate::config::AppConfig;
+    use crate::models::{Routes, SharedRoutes};
+    use crate::reload::ReloadStatus;
+
+    /// Runs `git` in `directory`, panicking if it fails.
+    fn run_git(directory: &Path, args: &[&str]) -> String {
+        let output = Command::new("git")
+            .arg("-C")
+            .arg(directory)
+            .args([
+                "-c",
+                "user.name=golinks",
+                "-c",
+                "user.email=golinks@example.com",
+            ])
+            .args(args)
+            .output()
+            .expect("Could not run git");
+        assert!(
+            output.status.success(),
+            "git {:?} failed: {}",
+            args,
+            String::from_utf8_lossy(&output.stderr)
+        );
+        String::from_utf8(output.stdout).unwrap().trim().to_string()
+    }
+
+    /// Writes the given files to the repository at `directory` and commits them,
+    /// returning the SHA of the commit.
+    fn commit(directory: &Path, files: &[(&str, &str)]) -> String {
+        for (path, contents) in files {
+            let path = directory.join(path);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, contents).unwrap();
+        }
+        run_git(directory, &["add", "--all"]);
+        run_git(
+            directory,
+            &["commit", "--quiet", "--message", "Update links"],
+        );
+        run_git(directory, &["rev-parse", "HEAD"])
+    }
+
+    /// Creates an empty repository with a `main` branch.
+    fn repository(name: &str) -> PathBuf {
+        let directory =
+            std::env::temp_dir().join(format!("golinks-{}-{}", std::process::id(), name));
+        let _ = fs::remove_dir_all(&directory);
+        fs::create_dir_all(&directory).unwrap();
+        run_git(&directory, &["init", "--quiet", "--initial-branch", "main"]);
+        directory
+    }
+
+    #[test]
+    fn test_read() {
+        let directory = repository("git-read");
+        let sha = commit(
+            &directory,
+            &[
+                ("links.yaml", "routes:\n  test: https://example.com\n"),
+                (
+                    "teams/infra.json",
+                    "{\"routes\": {\"dash\": \"https://dash.example.com\"}}",
+                ),
+                ("teams/notes.txt", "Not links"),
+                ("teams/.hidden.yaml", "routes: {}\n"),
+            ],
+        );
+        // Changes that were not committed are not read.
+        fs::write(directory.join("links.yaml"), "routes: [\n").unwrap();
+
+        let source = GitSource::new(
+            directory.to_str().unwrap(),
+            "main",
+            "./links.yaml,infra=teams/,teams/*.json",
+            None,
+        );
+        assert_eq!(source.resolve().unwrap(), sha);
+
+        let documents = source.read(&sha).unwrap();
+        assert_eq!(documents.len(), 2);
+        assert_eq!(
+            documents[0].path,
+            format!("{}@main", directory.join("links.yaml").display())
+        );
+        assert_eq!(documents[0].format, LinksFormat::Yaml);
+        assert_eq!(
+            documents[0].contents,
+            "routes:\n  test: https://example.com\n"
+        );
+        assert_eq!(documents[1].namespace.as_deref(), Some("infra"));
+        assert_eq!(documents[1].format, LinksFormat::Json);
+
+        let source = GitSource::new(directory.to_str().unwrap(), "missing", "links.yaml", None);
+        assert!(matches!(source.resolve(), Err(GitError::Failed { .. })));
+
+        let source = GitSource::new(directory.to_str().unwrap(), "main", "missing.yaml", None);
+        assert!(source.read(&sha).is_err());
+    }
+
+    #[rocket::async_test]
+    async fn test_poll() {
+        let origin = repository("git-origin");
+        commit(
+            &origin,
+            &[("links.yaml", "routes:\n  test: https://v1.example.com\n")],
+        );
+
+        let checkout =
+            origin.with_file_name(format!("golinks-{}-git-checkout", std::process::id()));
+        let _ = fs::remove_dir_all(&checkout);
+        run_git(
+            &origin,
+            &[
+                "clone",
+                "--quiet",
+                origin.to_str().unwrap(),
+                checkout.to_str().unwrap(),
+            ],
+        );
+
+        let mut configs = AppConfig::default();
+        configs.set_links_file("");
+        let routes = SharedRoutes::new(Routes::with_links(Default::default()));
+        let reloader = Reloader::new(configs, routes.clone());
+
+        let source = GitSource::new(
+            checkout.to_str().unwrap(),
+            "origin/main",
+            "links.yaml",
+            None,
+        )
+        .with_fetch(true);
+        let mut poller = GitPoller::new(source, reloader, Duration::from_secs(60));
+
+        let outcome = poller.poll().await.unwrap().unwrap();
+        assert_eq!(outcome.status, ReloadStatus::Reloaded);
+        assert_eq!(
+            routes.commit(),
+            Some(run_git(&origin, &["rev-parse", "HEAD"]))
+        );
+        assert_eq!(routes.status().commit, routes.commit());
+        assert!(poller.poll().await.unwrap().is_none());
+
+        // New commits are fetched from the remote of the checkout.
+        let sha = commit(
+            &origin,
+            &[("links.yaml", "routes:\n  test: https://v2.example.com\n")],
+        );
+        let outcome = poller.poll().await.unwrap().unwrap();
+        assert_eq!(outcome.status, ReloadStatus::Reloaded);
+        assert_eq!(
+            routes.load().fetch("test").unwrap().url,
+            "https://v2.example.com"
+        );
+        assert_eq!(routes.commit(), Some(sha.clone()));
+
+        // A commit with invalid links is rejected once, and the previous commit is still
+        // reported.
+        commit(&origin, &[("links.yaml", "routes: [\n")]);
+        let outcome = poller.poll().await.unwrap().unwrap();
+        assert_eq!(outcome.status, ReloadStatus::Failed);
+        assert!(poller.poll().await.unwrap().is_none());
+        assert_eq!(routes.commit(), Some(sha));
+        assert_eq!(routes.status().status, "degraded");
+
+        // A commit that does not change the links is still reported.
+        let sha = commit(
+            &origin,
+            &[
+                ("links.yaml", "routes:\n  test: https://v2.example.com\n"),
+                ("README.md", "Links"),
+            ],
+        );
+        let outcome = poller.poll().await.unwrap().unwrap();
+        assert_eq!(outcome.status, ReloadStatus::Unchanged);
+        assert_eq!(routes.commit(), Some(sha));
+        assert_eq!(routes.status().status, "ok");
+
+        fs::remove_dir_all(&checkout).unwrap();
+        assert!(poller.poll().await.is_err());
+        assert_eq!(poller.backoff.delay().as_secs(), 120);
+    }
+}
@@ -0,0 +1,490 @@
+use std::cmp::Ordering;
+use std::fmt::Write;
+
+use percent_encoding::{utf8_percent_encode, AsciiSet, CONTROLS};
+use url::form_urlencoded;
+
+use crate::link::Link;
+use crate::models::Routes;
+use crate::suggest::Suggestions;
+
+/// The path the link directory is served from.
+pub const DIRECTORY_PATH: &str = "/_/links";
+
+/// Characters that must be escaped in a segment of a permalink.
+const SEGMENT: &AsciiSet = &CONTROLS
+    .add(b' ')
+    .add(b'"')
+    .add(b'#')
+    .add(b'%')
+    .add(b'/')
+    .add(b'<')
+    .add(b'>')
+    .add(b'?')
+    .add(b'`')
+    .add(b'{')
+    .add(b'}');
+
+/// Escapes text so it can be embedded in HTML content and attribute values.
+pub fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Wraps `body` in a complete HTML page. Styles are inlined so the page works without
+/// any external assets.
+pub fn page(title: &str, body: &str) -> String {
+    format!(
+        r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+<meta charset="utf-8">
+<meta name="viewport" content="width=device-width, initial-scale=1">
+<title>{title}</title>
+<style>
+body {{ font-family: system-ui, sans-serif; margin: 2rem; color: #222; }}
+h1 {{ font-size: 1.5rem; }}
+table {{ border-collapse: collapse; width: 100%; }}
+th, td {{ text-align: left; padding: 0.4rem 0.6rem; border-bottom: 1px solid #ddd; vertical-align: top; }}
+th a {{ color: inherit; }}
+code {{ word-break: break-all; }}
+.tag {{ display: inline-block; background: #eef; border-radius: 0.3rem; padding: 0 0.3rem; margin: 0 0.2rem 0.2rem 0; }}
+.muted {{ color: #777; }}
+form {{ margin-bottom: 1rem; }}
+</style>
+</head>
+<body>
+<h1>{title}</h1>
+{body}
+</body>
+</html>
+"#,
+        title = escape(title),
+        body = body,
+    )
+}
+
+/// Column the link directory can be sorted by.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum SortColumn {
+    #[default]
+    Key,
+    Url,
+    Owners,
+    Updated,
+}
+
+impl SortColumn {
+    const ALL: [SortColumn; 4] = [
+        SortColumn::Key,
+        SortColumn::Url,
+        SortColumn::Owners,
+        SortColumn::Updated,
+    ];
+
+    /// Parses the value of the `sort` query parameter, falling back to sorting by key.
+    pub fn parse(value: Option<&str>) -> Self {
+        Self::ALL
+            .into_iter()
+            .find(|column| Some(column.name()) == value)
+            .unwrap_or_default()
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            SortColumn::Key => "key",
+            SortColumn::Url => "url",
+            SortColumn::Owners => "owners",
+            SortColumn::Updated => "updated",
+        }
+    }
+
+    fn title(self) -> &'static str {
+        match self {
+            SortColumn::Key => "Link",
+            SortColumn::Url => "Target",
+            SortColumn::Owners => "Owners",
+            SortColumn::Updated => "Updated",
+        }
+    }
+
+    fn compare(self, a: (&str, &Link), b: (&str, &Link)) -> Ordering {
+        let by_column = match self {
+            SortColumn::Key => Ordering::Equal,
+            SortColumn::Url => a.1.url.cmp(&b.1.url),
+            SortColumn::Owners => a.1.owners.cmp(&b.1.owners),
+            SortColumn::Updated => a.1.updated.cmp(&b.1.updated),
+        };
+
+        by_column.then_with(|| a.0.cmp(b.0))
+    }
+}
+
+/// The filters and sort order of the link directory, taken from its query string.
+#[derive(Debug, Default)]
+pub struct DirectoryQuery<'a> {
+    pub prefix: Option<&'a str>,
+    pub tag: Option<&'a str>,
+    pub sort: SortColumn,
+    pub descending: bool,
+}
+
+impl DirectoryQuery<'_> {
+    fn matches(&self, key: &str, link: &Link) -> bool {
+        self.prefix.is_none_or(|prefix| key.starts_with(prefix))
+            && self.tag.is_none_or(|tag| link.has_tag(tag))
+    }
+
+    /// Builds the URL of the directory with the same filters, sorted by `column`.
+    fn sort_url(&self, column: SortColumn) -> String {
+        let descending = column == self.sort && !self.descending;
+        let mut query = form_urlencoded::Serializer::new(String::new());
+
+        if let Some(prefix) = self.prefix {
+            query.append_pair("prefix", prefix);
+        }
+        if let Some(tag) = self.tag {
+            query.append_pair("tag", tag);
+        }
+        query.append_pair("sort", column.name());
+        query.append_pair("order", if descending { "desc" } else { "asc" });
+
+        format!("{}?{}", DIRECTORY_PATH, query.finish())
+    }
+}
+
+/// Builds the permalink of the directory entry for `key`.
+pub fn permalink(key: &str) -> String {
+    let encoded: Vec<String> = key
+        .split('/')
+        .map(|segment| utf8_percent_encode(segment, SEGMENT).to_string())
+        .collect();
+
+    format!("{}/{}", DIRECTORY_PATH, encoded.join("/"))
+}
+
+fn tags(link: &Link) -> String {
+    link.tags
+        .iter()
+        .map(|tag| {
+            let href = format!(
+                "{}?{}",
+                DIRECTORY_PATH,
+                form_urlencoded::Serializer::new(String::new())
+                    .append_pair("tag", tag)
+                    .finish()
+            );
+            format!(
+                r#"<a class="tag" href="{}">{}</a>"#,
+                escape(&href),
+                escape(tag)
+            )
+        })
+        .collect()
+}
+
+/// Renders the page listing every link that matches the filters of `query`.
+pub fn directory(routes: &Routes, query: &DirectoryQuery<'_>) -> String {
+    let mut links: Vec<(&str, &Link)> = routes
+        .links()
+        .filter(|(key, link)| query.matches(key, link))
+        .collect();
+
+    links.sort_by(|a, b| query.sort.compare(*a, *b));
+    if query.descending {
+        links.reverse();
+    }
+
+    let mut body = String::new();
+
+    let _ = write!(
+        body,
+        r#"<form method="get" action="{action}">
+<label>Prefix <input name="prefix" value="{prefix}"></label>
+<label>Tag <input name="tag" value="{tag}"></label>
+<button type="submit">Filter</button>
+<a href="{action}">Clear</a>
+</form>
+<p class="muted">Showing {shown} of {total} links.</p>
+<table>
+<thead><tr>"#,
+        action = DIRECTORY_PATH,
+        prefix = escape(query.prefix.unwrap_or("")),
+        tag = escape(query.tag.unwrap_or("")),
+        shown = links.len(),
+        total = routes.links().count(),
+    );
+
+    for column in SortColumn::ALL {
+        let marker = match (column == query.sort, query.descending) {
+            (true, false) => " ▲",
+            (true, true) => " ▼",
+            (false, _) => "",
+        };
+        let _ = write!(
+            body,
+            r#"<th><a href="{}">{}{}</a></th>"#,
+            escape(&query.sort_url(column)),
+            column.title(),
+            marker
+        );
+    }
+    body.push_str("<th>Description</th><th>Tags</th></tr></thead>\n<tbody>\n");
+
+    for (key, link) in links {
+        let _ = writeln!(
+            body,
+            r#"<tr id="{key}"><td><a href="{permalink}">{key}</a></td><td><a href="{url}"><code>{url}</code></a></td><td>{owners}</td><td>{updated}</td><td>{description}</td><td>{tags}</td></tr>"#,
+            key = escape(key),
+            permalink = escape(&permalink(key)),
+            url = escape(&link.url),
+            owners = escape(&link.owners.join(", ")),
+            updated = link.updated.map(|d| d.to_string()).unwrap_or_default(),
+            description = escape(link.description.as_deref().unwrap_or("")),
+            tags = tags(link),
+        );
+    }
+    body.push_str("</tbody>\n</table>\n");
+
+    if !routes.patterns().is_empty() {
+        body.push_str("<h2>Patterns</h2>\n<table>\n<thead><tr><th>Pattern</th><th>Target</th></tr></thead>\n<tbody>\n");
+        for pattern in routes.patterns() {
+            let _ = writeln!(
+                body,
+                "<tr><td><code>{}</code></td><td><code>{}</code></td></tr>",
+                escape(pattern.source()),
+                escape(&pattern.link().url),
+            );
+        }
+        body.push_str("</tbody>\n</table>\n");
+    }
+
+    page("Links", &body)
+}
+
+/// Renders the page describing a single link.
+pub fn link_details(key: &str, link: &Link) -> String {
+    let mut rows = vec![
+        (
+            "Target",
+            format!(
+                r#"<a href="{url}"><code>{url}</code></a>"#,
+                url = escape(&link.url)
+            ),
+        ),
+        ("Status", link.status.code().to_string()),
+    ];
+
+    if let Some(cache_control) = link.cache_control() {
+        rows.push(("Cache-Control", escape(&cache_control)));
+    }
+    if let Some(description) = &link.description {
+        rows.push(("Description", escape(description)));
+    }
+    if !link.owners.is_empty() {
+        rows.push(("Owners", escape(&link.owners.join(", "))));
+    }
+    if !link.tags.is_empty() {
+        rows.push(("Tags", tags(link)));
+    }
+    if let Some(created) = link.created {
+        rows.push(("Created", created.to_string()));
+    }
+    if let Some(updated) = link.updated {
+        rows.push(("Updated", updated.to_string()));
+    }
+    for (label, value) in &link.labels {
+        rows.push(("Label", format!("{}: {}", escape(label), escape(value))));
+    }
+    if let Some(source) = &link.source {
+        rows.push(("Source", escape(source)));
+    }
+
+    let mut body = String::from("<table>\n<tbody>\n");
+    for (name, value) in rows {
+        let _ = writeln!(body, "<tr><th>{}</th><td>{}</td></tr>", name, value);
+    }
+    let _ = write!(
+        body,
+        r#"</tbody>
+</table>
+<p><a href="{}">All links</a></p>
+"#,
+        DIRECTORY_PATH
+    );
+
+    page(key, &body)
+}
+
+fn link_list(keys: &[String]) -> String {
+    let mut list = String::from("<ul>\n");
+    for key in keys {
+        let _ = writeln!(
+            list,
+            r#"<li><a href="/{key}">{key}</a> <a class="muted" href="{permalink}">(details)</a></li>"#,
+            key = escape(key),
+            permalink = escape(&permalink(key)),
+        );
+    }
+    list.push_str("</ul>\n");
+    list
+}
+
+/// Renders the index page of a prefix that has links under it but no link of its own.
+pub fn index(prefix: &str, links: &[(&str, &Link)]) -> String {
+    let prefix = prefix.trim_matches('/');
+    let mut body = format!(
+        "<p class=\"muted\">'{}' is not a link, but {} {} under it.</p>\n",
+        escape(prefix),
+        links.len(),
+        if links.len() == 1 {
+            "link is"
+        } else {
+            "links are"
+        },
+    );
+
+    body.push_str(
+        "<table>\n<thead><tr><th>Link</th><th>Target</th><th>Description</th></tr></thead>\n<tbody>\n",
+    );
+    for (key, link) in links {
+        let _ = writeln!(
+            body,
+            r#"<tr><td><a href="/{key}">{key}</a> <a class="muted" href="{permalink}">(details)</a></td><td><code>{url}</code></td><td>{description}</td></tr>"#,
+            key = escape(key),
+            permalink = escape(&permalink(key)),
+            url = escape(&link.url),
+            description = escape(link.description.as_deref().unwrap_or("")),
+        );
+    }
+    body.push_str("</tbody>\n</table>\n");
+
+    let _ = writeln!(body, r#"<p><a href="{}">All links</a></p>"#, DIRECTORY_PATH);
+
+    page(&format!("{}/", prefix), &body)
+}
+
+/// Renders the page shown when a path is not a known link.
+pub fn not_found(path: &str, suggestions: &Suggestions) -> String {
+    let mut body = format!(
+        "<p>'{}' is not a known link.</p>\n",
+        escape(path.trim_matches('/'))
+    );
+
+    if !suggestions.similar.is_empty() {
+        body.push_str("<h2>Did you mean</h2>\n");
+        body.push_str(&link_list(&suggestions.similar));
+    }
+    if !suggestions.children.is_empty() {
+        body.push_str("<h2>Links under this path</h2>\n");
+        body.push_str(&link_list(&suggestions.children));
+    }
+
+    let _ = writeln!(body, r#"<p><a href="{}">All links</a></p>"#, DIRECTORY_PATH);
+
+    page("Link not found", &body)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn routes() -> Routes {
+        let mut infra = Link::new("https://infra.example.com");
+        infra.tags = vec!["ops".to_string()];
+
+        Routes::with_links(HashMap::from([
+            ("infra/dash".to_string(), infra),
+            ("b".to_string(), Link::new("https://a.example.com")),
+            ("a".to_string(), Link::new("https://b.example.com/?x=<y>")),
+        ]))
+    }
+
+    #[test]
+    fn test_escape() {
+        assert_eq!(
+            escape(r#"<a href="x">'&'</a>"#),
+            "&lt;a href=&quot;x&quot;&gt;&#39;&amp;&#39;&lt;/a&gt;"
+        );
+    }
+
+    #[test]
+    fn test_directory_filters() {
+        let query = DirectoryQuery {
+            tag: Some("ops"),
+            ..Default::default()
+        };
+        let html = directory(&routes(), &query);
+
+        assert!(html.contains("infra/dash"));
+        assert!(!html.contains(r#"<tr id="a">"#));
+        assert!(html.contains("Showing 1 of 3 links."));
+
+        let query = DirectoryQuery {
+            prefix: Some("infra/"),
+            ..Default::default()
+        };
+        assert!(directory(&routes(), &query).contains("Showing 1 of 3 links."));
+    }
+
+    #[test]
+    fn test_directory_sorts() {
+        let html = directory(&routes(), &DirectoryQuery::default());
+        assert!(html.find(r#"<tr id="a">"#) < html.find(r#"<tr id="b">"#));
+
+        let query = DirectoryQuery {
+            sort: SortColumn::Url,
+            ..Default::default()
+        };
+        let html = directory(&routes(), &query);
+        assert!(html.find(r#"<tr id="b">"#) < html.find(r#"<tr id="a">"#));
+        assert!(html.contains("?x=&lt;y&gt;"));
+    }
+
+    #[test]
+    fn test_sort_column_parse() {
+        assert_eq!(SortColumn::parse(Some("updated")), SortColumn::Updated);
+        assert_eq!(SortColumn::parse(Some("bogus")), SortColumn::Key);
+        assert_eq!(SortColumn::parse(None), SortColumn::Key);
+    }
+
+    #[test]
+    fn test_not_found_lists_suggestions() {
+        let suggestions = Suggestions {
+            similar: vec!["github".to_string()],
+            children: vec![],
+        };
+        let html = not_found("/githbu<", &suggestions);
+
+        assert!(html.contains("'githbu&lt;' is not a known link."));
+        assert!(html.contains(r#"<a href="/github">github</a>"#));
+        assert!(!html.contains("Links under this path"));
+    }
+
+    #[test]
+    fn test_index_lists_links() {
+        let routes = routes();
+        let links = routes.index("/infra").unwrap();
+        let html = index("/infra", &links);
+
+        assert!(html.contains("<title>infra/</title>"));
+        assert!(html.contains("1 link is under it."));
+        assert!(html.contains(r#"<a href="/infra/dash">infra/dash</a>"#));
+    }
+
+    #[test]
+    fn test_permalink() {
+        assert_eq!(permalink("infra/a b"), "/_/links/infra/a%20b");
+    }
+}
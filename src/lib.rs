@@ -1,3 +1,23 @@
+#[macro_use]
+extern crate rocket;
+
+pub mod admin;
+pub mod api;
+pub mod cli;
 pub mod config;
+pub mod edit;
+pub mod git;
+pub mod html;
+pub mod link;
+pub mod lint;
+pub mod loader;
 pub mod models;
+pub mod pattern;
+pub mod reload;
+pub mod remote;
+pub mod sqlite;
+pub mod store;
+pub mod suggest;
+pub mod template;
+pub mod trie;
 pub mod utils;
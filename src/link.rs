@@ -0,0 +1,485 @@
+use std::borrow::Cow;
+use std::collections::BTreeMap;
+use std::fmt;
+
+use chrono::NaiveDate;
+use rocket::serde::de::{self, Deserializer, MapAccess, Visitor};
+use rocket::serde::{Deserialize, Serialize, Serializer};
+use url::{form_urlencoded, Url};
+
+use crate::template::{self, TemplateContext};
+
+/// How the query string of a request is combined with the query string of the target.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(crate = "rocket::serde", rename_all = "kebab-case")]
+pub enum QueryPolicy {
+    /// Request parameters are added to the target, replacing target parameters
+    /// with the same name.
+    #[default]
+    Passthrough,
+    /// Request parameters are ignored.
+    Drop,
+    /// Request parameters are added to the target, except for the ones the target
+    /// already sets.
+    TargetWins,
+}
+
+/// The status code a link redirects with.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(crate = "rocket::serde", try_from = "u16", into = "u16")]
+pub enum RedirectStatus {
+    /// 301 Moved Permanently
+    MovedPermanently,
+    /// 302 Found
+    Found,
+    /// 303 See Other
+    SeeOther,
+    /// 307 Temporary Redirect
+    #[default]
+    TemporaryRedirect,
+    /// 308 Permanent Redirect
+    PermanentRedirect,
+}
+
+impl RedirectStatus {
+    /// Gets the HTTP status code.
+    pub fn code(self) -> u16 {
+        match self {
+            RedirectStatus::MovedPermanently => 301,
+            RedirectStatus::Found => 302,
+            RedirectStatus::SeeOther => 303,
+            RedirectStatus::TemporaryRedirect => 307,
+            RedirectStatus::PermanentRedirect => 308,
+        }
+    }
+}
+
+impl TryFrom<u16> for RedirectStatus {
+    type Error = String;
+
+    fn try_from(code: u16) -> Result<Self, Self::Error> {
+        match code {
+            301 => Ok(RedirectStatus::MovedPermanently),
+            302 => Ok(RedirectStatus::Found),
+            303 => Ok(RedirectStatus::SeeOther),
+            307 => Ok(RedirectStatus::TemporaryRedirect),
+            308 => Ok(RedirectStatus::PermanentRedirect),
+            _ => Err(format!(
+                "{} is not a supported redirect status, expected one of 301, 302, 303, 307 or 308",
+                code
+            )),
+        }
+    }
+}
+
+impl From<RedirectStatus> for u16 {
+    fn from(status: RedirectStatus) -> Self {
+        status.code()
+    }
+}
+
+/// A registered link. In the links file, a link is either the target URL by itself or
+/// an object with a `url` and the optional settings and metadata below.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(crate = "rocket::serde", remote = "Self", deny_unknown_fields)]
+pub struct Link {
+    pub url: String,
+
+    #[serde(default)]
+    pub query: QueryPolicy,
+
+    #[serde(default)]
+    pub status: RedirectStatus,
+
+    /// Sent as-is in the `Cache-Control` header of the redirect.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cache_control: Option<String>,
+
+    /// Shorthand for a `Cache-Control: max-age=<seconds>` header. Ignored when
+    /// `cache_control` is set.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_age: Option<u64>,
+
+    /// What the link is for.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+
+    /// The people or teams responsible for the link.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub owners: Vec<String>,
+
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+
+    /// The date the link was created, as `YYYY-MM-DD`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub created: Option<NaiveDate>,
+
+    /// The date the link was last updated, as `YYYY-MM-DD`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub updated: Option<NaiveDate>,
+
+    /// Free-form key/value pairs.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub labels: BTreeMap<String, String>,
+
+    /// The links file the link was loaded from. This is filled in by the loader and
+    /// cannot be set in the links file.
+    #[serde(skip_deserializing, skip_serializing_if = "Option::is_none")]
+    pub source: Option<String>,
+}
+
+impl Link {
+    /// Creates a new `Link` to `url` with the default settings.
+    pub fn new(url: impl Into<String>) -> Self {
+        Self {
+            url: url.into(),
+            ..Default::default()
+        }
+    }
+
+    /// Gets a value indicating whether the link is tagged with `tag`.
+    pub fn has_tag(&self, tag: &str) -> bool {
+        self.tags.iter().any(|t| t == tag)
+    }
+
+    /// Gets the value of the `Cache-Control` header to send with the redirect, if any.
+    pub fn cache_control(&self) -> Option<String> {
+        self.cache_control
+            .clone()
+            .or_else(|| self.max_age.map(|age| format!("max-age={}", age)))
+    }
+
+    /// Builds the destination for a request that matched this link with `remainder`
+    /// path segments left over.
+    ///
+    /// The placeholders of a templated target are filled from `remainder` and `query`,
+    /// the parameters of the query string of the request as they were sent (see
+    /// `query_params`). Otherwise `remainder` is inserted at the end of the path of the
+    /// target. The query parameters that were not used by a placeholder are then merged
+    /// into the target according to the query policy of the link.
+    pub fn destination(&self, remainder: &[&str], query: &[&str]) -> String {
+        if template::has_placeholders(&self.url) {
+            let context = TemplateContext {
+                segments: remainder.to_vec(),
+                ..Default::default()
+            };
+
+            return self.render(context, query);
+        }
+
+        self.join(&self.url, remainder, query)
+    }
+
+    /// Builds the destination of a templated target from `context` and the query
+    /// parameters `query`, merging the parameters that were not used by a placeholder
+    /// into it.
+    pub fn render(&self, context: TemplateContext<'_>, query: &[&str]) -> String {
+        let decoded: Vec<(Cow<str>, Cow<str>)> = query.iter().map(|param| decode(param)).collect();
+        let context = TemplateContext {
+            query: decoded
+                .iter()
+                .map(|(name, value)| (name.as_ref(), value.as_ref()))
+                .collect(),
+            ..context
+        };
+
+        let rendered = template::render(&self.url, &context);
+        let used = template::names(&self.url);
+        let unused: Vec<&str> = query
+            .iter()
+            .zip(&decoded)
+            .filter(|(_, (name, _))| !used.contains(&name.as_ref()))
+            .map(|(param, _)| *param)
+            .collect();
+
+        self.join(&rendered, &[], &unused)
+    }
+
+    /// Merges the query parameters `query` into `target`. Parameters are copied as they
+    /// were sent, and the parameters of the target are kept as they are written, so that
+    /// merging never changes the encoding of either.
+    fn join(&self, target: &str, remainder: &[&str], query: &[&str]) -> String {
+        let query = match self.query {
+            QueryPolicy::Drop => &[],
+            _ => query,
+        };
+
+        if remainder.is_empty() && query.is_empty() {
+            return target.to_string();
+        }
+
+        let mut url = match Url::parse(target) {
+            Ok(url) if !url.cannot_be_a_base() => url,
+            _ => return append(target, remainder, query),
+        };
+
+        if !remainder.is_empty() {
+            if let Ok(mut segments) = url.path_segments_mut() {
+                segments.pop_if_empty().extend(remainder);
+            }
+        }
+
+        if !query.is_empty() {
+            let name = |param: &str| decode(param).0.into_owned();
+            let mut params: Vec<String> = url
+                .query()
+                .map(query_params)
+                .unwrap_or_default()
+                .into_iter()
+                .map(str::to_string)
+                .collect();
+
+            match self.query {
+                QueryPolicy::Passthrough => {
+                    params.retain(|param| !query.iter().any(|added| name(added) == name(param)));
+                    params.extend(query.iter().map(|param| param.to_string()));
+                }
+                QueryPolicy::TargetWins => {
+                    let added: Vec<String> = query
+                        .iter()
+                        .filter(|added| !params.iter().any(|param| name(param) == name(added)))
+                        .map(|param| param.to_string())
+                        .collect();
+                    params.extend(added);
+                }
+                QueryPolicy::Drop => {}
+            }
+
+            url.set_query(Some(&params.join("&")));
+        }
+
+        url.to_string()
+    }
+}
+
+/// Splits a query string into its parameters, leaving them as they were sent.
+pub fn query_params(query: &str) -> Vec<&str> {
+    query.split('&').filter(|param| !param.is_empty()).collect()
+}
+
+/// Decodes the name and value of a query parameter.
+fn decode(param: &str) -> (Cow<'_, str>, Cow<'_, str>) {
+    form_urlencoded::parse(param.as_bytes())
+        .next()
+        .unwrap_or_default()
+}
+
+/// Joins a target that is not an absolute URL by appending to it.
+fn append(target: &str, remainder: &[&str], query: &[&str]) -> String {
+    let mut joined = target.to_string();
+
+    for segment in remainder {
+        if !joined.ends_with('/') {
+            joined.push('/');
+        }
+        joined.push_str(segment);
+    }
+
+    if !query.is_empty() {
+        joined.push(if joined.contains('?') { '&' } else { '?' });
+        joined.push_str(&query.join("&"));
+    }
+
+    joined
+}
+
+impl From<String> for Link {
+    fn from(url: String) -> Self {
+        Link::new(url)
+    }
+}
+
+impl From<&str> for Link {
+    fn from(url: &str) -> Self {
+        Link::new(url)
+    }
+}
+
+impl Serialize for Link {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        Link::serialize(self, serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for Link {
+    /// Accepts both the bare URL shorthand and the object form of a link.
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct LinkVisitor;
+
+        impl<'de> Visitor<'de> for LinkVisitor {
+            type Value = Link;
+
+            fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+                formatter.write_str("a URL or a link object with a `url`")
+            }
+
+            fn visit_str<E: de::Error>(self, value: &str) -> Result<Link, E> {
+                Ok(Link::new(value))
+            }
+
+            fn visit_map<A: MapAccess<'de>>(self, map: A) -> Result<Link, A::Error> {
+                Link::deserialize(de::value::MapAccessDeserializer::new(map))
+            }
+        }
+
+        deserializer.deserialize_any(LinkVisitor)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_destination_inserts_remainder_into_path() {
+        let link = Link::new("https://example.com/docs?a=1#intro");
+
+        assert_eq!(
+            link.destination(&["guide", "setup"], &[]),
+            "https://example.com/docs/guide/setup?a=1#intro"
+        );
+    }
+
+    #[test]
+    fn test_destination_without_remainder_keeps_target() {
+        let link = Link::new("https://example.com");
+
+        assert_eq!(link.destination(&[], &[]), "https://example.com");
+    }
+
+    #[test]
+    fn test_query_passthrough() {
+        let link = Link::new("https://example.com/search?a=1&b=2");
+
+        assert_eq!(
+            link.destination(&[], &["b=3", "q=rust+lang"]),
+            "https://example.com/search?a=1&b=3&q=rust+lang"
+        );
+    }
+
+    #[test]
+    fn test_query_kept_as_sent() {
+        let link = Link::new("https://example.com/search?a=x%2fy&b=2");
+
+        assert_eq!(
+            link.destination(&[], &["path=a%2fb+c", "flag", "%62=3"]),
+            "https://example.com/search?a=x%2fy&path=a%2fb+c&flag&%62=3"
+        );
+    }
+
+    #[test]
+    fn test_query_target_wins() {
+        let link = Link {
+            query: QueryPolicy::TargetWins,
+            ..Link::new("https://example.com/search?a=1&b=2")
+        };
+
+        assert_eq!(
+            link.destination(&[], &["b=3", "q=x"]),
+            "https://example.com/search?a=1&b=2&q=x"
+        );
+    }
+
+    #[test]
+    fn test_query_drop() {
+        let link = Link {
+            query: QueryPolicy::Drop,
+            ..Link::new("https://example.com/search?a=1")
+        };
+
+        assert_eq!(
+            link.destination(&["x"], &["q=x"]),
+            "https://example.com/search/x?a=1"
+        );
+    }
+
+    #[test]
+    fn test_template_does_not_pass_through_used_parameters() {
+        let link = Link::new("https://google.com/search?q={q}");
+
+        assert_eq!(
+            link.destination(&[], &["q=rust", "hl=en"]),
+            "https://google.com/search?q=rust&hl=en"
+        );
+    }
+
+    #[test]
+    fn test_relative_target_is_appended() {
+        let link = Link::new("/internal");
+
+        assert_eq!(link.destination(&["a"], &["q=x+y"]), "/internal/a?q=x+y");
+    }
+
+    #[test]
+    fn test_cache_control() {
+        let link = Link {
+            max_age: Some(3600),
+            ..Link::new("https://example.com")
+        };
+
+        assert_eq!(link.cache_control(), Some("max-age=3600".to_string()));
+
+        let link = Link {
+            cache_control: Some("no-store".to_string()),
+            ..link
+        };
+
+        assert_eq!(link.cache_control(), Some("no-store".to_string()));
+        assert_eq!(Link::new("https://example.com").cache_control(), None);
+    }
+
+    #[test]
+    fn test_deserialize_status() {
+        let link: Link = serde_yaml::from_str("url: https://a.com\nstatus: 308\n").unwrap();
+
+        assert_eq!(link.status, RedirectStatus::PermanentRedirect);
+
+        let err = serde_yaml::from_str::<Link>("url: https://a.com\nstatus: 200\n").unwrap_err();
+
+        assert!(err
+            .to_string()
+            .contains("200 is not a supported redirect status"));
+    }
+
+    #[test]
+    fn test_deserialize_metadata() {
+        let link: Link = serde_yaml::from_str(
+            r#"
+            url: https://oncall.example.com
+            description: Who is on call for infra this week
+            owners: [infra-team, alice]
+            tags: [infra, oncall]
+            created: 2024-01-31
+            updated: 2024-06-01
+            labels:
+              tier: critical
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            link.description.as_deref(),
+            Some("Who is on call for infra this week")
+        );
+        assert_eq!(link.owners, vec!["infra-team", "alice"]);
+        assert!(link.has_tag("oncall"));
+        assert_eq!(link.created, NaiveDate::from_ymd_opt(2024, 1, 31));
+        assert_eq!(link.updated, NaiveDate::from_ymd_opt(2024, 6, 1));
+        assert_eq!(link.labels["tier"], "critical");
+
+        let err = serde_yaml::from_str::<Link>("url: https://a.com\ncreated: yesterday\n");
+        assert!(err.is_err());
+    }
+
+    #[test]
+    fn test_deserialize_shorthand_and_object() {
+        let links: std::collections::HashMap<String, Link> = serde_yaml::from_str(
+            "a: https://a.com\nb:\n  url: https://b.com\n  query: target-wins\n",
+        )
+        .unwrap();
+
+        assert_eq!(links["a"], Link::new("https://a.com"));
+        assert_eq!(links["b"].query, QueryPolicy::TargetWins);
+    }
+}
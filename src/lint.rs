@@ -0,0 +1,609 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::net::IpAddr;
+
+use rocket::serde::Serialize;
+use url::{Host, Url};
+
+use crate::models::{Conflict, ConflictPolicy, Routes};
+use crate::pattern::{Pattern, PatternKind};
+use crate::template;
+
+/// Schemes a link may never redirect to, since following them runs code or reads local
+/// files instead of opening a page.
+const DISALLOWED_SCHEMES: [&str; 4] = ["javascript", "data", "vbscript", "file"];
+
+/// How serious a problem found in a links file is.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(crate = "rocket::serde", rename_all = "lowercase")]
+pub enum Severity {
+    /// The link works, but probably not the way it was meant to.
+    Warning,
+    /// The link can never work, so the links file is rejected.
+    Error,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Severity::Warning => f.write_str("warning"),
+            Severity::Error => f.write_str("error"),
+        }
+    }
+}
+
+/// A problem found with a link in a links file.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+#[serde(crate = "rocket::serde")]
+pub struct Diagnostic {
+    pub severity: Severity,
+    /// The key of the link, or the pattern of a pattern link.
+    pub key: String,
+    /// The links file the link is defined in, when it is known.
+    pub file: Option<String>,
+    /// The line (1-based) the link is defined on, when it could be found.
+    pub line: Option<usize>,
+    /// The column (1-based) the link is defined at, when it could be found.
+    pub column: Option<usize>,
+    pub message: String,
+}
+
+impl Diagnostic {
+    /// Gets the line and column (both 1-based) of the link, if known.
+    pub fn location(&self) -> Option<(usize, usize)> {
+        self.line.zip(self.column)
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(file) = &self.file {
+            write!(f, "{}: ", file)?;
+        }
+        if let Some((line, column)) = self.location() {
+            write!(f, "line {}, column {}: ", line, column)?;
+        }
+        write!(f, "{}: '{}': {}", self.severity, self.key, self.message)
+    }
+}
+
+/// The contents of a links file that was loaded, used to find where its links are
+/// defined.
+#[derive(Clone, Copy, Debug)]
+pub struct SourceFile<'a> {
+    pub path: &'a str,
+    pub contents: &'a str,
+    /// The namespace the links of the file were mounted under.
+    pub namespace: Option<&'a str>,
+}
+
+/// Where the links of a links file are defined. Only the block style that links files
+/// are usually written in is understood, so links written in flow style (`{ ... }`) have
+/// no location.
+#[derive(Debug, Default)]
+struct SourceMap {
+    keys: HashMap<String, (usize, usize)>,
+    patterns: Vec<(usize, usize)>,
+}
+
+impl SourceMap {
+    /// Finds the links of a links file, keyed as they are once mounted under `namespace`.
+    fn parse(source: &str, namespace: Option<&str>) -> Self {
+        let mut map = SourceMap::default();
+        let mut section = None;
+        let mut indent = None;
+
+        for (number, line) in source.lines().enumerate() {
+            let content = line.trim_start();
+            if content.is_empty() || content.starts_with('#') {
+                continue;
+            }
+
+            let line_indent = line.len() - content.len();
+            // Sequences may be written at the same indentation as the key they belong to.
+            if line_indent == 0 && section == Some("patterns") && content.starts_with('-') {
+                map.patterns.push((number + 1, 1));
+                continue;
+            }
+            if line_indent == 0 {
+                section = ["routes", "patterns"]
+                    .into_iter()
+                    .find(|name| mapping_key(content).as_deref() == Some(*name));
+                indent = None;
+                continue;
+            }
+
+            let Some(section) = section else {
+                continue;
+            };
+            if *indent.get_or_insert(line_indent) != line_indent {
+                continue;
+            }
+
+            let location = (number + 1, line_indent + 1);
+            if section == "patterns" {
+                if content.starts_with('-') {
+                    map.patterns.push(location);
+                }
+            } else if let Some(key) = mapping_key(content) {
+                let key = match (namespace, key.as_str()) {
+                    (Some(namespace), "") => namespace.to_string(),
+                    (Some(namespace), _) => format!("{}/{}", namespace, key),
+                    (None, _) => key,
+                };
+                map.keys.entry(key).or_insert(location);
+            }
+        }
+
+        map
+    }
+}
+
+/// Reads the key of a `key: value` line, unquoting it if needed.
+fn mapping_key(content: &str) -> Option<String> {
+    let mut chars = content.char_indices();
+
+    let (key, rest) = match chars.next()? {
+        (_, quote @ ('"' | '\'')) => {
+            let mut key = String::new();
+            let mut escaped = false;
+            let mut end = None;
+
+            while let Some((i, c)) = chars.next() {
+                match c {
+                    _ if escaped => {
+                        key.push(c);
+                        escaped = false;
+                    }
+                    '\\' if quote == '"' => escaped = true,
+                    '\'' if quote == '\'' && content[i + 1..].starts_with('\'') => {
+                        key.push('\'');
+                        chars.next();
+                    }
+                    c if c == quote => {
+                        end = Some(i + 1);
+                        break;
+                    }
+                    c => key.push(c),
+                }
+            }
+
+            (key, content[end?..].trim_start())
+        }
+        _ => {
+            let end = content
+                .match_indices(':')
+                .map(|(i, _)| i)
+                .find(|&i| content[i + 1..].is_empty() || content[i + 1..].starts_with(' '))?;
+
+            (content[..end].trim_end().to_string(), &content[end..])
+        }
+    };
+
+    rest.starts_with(':').then_some(key)
+}
+
+/// The source maps of the links files that were loaded, by path.
+struct SourceMaps<'a> {
+    files: Vec<SourceFile<'a>>,
+    maps: Vec<(&'a str, SourceMap)>,
+}
+
+impl<'a> SourceMaps<'a> {
+    fn parse(files: &[SourceFile<'a>]) -> Self {
+        Self {
+            files: files.to_vec(),
+            maps: files
+                .iter()
+                .map(|file| (file.path, SourceMap::parse(file.contents, file.namespace)))
+                .collect(),
+        }
+    }
+
+    /// Finds a namespace of another file than `file` that `key` is mounted in, unless
+    /// `file` is mounted in that namespace too.
+    fn claimed_by(&self, key: &str, file: Option<&str>) -> Option<(&'a str, &'a str)> {
+        let namespace = self
+            .files
+            .iter()
+            .find(|source| Some(source.path) == file)
+            .and_then(|source| source.namespace);
+
+        self.files
+            .iter()
+            .filter(|source| Some(source.path) != file)
+            .filter_map(|source| Some((source.namespace?, source.path)))
+            .find(|(claimed, _)| {
+                is_within(key, claimed) && !namespace.is_some_and(|own| is_within(own, claimed))
+            })
+    }
+
+    /// Gets the source map of `file`. Links that do not know their file are assumed to
+    /// come from the only file loaded, if there is just one.
+    fn get(&self, file: Option<&str>) -> Option<(&'a str, &SourceMap)> {
+        match file {
+            Some(file) => self.maps.iter().find(|(path, _)| *path == file),
+            None if self.maps.len() == 1 => self.maps.first(),
+            None => None,
+        }
+        .map(|(path, map)| (*path, map))
+    }
+}
+
+struct Diagnostics(Vec<Diagnostic>);
+
+impl Diagnostics {
+    fn push(
+        &mut self,
+        severity: Severity,
+        key: &str,
+        file: Option<&str>,
+        location: Option<&(usize, usize)>,
+        message: String,
+    ) {
+        self.0.push(Diagnostic {
+            severity,
+            key: key.to_string(),
+            file: file.map(str::to_string),
+            line: location.map(|(line, _)| *line),
+            column: location.map(|(_, column)| *column),
+            message,
+        });
+    }
+
+    /// Sorts the diagnostics by file and location.
+    fn into_sorted(mut self) -> Vec<Diagnostic> {
+        self.0
+            .sort_by(|a, b| (&a.file, a.line, &a.key).cmp(&(&b.file, b.line, &b.key)));
+        self.0
+    }
+}
+
+/// Checks the links loaded from `files` for links that can never work and links that
+/// probably do not work the way they were meant to. Diagnostics are sorted by file and
+/// location.
+pub fn lint(routes: &Routes, files: &[SourceFile<'_>]) -> Vec<Diagnostic> {
+    let maps = SourceMaps::parse(files);
+    let mut diagnostics = Diagnostics(Vec::new());
+
+    let mut links: Vec<_> = routes.links().collect();
+    links.sort_by_key(|(key, _)| *key);
+
+    for (key, link) in links {
+        let source = maps.get(link.source.as_deref());
+        let file = source.map(|(path, _)| path);
+        let location = source.and_then(|(_, map)| map.keys.get(key));
+
+        if let Some((namespace, owner)) = maps.claimed_by(key, file) {
+            diagnostics.push(
+                Severity::Warning,
+                key,
+                file,
+                location,
+                format!("is inside the '{}' namespace of {}", namespace, owner),
+            );
+        }
+
+        for (severity, message) in check_key(key)
+            .into_iter()
+            .chain(check_target(&link.url))
+            .chain(check_ancestors(routes, key))
+        {
+            diagnostics.push(severity, key, file, location, message);
+        }
+    }
+
+    let mut seen: HashMap<Option<&str>, usize> = HashMap::new();
+    for pattern in routes.patterns() {
+        let source = maps.get(pattern.link().source.as_deref());
+        let file = source.map(|(path, _)| path);
+
+        // Patterns are merged in file order, so count them per file to find each one.
+        let index = seen.entry(file).or_default();
+        let location = source.and_then(|(_, map)| map.patterns.get(*index));
+        *index += 1;
+
+        for (severity, message) in check_target(&pattern.link().url)
+            .into_iter()
+            .chain(check_pattern(routes, pattern))
+        {
+            diagnostics.push(severity, pattern.source(), file, location, message);
+        }
+    }
+
+    diagnostics.into_sorted()
+}
+
+/// Describes links defined in more than one of `files`, at their second definition.
+/// They are errors when conflicts are not allowed by `policy`, and warnings otherwise.
+pub fn conflicts(
+    conflicts: &[Conflict],
+    policy: ConflictPolicy,
+    files: &[SourceFile<'_>],
+) -> Vec<Diagnostic> {
+    let maps = SourceMaps::parse(files);
+    let mut diagnostics = Diagnostics(Vec::new());
+
+    for conflict in conflicts {
+        let first = conflict.first.as_deref().unwrap_or("another file");
+        let second = conflict.second.as_deref().unwrap_or("another file");
+        let (severity, message) = match policy {
+            ConflictPolicy::Error => (Severity::Error, format!("is already defined in {}", first)),
+            ConflictPolicy::FirstWins => (
+                Severity::Warning,
+                format!("is already defined in {}, which is used instead", first),
+            ),
+            ConflictPolicy::LastWins => (
+                Severity::Warning,
+                format!(
+                    "is already defined in {}, the definition in {} is used",
+                    first, second
+                ),
+            ),
+        };
+
+        let source = maps.get(conflict.second.as_deref());
+        diagnostics.push(
+            severity,
+            &conflict.key,
+            conflict.second.as_deref(),
+            source.and_then(|(_, map)| map.keys.get(&conflict.key)),
+            message,
+        );
+    }
+
+    diagnostics.into_sorted()
+}
+
+/// Gets a value indicating whether `key` is `namespace` or nested under it.
+pub(crate) fn is_within(key: &str, namespace: &str) -> bool {
+    key.strip_prefix(namespace)
+        .is_some_and(|rest| rest.is_empty() || rest.starts_with('/'))
+}
+
+/// Checks that a key can be requested at all.
+fn check_key(key: &str) -> Vec<(Severity, String)> {
+    let mut problems = Vec::new();
+
+    if key.chars().any(char::is_whitespace) {
+        problems.push((
+            Severity::Warning,
+            "contains whitespace, which has to be typed as '%20'".to_string(),
+        ));
+    }
+
+    if key.is_empty() {
+        return problems;
+    }
+
+    let segments: Vec<&str> = key.split('/').collect();
+    if segments.iter().any(|segment| segment.is_empty()) {
+        problems.push((
+            Severity::Error,
+            "has a leading, trailing or doubled '/', so it can never be matched".to_string(),
+        ));
+    }
+    if segments
+        .iter()
+        .any(|segment| matches!(*segment, "." | ".."))
+    {
+        problems.push((
+            Severity::Error,
+            "has a '.' or '..' segment, which browsers remove before sending the request"
+                .to_string(),
+        ));
+    }
+
+    problems
+}
+
+/// Checks that a target is an absolute URL that is safe to redirect to.
+fn check_target(target: &str) -> Vec<(Severity, String)> {
+    let mut problems = Vec::new();
+
+    if target.chars().any(char::is_whitespace) {
+        problems.push((Severity::Warning, "target contains whitespace".to_string()));
+    }
+
+    let url = match Url::parse(&template::sample(target)) {
+        Ok(url) => url,
+        Err(err) => {
+            problems.push((
+                Severity::Error,
+                format!("target '{}' is not a valid absolute URL: {}", target, err),
+            ));
+            return problems;
+        }
+    };
+
+    let scheme = url.scheme();
+    if DISALLOWED_SCHEMES.contains(&scheme) {
+        problems.push((
+            Severity::Error,
+            format!("target uses the disallowed scheme '{}'", scheme),
+        ));
+        return problems;
+    }
+    if !matches!(scheme, "http" | "https") {
+        problems.push((
+            Severity::Warning,
+            format!("target uses the unusual scheme '{}'", scheme),
+        ));
+    }
+
+    if !url.username().is_empty() || url.password().is_some() {
+        problems.push((
+            Severity::Warning,
+            "target contains credentials, which are visible to anyone who can list the links"
+                .to_string(),
+        ));
+    }
+
+    let loopback = match url.host() {
+        Some(Host::Domain(domain)) => domain == "localhost" || domain.ends_with(".localhost"),
+        Some(Host::Ipv4(ip)) => IpAddr::V4(ip).is_loopback(),
+        Some(Host::Ipv6(ip)) => IpAddr::V6(ip).is_loopback(),
+        None => false,
+    };
+    if loopback {
+        problems.push((
+            Severity::Warning,
+            "target points at the machine of whoever follows the link".to_string(),
+        ));
+    }
+
+    problems
+}
+
+/// Checks whether the nearest ancestor of a key would have filled its placeholders with
+/// the rest of the path, had the key not been registered.
+fn check_ancestors(routes: &Routes, key: &str) -> Vec<(Severity, String)> {
+    let mut current = key;
+
+    while !current.is_empty() {
+        current = current.rfind('/').map_or("", |i| &current[..i]);
+
+        if let Some(parent) = routes.fetch(current) {
+            if template::uses_path(&parent.url) {
+                return vec![(
+                    Severity::Warning,
+                    format!(
+                        "takes precedence over '{}', whose target is filled from the rest of the path",
+                        current
+                    ),
+                )];
+            }
+            break;
+        }
+    }
+
+    Vec::new()
+}
+
+/// Checks whether a link is registered for a prefix of every path a pattern can match,
+/// since the pattern would then never be tried.
+fn check_pattern(routes: &Routes, pattern: &Pattern) -> Vec<(Severity, String)> {
+    let source = pattern.source();
+    if pattern.kind() == PatternKind::Regex && source.contains('|') {
+        return Vec::new();
+    }
+
+    let special: &[char] = match pattern.kind() {
+        PatternKind::Regex => &[
+            '\\', '.', '+', '*', '?', '(', ')', '[', ']', '{', '}', '^', '$',
+        ],
+        PatternKind::Glob => &['*', '?'],
+    };
+    let literal = source.find(special).map_or(source, |i| &source[..i]);
+
+    let mut current = literal.rfind('/').map_or("", |i| &literal[..i]);
+    loop {
+        if routes.fetch(current).is_some() {
+            return vec![(
+                Severity::Warning,
+                format!(
+                    "is never tried, since every path it matches is under the link '{}'",
+                    current
+                ),
+            )];
+        }
+
+        if current.is_empty() {
+            return Vec::new();
+        }
+        current = current.rfind('/').map_or("", |i| &current[..i]);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn lint_yaml(source: &str) -> Vec<Diagnostic> {
+        let routes: Routes = serde_yaml::from_str(source).unwrap();
+        lint(
+            &routes,
+            &[SourceFile {
+                path: "links.yaml",
+                contents: source,
+                namespace: None,
+            }],
+        )
+    }
+
+    fn messages(diagnostics: &[Diagnostic]) -> Vec<(Severity, &str)> {
+        diagnostics
+            .iter()
+            .map(|diagnostic| (diagnostic.severity, diagnostic.key.as_str()))
+            .collect()
+    }
+
+    #[test]
+    fn test_clean_links() {
+        let diagnostics = lint_yaml(
+            "routes:\n  e: https://example.com\n  gh:\n    url: https://github.com/{1}\n  mail: mailto:team@example.com\n",
+        );
+
+        assert_eq!(messages(&diagnostics), vec![(Severity::Warning, "mail")]);
+    }
+
+    #[test]
+    fn test_keys() {
+        let diagnostics = lint_yaml(
+            "routes:\n  /e: https://example.com\n  a/../b: https://example.com\n  'a b': https://example.com\n",
+        );
+
+        assert_eq!(
+            messages(&diagnostics),
+            vec![
+                (Severity::Error, "/e"),
+                (Severity::Error, "a/../b"),
+                (Severity::Warning, "a b"),
+            ]
+        );
+        assert_eq!(diagnostics[2].location(), Some((4, 3)));
+    }
+
+    #[test]
+    fn test_targets() {
+        let routes = Routes::with_routes(HashMap::from([
+            ("relative".to_string(), "/docs".to_string()),
+            ("script".to_string(), "javascript:alert(1)".to_string()),
+            ("local".to_string(), "http://127.0.0.1:8080".to_string()),
+            (
+                "secret".to_string(),
+                "https://user:pw@example.com".to_string(),
+            ),
+            ("host".to_string(), "https://{1}.example.com".to_string()),
+        ]));
+        let diagnostics = lint(&routes, &[]);
+
+        assert_eq!(
+            messages(&diagnostics),
+            vec![
+                (Severity::Warning, "local"),
+                (Severity::Error, "relative"),
+                (Severity::Error, "script"),
+                (Severity::Warning, "secret"),
+            ]
+        );
+        assert_eq!(diagnostics[0].location(), None);
+    }
+
+    #[test]
+    fn test_shadowing() {
+        let diagnostics = lint_yaml(
+            "routes:\n  gh: https://github.com/{1}\n  gh/me: https://github.com/me\n  e: https://example.com\n  e/x: https://example.com/x\npatterns:\n  - regex: 'e/(\\d+)'\n    url: https://example.com/{1}\n  - glob: 'docs/*'\n    url: https://docs.example.com/{1}\n",
+        );
+
+        assert_eq!(
+            messages(&diagnostics),
+            vec![
+                (Severity::Warning, "gh/me"),
+                (Severity::Warning, r"e/(\d+)")
+            ]
+        );
+        assert_eq!(diagnostics[1].location(), Some((7, 3)));
+    }
+}
@@ -0,0 +1,1080 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use log::{debug, warn};
+use regex::Regex;
+use rocket::serde::json::serde_json;
+use rocket::serde::DeserializeOwned;
+use sha2::{Digest, Sha256};
+
+use crate::lint::{self, Diagnostic, Severity, SourceFile};
+use crate::models::{ConflictPolicy, LinksFile, LinksFormat, LoadFailure, Routes};
+use crate::pattern::glob_to_regex;
+
+/// The extensions of the files that are loaded from a links directory.
+const LINKS_EXTENSIONS: [&str; 4] = ["yaml", "yml", "json", "toml"];
+
+/// An error from parsing a links file in one of the formats it can be written in.
+#[derive(Debug)]
+pub enum ParseError {
+    Yaml(serde_yaml::Error),
+    Json(serde_json::Error),
+    /// TOML errors point to a range of the file, so the line and column it starts at are
+    /// worked out from the contents of the file.
+    Toml(Box<toml::de::Error>, Option<(usize, usize)>),
+}
+
+impl ParseError {
+    /// Gets the line and column (both 1-based) where parsing failed, if known.
+    pub fn location(&self) -> Option<(usize, usize)> {
+        match self {
+            ParseError::Yaml(source) => source
+                .location()
+                .map(|location| (location.line(), location.column())),
+            ParseError::Json(source) if source.line() > 0 => Some((source.line(), source.column())),
+            ParseError::Json(_) => None,
+            ParseError::Toml(_, location) => *location,
+        }
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParseError::Yaml(source) => write!(f, "{}", source),
+            ParseError::Json(source) => write!(f, "{}", source),
+            ParseError::Toml(source, Some((line, column))) => write!(
+                f,
+                "{} at line {} column {}",
+                source.message().trim_end(),
+                line,
+                column
+            ),
+            ParseError::Toml(source, None) => write!(f, "{}", source.message().trim_end()),
+        }
+    }
+}
+
+impl std::error::Error for ParseError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ParseError::Yaml(source) => Some(source),
+            ParseError::Json(source) => Some(source),
+            ParseError::Toml(source, _) => Some(source),
+        }
+    }
+}
+
+/// Deserializes `contents`, written in `format`.
+fn deserialize<T: DeserializeOwned>(format: LinksFormat, contents: &str) -> Result<T, ParseError> {
+    match format {
+        LinksFormat::Yaml => serde_yaml::from_str(contents).map_err(ParseError::Yaml),
+        LinksFormat::Json => serde_json::from_str(contents).map_err(ParseError::Json),
+        LinksFormat::Toml => toml::from_str(contents).map_err(|err| {
+            let location = err.span().map(|span| {
+                let before = &contents[..span.start];
+                let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+                (
+                    before.matches('\n').count() + 1,
+                    before[line_start..].chars().count() + 1,
+                )
+            });
+            ParseError::Toml(Box::new(err), location)
+        }),
+    }
+}
+
+/// Errors that can occur while loading routes from the links files.
+#[derive(Debug)]
+pub enum LoadError {
+    /// The links file could not be opened.
+    Io { path: String, source: io::Error },
+    /// The links file could be opened but does not contain valid routes.
+    Parse { path: String, source: ParseError },
+    /// The links file could be parsed but the routes in it are not allowed.
+    Invalid { path: String, message: String },
+    /// The links files contain links that can never work, or conflicting links.
+    Lint {
+        path: String,
+        diagnostics: Vec<Diagnostic>,
+    },
+}
+
+impl LoadError {
+    /// Gets the line and column (both 1-based) where parsing failed, if known.
+    pub fn location(&self) -> Option<(usize, usize)> {
+        match self {
+            LoadError::Io { .. } | LoadError::Invalid { .. } => None,
+            LoadError::Parse { source, .. } => source.location(),
+            LoadError::Lint { diagnostics, .. } => diagnostics
+                .iter()
+                .find_map(|diagnostic| diagnostic.location()),
+        }
+    }
+}
+
+impl fmt::Display for LoadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LoadError::Io { path, source } => write!(f, "Unable to open {}: {}", path, source),
+            LoadError::Parse { path, source } => write!(f, "Unable to parse {}: {}", path, source),
+            LoadError::Invalid { path, message } => {
+                write!(f, "Invalid links in {}: {}", path, message)
+            }
+            LoadError::Lint { path, diagnostics } => {
+                write!(f, "Invalid links in {}:", path)?;
+                for diagnostic in diagnostics {
+                    write!(f, "\n  {}", diagnostic)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl std::error::Error for LoadError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            LoadError::Io { source, .. } => Some(source),
+            LoadError::Parse { source, .. } => Some(source),
+            LoadError::Invalid { .. } | LoadError::Lint { .. } => None,
+        }
+    }
+}
+
+impl From<&LoadError> for LoadFailure {
+    fn from(err: &LoadError) -> Self {
+        let location = err.location();
+        LoadFailure {
+            message: err.to_string(),
+            line: location.map(|(line, _)| line),
+            column: location.map(|(_, column)| column),
+            at: chrono::Utc::now().to_rfc3339(),
+            hash: None,
+        }
+    }
+}
+
+/// Reads and parses the routes from the given links files, merging links defined in
+/// more than one file according to `conflicts`. Each file is read in `format`, or in the
+/// format its extension stands for. Warnings about the links are logged.
+pub fn load_routes(
+    sources: &str,
+    conflicts: ConflictPolicy,
+    format: Option<LinksFormat>,
+) -> Result<Routes, LoadError> {
+    let (routes, warnings) = load_and_lint(sources, conflicts, format)?;
+
+    for warning in warnings {
+        warn!("{}", warning);
+    }
+
+    Ok(routes)
+}
+
+/// Reads and parses the routes from the given links files, returning them along with
+/// the warnings about the links in them. Links files with links that can never work,
+/// or that define the same link when `conflicts` does not allow it, are rejected.
+pub fn load_and_lint(
+    sources: &str,
+    conflicts: ConflictPolicy,
+    format: Option<LinksFormat>,
+) -> Result<(Routes, Vec<Diagnostic>), LoadError> {
+    load_documents(&read_documents(sources, format)?, conflicts, sources)
+}
+
+/// The contents of a links file, read from disk or fetched from a remote source.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LinksDocument {
+    /// Where the links were read from, which is recorded as the source of every link.
+    pub path: String,
+    /// The namespace the configuration mounts the links under.
+    pub namespace: Option<String>,
+    pub format: LinksFormat,
+    pub contents: String,
+}
+
+/// Reads the given links files. Each file is read in `format`, or in the format its
+/// extension stands for.
+pub fn read_documents(
+    sources: &str,
+    format: Option<LinksFormat>,
+) -> Result<Vec<LinksDocument>, LoadError> {
+    mounted_files(sources)?
+        .into_iter()
+        .map(|(path, namespace)| {
+            let contents = fs::read_to_string(&path).map_err(|source| LoadError::Io {
+                path: path.clone(),
+                source,
+            })?;
+
+            Ok(LinksDocument {
+                format: format
+                    .or_else(|| LinksFormat::from_path(&path))
+                    .unwrap_or(LinksFormat::Yaml),
+                path,
+                namespace: namespace.map(str::to_string),
+                contents,
+            })
+        })
+        .collect()
+}
+
+/// Parses the routes from the given links documents, merging links defined in more
+/// than one document according to `conflicts`, and returns them along with the
+/// warnings about the links in them. `sources` names the documents in errors about
+/// more than one of them.
+pub fn load_documents(
+    documents: &[LinksDocument],
+    conflicts: ConflictPolicy,
+    sources: &str,
+) -> Result<(Routes, Vec<Diagnostic>), LoadError> {
+    let (parsed, namespaces): (Vec<Routes>, Vec<Option<String>>) = documents
+        .iter()
+        .map(|document| {
+            parse(
+                &document.path,
+                &document.contents,
+                document.format,
+                document.namespace.as_deref(),
+            )
+        })
+        .collect::<Result<Vec<_>, LoadError>>()?
+        .into_iter()
+        .unzip();
+
+    let files: Vec<SourceFile> = documents
+        .iter()
+        .zip(&namespaces)
+        .map(|(document, namespace)| SourceFile {
+            path: &document.path,
+            contents: &document.contents,
+            namespace: namespace.as_deref(),
+        })
+        .collect();
+
+    let mut routes = Routes::with_links(HashMap::new());
+    let mut found = Vec::new();
+    for parsed in parsed {
+        found.extend(routes.merge(parsed, conflicts));
+    }
+
+    let (errors, warnings): (Vec<Diagnostic>, Vec<Diagnostic>) =
+        lint::conflicts(&found, conflicts, &files)
+            .into_iter()
+            .chain(lint::lint(&routes, &files))
+            .partition(|diagnostic| diagnostic.severity == Severity::Error);
+
+    if !errors.is_empty() {
+        return Err(LoadError::Lint {
+            path: sources.to_string(),
+            diagnostics: errors,
+        });
+    }
+
+    Ok((routes, warnings))
+}
+
+/// Hashes the contents of the given links files, so that a reload can be skipped when
+/// the files were touched without being changed.
+pub fn content_hash(sources: &str) -> Result<String, LoadError> {
+    Ok(documents_hash(&read_documents(sources, None)?))
+}
+
+/// Hashes the contents of the given links documents, along with where they were read
+/// from and the namespace they are mounted under.
+pub fn documents_hash(documents: &[LinksDocument]) -> String {
+    let mut hasher = Sha256::new();
+
+    for document in documents {
+        for part in [
+            document.path.as_bytes(),
+            document.namespace.as_deref().unwrap_or_default().as_bytes(),
+            document.contents.as_bytes(),
+        ] {
+            hasher.update((part.len() as u64).to_le_bytes());
+            hasher.update(part);
+        }
+    }
+
+    hasher
+        .finalize()
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+/// Gets the namespace the links of a links document are mounted under: the namespace
+/// from the configuration, followed by the namespace declared by the document.
+pub fn document_namespace(document: &LinksDocument) -> Result<Option<String>, LoadError> {
+    parse(
+        &document.path,
+        &document.contents,
+        document.format,
+        document.namespace.as_deref(),
+    )
+    .map(|(_, namespace)| namespace)
+}
+
+/// Parses a links file, mounting its links under the namespace from the configuration,
+/// followed by the namespace declared by the file. Returns the links along with the
+/// namespace they were mounted under.
+fn parse(
+    path: &str,
+    contents: &str,
+    format: LinksFormat,
+    namespace: Option<&str>,
+) -> Result<(Routes, Option<String>), LoadError> {
+    debug!("Parsing {} as {:?}...", path, format);
+
+    let invalid = |message| LoadError::Invalid {
+        path: path.to_string(),
+        message,
+    };
+
+    let file: LinksFile = deserialize(format, contents).map_err(|source| LoadError::Parse {
+        path: path.to_string(),
+        source,
+    })?;
+
+    let namespace = match (namespace, file.namespace.as_deref()) {
+        (Some(outer), Some(inner)) => Some(format!(
+            "{}/{}",
+            outer.trim_end_matches('/'),
+            inner.trim_start_matches('/')
+        )),
+        (outer, inner) => outer.or(inner).map(str::to_string),
+    }
+    .map(|namespace| namespace.trim_matches('/').to_string());
+
+    let routes = match &namespace {
+        Some(namespace) => file.routes.with_namespace(namespace).map_err(invalid)?,
+        None => file.routes,
+    };
+    routes.validate().map_err(invalid)?;
+
+    debug!("Finished parsing {}", path);
+    Ok((routes.with_source(path), namespace))
+}
+
+/// One entry of the comma-separated list of links files.
+enum LinksSource<'a> {
+    File(&'a str),
+    /// A directory, with an optional glob the names of its files must match.
+    Directory(&'a Path, Option<&'a str>),
+}
+
+impl<'a> LinksSource<'a> {
+    /// Parses the entries of a comma-separated list of links files, along with the
+    /// namespace of every entry written as `namespace=path`.
+    fn parse_all(sources: &'a str) -> impl Iterator<Item = (LinksSource<'a>, Option<&'a str>)> {
+        source_entries(sources).map(|(namespace, source)| {
+            let path = Path::new(source);
+            let name = path.file_name().and_then(|name| name.to_str());
+
+            let source = match name {
+                Some(name) if name.contains(['*', '?']) => {
+                    let directory = path
+                        .parent()
+                        .filter(|parent| !parent.as_os_str().is_empty())
+                        .unwrap_or(Path::new("."));
+                    LinksSource::Directory(directory, Some(name))
+                }
+                _ if path.is_dir() => LinksSource::Directory(path, None),
+                _ => LinksSource::File(source),
+            };
+
+            (source, namespace)
+        })
+    }
+}
+
+/// Splits a comma-separated list of links files into its entries, along with the
+/// namespace of every entry written as `namespace=path`. Only a single segment without
+/// dots can be a namespace, so that paths containing `=` are read as paths.
+pub(crate) fn source_entries(sources: &str) -> impl Iterator<Item = (Option<&str>, &str)> {
+    sources
+        .split(',')
+        .map(str::trim)
+        .filter(|source| !source.is_empty())
+        .map(|source| match source.split_once('=') {
+            Some((namespace, path)) if is_namespace(namespace) => {
+                (Some(namespace.trim()), path.trim())
+            }
+            _ => (None, source),
+        })
+}
+
+/// Gets a value indicating whether the left side of a `namespace=path` entry can be a
+/// namespace, rather than part of a path.
+fn is_namespace(namespace: &str) -> bool {
+    let namespace = namespace.trim().trim_end_matches('/');
+    !namespace.is_empty()
+        && !namespace
+            .chars()
+            .any(|c| matches!(c, '/' | '.' | '\\') || c.is_whitespace())
+}
+
+/// Expands a comma-separated list of links files, directories and globs on file names
+/// (such as `/conf/*.yaml`) into the links files to load, in order. The files of a
+/// directory are loaded in name order, and without a glob only `.yaml`, `.yml`, `.json`
+/// and `.toml` files are loaded. Hidden files, like the ones Kubernetes uses to swap
+/// ConfigMap contents, are skipped.
+pub fn links_files(sources: &str) -> Result<Vec<String>, LoadError> {
+    Ok(mounted_files(sources)?
+        .into_iter()
+        .map(|(file, _)| file)
+        .collect())
+}
+
+/// Expands a list of links files like `links_files`, along with the namespace the
+/// configuration mounts each file under. A file listed more than once is only loaded
+/// the first time.
+fn mounted_files(sources: &str) -> Result<Vec<(String, Option<&str>)>, LoadError> {
+    let mut files: Vec<(String, Option<&str>)> = Vec::new();
+
+    for (source, namespace) in LinksSource::parse_all(sources) {
+        let found = match source {
+            LinksSource::File(file) => vec![file.to_string()],
+            LinksSource::Directory(directory, glob) => directory_files(directory, glob)?,
+        };
+
+        for file in found {
+            if !files.iter().any(|(loaded, _)| *loaded == file) {
+                files.push((file, namespace));
+            }
+        }
+    }
+
+    Ok(files)
+}
+
+fn directory_files(directory: &Path, glob: Option<&str>) -> Result<Vec<String>, LoadError> {
+    let io_error = |source| LoadError::Io {
+        path: directory.display().to_string(),
+        source,
+    };
+
+    let filter = name_filter(glob).map_err(|message| LoadError::Invalid {
+        path: directory.display().to_string(),
+        message,
+    })?;
+
+    let mut files = Vec::new();
+    for entry in fs::read_dir(directory).map_err(io_error)? {
+        let path = entry.map_err(io_error)?.path();
+        let Some(name) = path.file_name().and_then(|name| name.to_str()) else {
+            continue;
+        };
+
+        if is_links_file(name, filter.as_ref()) && path.is_file() {
+            files.push(path.display().to_string());
+        }
+    }
+
+    files.sort();
+    Ok(files)
+}
+
+/// Compiles the glob the names of the files of a directory must match, if any.
+pub(crate) fn name_filter(glob: Option<&str>) -> Result<Option<Regex>, String> {
+    glob.map(|glob| {
+        Regex::new(&glob_to_regex(glob)).map_err(|err| format!("Invalid glob '{}': {}", glob, err))
+    })
+    .transpose()
+}
+
+/// Gets a value indicating whether a file of a directory named `name` is loaded: it
+/// matches the glob the directory was listed with, or has the extension of a links file
+/// when there is none, and it is not hidden.
+pub(crate) fn is_links_file(name: &str, filter: Option<&Regex>) -> bool {
+    let matches = match filter {
+        Some(filter) => filter.is_match(name),
+        None => Path::new(name)
+            .extension()
+            .is_some_and(|extension| LINKS_EXTENSIONS.iter().any(|e| *e == extension)),
+    };
+
+    matches && !name.starts_with('.')
+}
+
+/// Gets the directories to watch for changes to the links files in `sources`.
+///
+/// Directories are watched rather than the files in them, since files are often replaced
+/// rather than modified: editors save to a new file and rename it over the old one, and
+/// Kubernetes updates a ConfigMap volume by swapping its `..data` symlink. A links file
+/// that is a symlink to another directory has that directory watched as well, which can
+/// change whenever the symlink does.
+pub fn watch_paths(sources: &str) -> Vec<PathBuf> {
+    let mut paths: Vec<PathBuf> = Vec::new();
+
+    for (source, _) in LinksSource::parse_all(sources) {
+        let found = match source {
+            LinksSource::File(file) => {
+                let file = Path::new(file);
+                let target = fs::canonicalize(file).ok();
+                [Some(file), target.as_deref()]
+                    .into_iter()
+                    .flatten()
+                    .map(|file| parent_of(file).to_path_buf())
+                    .collect()
+            }
+            LinksSource::Directory(directory, _) => vec![directory.to_path_buf()],
+        };
+
+        for path in found {
+            if !paths.contains(&path) {
+                paths.push(path);
+            }
+        }
+    }
+
+    paths
+}
+
+/// Gets the file the revisions of the links loaded from `sources` are kept in, which is a
+/// hidden file next to the first links file, or in the first directory of links files,
+/// so that it is never loaded as a links file itself.
+pub fn revisions_path(sources: &str) -> Option<PathBuf> {
+    let (source, _) = LinksSource::parse_all(sources).next()?;
+    Some(match source {
+        LinksSource::File(file) => {
+            let file = Path::new(file);
+            let name = file.file_name()?.to_string_lossy();
+            parent_of(file).join(format!(".{}.revisions", name))
+        }
+        LinksSource::Directory(directory, _) => directory.join(".links.revisions"),
+    })
+}
+
+/// Gets a value indicating whether a change to `path`, found in one of the directories
+/// from `watch_paths`, can change the links loaded from `sources`. Changes to other
+/// files in those directories, such as the swap files of editors, are ignored.
+pub fn affects_links(sources: &str, path: &Path) -> bool {
+    let Some(name) = path.file_name().and_then(|name| name.to_str()) else {
+        return false;
+    };
+
+    // Kubernetes swaps the contents of a ConfigMap volume through `..data`, along with
+    // timestamped directories whose names also start with `..`.
+    if name.starts_with("..") {
+        return true;
+    }
+
+    LinksSource::parse_all(sources).any(|(source, _)| match source {
+        LinksSource::File(file) => {
+            let matches = |file: &Path| {
+                file.file_name() == Some(name.as_ref())
+                    && same_directory(parent_of(file), parent_of(path))
+            };
+            matches(Path::new(file)) || fs::canonicalize(file).is_ok_and(|target| matches(&target))
+        }
+        LinksSource::Directory(directory, glob) => {
+            path == directory
+                || (same_directory(directory, parent_of(path))
+                    && name_filter(glob).is_ok_and(|filter| is_links_file(name, filter.as_ref())))
+        }
+    })
+}
+
+/// Gets the directory `path` is in, which is the working directory for a bare file name.
+fn parent_of(path: &Path) -> &Path {
+    match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    }
+}
+
+/// Gets a value indicating whether two paths name the same directory, following
+/// symlinks when both of them exist.
+fn same_directory(a: &Path, b: &Path) -> bool {
+    match (fs::canonicalize(a), fs::canonicalize(b)) {
+        (Ok(a), Ok(b)) => a == b,
+        _ => a == b,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Writes `contents` to a uniquely named file in the temp directory.
+    fn links_file(name: &str, contents: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(format!("golinks-{}-{}", std::process::id(), name));
+        fs::write(&path, contents).expect("Could not write links file");
+        path
+    }
+
+    /// Creates a uniquely named directory in the temp directory holding `files`.
+    fn links_dir(name: &str, files: &[(&str, &str)]) -> PathBuf {
+        let path = std::env::temp_dir().join(format!("golinks-{}-{}", std::process::id(), name));
+        let _ = fs::remove_dir_all(&path);
+        fs::create_dir_all(&path).expect("Could not create links directory");
+        for (file, contents) in files {
+            fs::write(path.join(file), contents).expect("Could not write links file");
+        }
+        path
+    }
+
+    #[test]
+    fn test_load_routes() {
+        let path = links_file("valid.yaml", "routes:\n  test: https://example.com\n");
+        let routes = load_routes(path.to_str().unwrap(), ConflictPolicy::Error, None)
+            .expect("Could not load routes");
+
+        assert_eq!(
+            routes.fetch("test").map(|link| link.url.as_str()),
+            Some("https://example.com")
+        );
+    }
+
+    #[test]
+    fn test_load_routes_preserves_metadata() {
+        let path = links_file(
+            "metadata.yaml",
+            "routes:\n  oncall:\n    url: https://oncall.example.com\n    owners: [infra]\n    tags: [ops]\n",
+        );
+        let routes = load_routes(path.to_str().unwrap(), ConflictPolicy::Error, None)
+            .expect("Could not load routes");
+        let (key, link) = routes.links().next().unwrap();
+
+        assert_eq!(key, "oncall");
+        assert_eq!(link.owners, vec!["infra"]);
+        assert!(link.has_tag("ops"));
+    }
+
+    #[test]
+    fn test_load_routes_rejects_reserved_links() {
+        let path = links_file(
+            "reserved.yaml",
+            "routes:\n  _/links: https://example.com\n  _: https://example.com\n",
+        );
+        let err = load_routes(path.to_str().unwrap(), ConflictPolicy::Error, None).unwrap_err();
+
+        assert!(matches!(err, LoadError::Invalid { .. }));
+        assert!(err.to_string().contains("_, _/links"));
+    }
+
+    #[test]
+    fn test_load_routes_disabled_indexes() {
+        let path = links_file(
+            "indexes.yaml",
+            "routes:\n  hr/payroll: https://payroll.example.com\n  hr/team/x: https://x.example.com\n  infra/dash: https://dash.example.com\nindexes:\n  disabled: [hr]\n",
+        );
+        let routes = load_routes(path.to_str().unwrap(), ConflictPolicy::Error, None)
+            .expect("Could not load routes");
+
+        assert!(routes.index("hr").is_none());
+        assert!(routes.index("hr/team").is_none());
+        assert_eq!(routes.index("infra").map(|links| links.len()), Some(1));
+        assert!(routes.index("infra/dash").is_none());
+    }
+
+    #[test]
+    fn test_load_routes_rejects_duplicate_keys() {
+        let path = links_file(
+            "duplicate.yaml",
+            "routes:\n  test: https://a.example.com\n  \"test\": https://b.example.com\n",
+        );
+        let err = load_routes(path.to_str().unwrap(), ConflictPolicy::Error, None).unwrap_err();
+
+        assert!(matches!(err, LoadError::Parse { .. }));
+        assert!(err.location().is_some());
+        assert!(err.to_string().contains("'test' is defined more than once"));
+
+        let path = links_file(
+            "duplicate-flow.yaml",
+            "routes: {a: https://a.example.com, a: https://b.example.com}\n",
+        );
+        let err = load_routes(path.to_str().unwrap(), ConflictPolicy::Error, None).unwrap_err();
+
+        assert!(err.to_string().contains("'a' is defined more than once"));
+    }
+
+    #[test]
+    fn test_load_and_lint_returns_warnings() {
+        let path = links_file(
+            "warnings.yaml",
+            "routes:\n  test: https://example.com\n  local: http://localhost:3000\n",
+        );
+        let (routes, warnings) =
+            load_and_lint(path.to_str().unwrap(), ConflictPolicy::Error, None).unwrap();
+
+        assert!(routes.fetch("local").is_some());
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(warnings[0].key, "local");
+    }
+
+    #[test]
+    fn test_load_routes_from_directory() {
+        let dir = links_dir(
+            "directory",
+            &[
+                (
+                    "infra.yaml",
+                    "routes:\n  infra/dash: https://dash.example.com\n",
+                ),
+                ("eng.yml", "routes:\n  eng/wiki: https://wiki.example.com\n"),
+                ("notes.txt", "not links"),
+                (".hidden.yaml", "routes:\n  hidden: https://example.com\n"),
+            ],
+        );
+        let routes = load_routes(dir.to_str().unwrap(), ConflictPolicy::Error, None).unwrap();
+
+        assert_eq!(routes.len(), 2);
+        assert!(routes.fetch("hidden").is_none());
+        assert_eq!(
+            routes.fetch("infra/dash").unwrap().source.as_deref(),
+            dir.join("infra.yaml").to_str()
+        );
+    }
+
+    #[test]
+    fn test_links_files_expands_globs_and_lists() {
+        let dir = links_dir(
+            "glob",
+            &[
+                ("b-team.yaml", "routes: {}\n"),
+                ("a-team.yaml", "routes: {}\n"),
+                ("other.yaml", "routes: {}\n"),
+            ],
+        );
+        let other = dir.join("other.yaml").display().to_string();
+        let sources = format!("{}, {}/*-team.yaml, {}", other, dir.display(), other);
+
+        let files = links_files(&sources).unwrap();
+
+        assert_eq!(
+            files,
+            vec![
+                other,
+                dir.join("a-team.yaml").display().to_string(),
+                dir.join("b-team.yaml").display().to_string(),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_load_routes_conflicts() {
+        let dir = links_dir(
+            "conflicts",
+            &[
+                ("a.yaml", "routes:\n  wiki: https://a.example.com\n"),
+                (
+                    "b.yaml",
+                    "routes:\n  other: https://example.com\n  wiki: https://b.example.com\n",
+                ),
+            ],
+        );
+        let sources = dir.to_str().unwrap();
+
+        let err = load_routes(sources, ConflictPolicy::Error, None).unwrap_err();
+        assert!(matches!(err, LoadError::Lint { .. }));
+        assert_eq!(err.location(), Some((3, 3)));
+        assert!(err.to_string().contains("a.yaml"));
+
+        let routes = load_routes(sources, ConflictPolicy::FirstWins, None).unwrap();
+        assert_eq!(routes.fetch("wiki").unwrap().url, "https://a.example.com");
+
+        let (routes, warnings) = load_and_lint(sources, ConflictPolicy::LastWins, None).unwrap();
+        assert_eq!(routes.fetch("wiki").unwrap().url, "https://b.example.com");
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(warnings[0].file.as_deref(), dir.join("b.yaml").to_str());
+    }
+
+    #[test]
+    fn test_load_routes_namespaces() {
+        let dir = links_dir(
+            "namespaces",
+            &[
+                (
+                    "infra.yaml",
+                    "namespace: infra\nroutes:\n  dash: https://dash.example.com\npatterns:\n  - glob: 'docs/*'\n    url: https://docs.example.com/{1}\nindexes:\n  disabled: [secret]\n",
+                ),
+                ("wiki.yaml", "routes:\n  home: https://wiki.example.com\n"),
+                ("nested.yaml", "namespace: oncall\nroutes:\n  '': https://oncall.example.com\n"),
+            ],
+        );
+        let sources = format!(
+            "{0}/infra.yaml, wiki={0}/wiki.yaml, teams/={0}/nested.yaml",
+            dir.display()
+        );
+        let routes = load_routes(&sources, ConflictPolicy::Error, None).unwrap();
+
+        assert!(routes.fetch("dash").is_none());
+        assert!(routes.fetch("infra/dash").is_some());
+        assert!(routes.fetch("wiki/home").is_some());
+        assert!(routes.fetch("teams/oncall").is_some());
+        assert!(routes.indexes().is_disabled("infra/secret"));
+        assert_eq!(
+            routes.resolve("infra/docs/setup").unwrap().destination(&[]),
+            "https://docs.example.com/setup"
+        );
+        assert!(routes.resolve("docs/setup").is_none());
+    }
+
+    #[test]
+    fn test_source_entries() {
+        let entries: Vec<_> = source_entries(
+            "infra=/conf/infra.yaml, teams/ = teams.yaml, /conf/a=b/links.yaml, v1.0=x.yaml",
+        )
+        .collect();
+
+        assert_eq!(
+            entries,
+            vec![
+                (Some("infra"), "/conf/infra.yaml"),
+                (Some("teams/"), "teams.yaml"),
+                (None, "/conf/a=b/links.yaml"),
+                (None, "v1.0=x.yaml"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_load_routes_namespace_conflicts() {
+        let dir = links_dir(
+            "namespace-conflicts",
+            &[
+                ("a.yaml", "routes:\n  infra/dash: https://a.example.com\n  infra/wiki: https://wiki.example.com\n"),
+                ("b.yaml", "namespace: infra\nroutes:\n  dash: https://b.example.com\n"),
+            ],
+        );
+        let sources = dir.to_str().unwrap();
+
+        let err = load_routes(sources, ConflictPolicy::Error, None).unwrap_err();
+        assert_eq!(err.location(), Some((3, 3)));
+        assert!(err.to_string().contains("'infra/dash'"));
+
+        let (routes, warnings) = load_and_lint(sources, ConflictPolicy::LastWins, None).unwrap();
+        assert_eq!(
+            routes.fetch("infra/dash").unwrap().url,
+            "https://b.example.com"
+        );
+        assert_eq!(
+            warnings
+                .iter()
+                .map(|warning| (warning.key.as_str(), warning.location()))
+                .collect::<Vec<_>>(),
+            vec![("infra/dash", Some((3, 3))), ("infra/wiki", Some((3, 3)))]
+        );
+        assert_eq!(warnings[0].file.as_deref(), dir.join("b.yaml").to_str());
+        assert!(warnings[1].message.contains("'infra' namespace"));
+    }
+
+    #[test]
+    fn test_content_hash() {
+        let dir = links_dir(
+            "hash",
+            &[("a.yaml", "routes: {}\n"), ("b.yaml", "routes: {}\n")],
+        );
+        let sources = dir.to_str().unwrap();
+
+        let hash = content_hash(sources).unwrap();
+        assert_eq!(hash.len(), 64);
+        assert_eq!(content_hash(sources).unwrap(), hash);
+
+        fs::write(dir.join("b.yaml"), "routes: {}\n").unwrap();
+        assert_eq!(content_hash(sources).unwrap(), hash);
+
+        fs::write(dir.join("b.yaml"), "routes:\n  e: https://example.com\n").unwrap();
+        assert_ne!(content_hash(sources).unwrap(), hash);
+
+        let mounted = format!("infra={}", sources);
+        assert_ne!(
+            content_hash(&mounted).unwrap(),
+            content_hash(sources).unwrap()
+        );
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_watch_paths() {
+        let dir = links_dir("watch", &[("links.yaml", "routes: {}\n")]);
+        let elsewhere = links_dir("watch-target", &[("target.yaml", "routes: {}\n")]);
+        std::os::unix::fs::symlink(elsewhere.join("target.yaml"), dir.join("linked.yaml")).unwrap();
+
+        let sources = format!(
+            "{0}/links.yaml, {0}/linked.yaml, teams=/conf/teams/*.yaml, links.yaml",
+            dir.display()
+        );
+
+        assert_eq!(
+            watch_paths(&sources),
+            vec![
+                dir.clone(),
+                elsewhere.canonicalize().unwrap(),
+                PathBuf::from("/conf/teams"),
+                PathBuf::from("."),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_revisions_path() {
+        let dir = links_dir("revisions", &[("links.yaml", "routes: {}\n")]);
+        let sources = format!("{0}/links.yaml, {0}", dir.display());
+
+        assert_eq!(
+            revisions_path(&sources),
+            Some(dir.join(".links.yaml.revisions"))
+        );
+        assert_eq!(
+            revisions_path(&format!("wiki={}/*.yaml", dir.display())),
+            Some(dir.join(".links.revisions"))
+        );
+        assert_eq!(
+            revisions_path("links.yaml"),
+            Some(PathBuf::from("./.links.yaml.revisions"))
+        );
+        assert_eq!(revisions_path(""), None);
+
+        // The revisions file is never taken for a links file.
+        assert!(!affects_links(&sources, &dir.join(".links.yaml.revisions")));
+        assert!(!affects_links(&sources, &dir.join(".links.revisions")));
+    }
+
+    #[test]
+    fn test_affects_links() {
+        let sources = "/conf/links.yaml, /conf/teams/*.yaml";
+
+        assert!(affects_links(sources, Path::new("/conf/links.yaml")));
+        assert!(affects_links(sources, Path::new("/conf/..data")));
+        assert!(affects_links(
+            sources,
+            Path::new("/conf/..2026_10_18_09_00_00.123")
+        ));
+        assert!(affects_links(sources, Path::new("/conf/teams/infra.yaml")));
+        assert!(!affects_links(sources, Path::new("/conf/.links.yaml.swp")));
+        assert!(!affects_links(sources, Path::new("/conf/links.yaml~")));
+        assert!(!affects_links(sources, Path::new("/conf/teams/notes.txt")));
+        assert!(!affects_links(sources, Path::new("/other/links.yaml")));
+        assert!(!affects_links(sources, Path::new("/conf/infra.yaml")));
+
+        let dir = links_dir("affects", &[("links.yaml", "routes: {}\n")]);
+        let sources = dir.join("links.yaml");
+        let sources = sources.to_str().unwrap();
+        assert!(affects_links(sources, &dir.join(".").join("links.yaml")));
+        assert!(!affects_links(
+            sources,
+            &dir.join("nested").join("links.yaml")
+        ));
+    }
+
+    #[test]
+    fn test_load_routes_missing_file() {
+        let err = load_routes("/does/not/exist.yaml", ConflictPolicy::Error, None).unwrap_err();
+
+        assert!(matches!(err, LoadError::Io { .. }));
+        assert_eq!(err.location(), None);
+    }
+
+    #[test]
+    fn test_load_routes_reports_location() {
+        let path = links_file(
+            "invalid.yaml",
+            "routes:\n  test: https://example.com\n  broken: [\n",
+        );
+        let err = load_routes(path.to_str().unwrap(), ConflictPolicy::Error, None).unwrap_err();
+
+        assert!(matches!(err, LoadError::Parse { .. }));
+
+        let (line, _) = err.location().expect("Parse errors should have a location");
+        assert!(line >= 3);
+    }
+
+    #[test]
+    fn test_load_routes_json_and_toml() {
+        let json = links_file(
+            "links.json",
+            r#"{"routes": {"e": "https://example.com", "gh": {"url": "https://github.com/{1}", "status": 308}}}"#,
+        );
+        let toml = links_file(
+            "links.toml",
+            "namespace = \"infra\"\n\n[routes]\ndash = \"https://dash.example.com\"\n\n[[patterns]]\nglob = \"docs/*\"\nurl = \"https://docs.example.com/{1}\"\n",
+        );
+        let sources = format!("{},{}", json.display(), toml.display());
+        let routes = load_routes(&sources, ConflictPolicy::Error, None).unwrap();
+
+        assert_eq!(routes.fetch("gh").unwrap().status.code(), 308);
+        assert!(routes.fetch("infra/dash").is_some());
+        assert!(routes.resolve("infra/docs/x").is_some());
+    }
+
+    #[test]
+    fn test_load_routes_format_override() {
+        let path = links_file(
+            "links.conf",
+            "{\"routes\": {\"e\": \"https://example.com\"}}",
+        );
+
+        let routes = load_routes(
+            path.to_str().unwrap(),
+            ConflictPolicy::Error,
+            Some(LinksFormat::Json),
+        );
+        assert!(routes.unwrap().fetch("e").is_some());
+
+        let err = load_routes(
+            path.to_str().unwrap(),
+            ConflictPolicy::Error,
+            Some(LinksFormat::Toml),
+        );
+        assert!(matches!(err, Err(LoadError::Parse { .. })));
+    }
+
+    #[test]
+    fn test_load_routes_reports_json_and_toml_locations() {
+        let json = links_file(
+            "invalid.json",
+            "{\n  \"routes\": {\n    \"e\": 42\n  }\n}\n",
+        );
+        let err = load_routes(json.to_str().unwrap(), ConflictPolicy::Error, None).unwrap_err();
+        assert!(matches!(err, LoadError::Parse { .. }));
+        assert_eq!(err.location().map(|(line, _)| line), Some(3));
+
+        let toml = links_file(
+            "invalid.toml",
+            "[routes]\ne = \"https://example.com\"\n  gh = [\n",
+        );
+        let err = load_routes(toml.to_str().unwrap(), ConflictPolicy::Error, None).unwrap_err();
+        let (line, _) = err.location().expect("TOML errors should have a location");
+        assert!(line >= 3);
+        assert!(err.to_string().contains(&format!("at line {}", line)));
+    }
+
+    #[test]
+    fn test_load_routes_rejects_json_and_toml_duplicate_keys() {
+        let json = links_file(
+            "duplicate.json",
+            "{\n  \"routes\": {\n    \"e\": \"https://a.example.com\",\n    \"e\": \"https://b.example.com\"\n  }\n}\n",
+        );
+        let err = load_routes(json.to_str().unwrap(), ConflictPolicy::Error, None).unwrap_err();
+        assert!(matches!(err, LoadError::Parse { .. }));
+        assert!(err.to_string().contains("'e' is defined more than once"));
+        assert_eq!(err.location().map(|(line, _)| line), Some(4));
+
+        let toml = links_file(
+            "duplicate.toml",
+            "[routes]\ne = \"https://a.example.com\"\n\"e\" = \"https://b.example.com\"\n",
+        );
+        let err = load_routes(toml.to_str().unwrap(), ConflictPolicy::Error, None).unwrap_err();
+        assert!(matches!(err, LoadError::Parse { .. }));
+        assert_eq!(err.location().map(|(line, _)| line), Some(3));
+    }
+
+    #[test]
+    fn test_load_routes_reports_invalid_pattern() {
+        let path = links_file(
+            "invalid-pattern.yaml",
+            "routes: {}\npatterns:\n  - regex: 'pr/('\n    url: https://example.com\n",
+        );
+        let err = load_routes(path.to_str().unwrap(), ConflictPolicy::Error, None).unwrap_err();
+
+        assert!(err.to_string().contains("'pr/('"));
+        assert!(err.location().is_some());
+    }
+}
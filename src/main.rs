@@ -1,42 +1,113 @@
 #[macro_use]
 extern crate rocket;
 
-use std::fs;
+use std::collections::{HashMap, HashSet};
+use std::io::Write;
 use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use std::time::Duration;
+
+use clap::Parser;
 
 use fern::colors::{Color, ColoredLevelConfig};
-use log::{debug, error, info};
+use log::{debug, error, info, warn};
 
 use rocket::fairing::AdHoc;
+use rocket::http::uri::Origin;
+use rocket::http::Accept;
+use rocket::response::content::RawHtml;
 use rocket::response::Redirect;
 use rocket::serde::json::Json;
+use rocket::tokio::time::MissedTickBehavior;
 use rocket::{Build, Request, Rocket, State};
-use tokio::sync::mpsc::{channel, Receiver, Sender};
+use tokio::sync::mpsc::{unbounded_channel, UnboundedReceiver};
 
 use notify::{Event, RecommendedWatcher, Watcher};
+use notify_debouncer_mini::{
+    new_debouncer_opt, Config as DebouncerConfig, DebounceEventResult, Debouncer,
+};
 
+use golinks::admin;
+use golinks::api::{self, LinkIndex};
+use golinks::cli::{self, Cli, Command, ServeArgs};
 use golinks::config::AppConfig;
-use golinks::models::{RequestTimer, Routes, StatusMessage};
+use golinks::git::{GitPoller, GitSource};
+use golinks::html::{self, DirectoryQuery, SortColumn};
+use golinks::link;
+use golinks::loader;
+use golinks::models::{
+    ApiResponse, Heartbeat, LinkRedirect, NotFoundMessage, RequestTimer, Routes, RoutesStatus,
+    SharedRoutes,
+};
+use golinks::reload::{Reloader, Trigger};
+use golinks::remote::{RemotePoller, RemoteSource};
+use golinks::store::{self, StoreKind};
+use golinks::suggest::{self, Suggestions};
+
+/// The response to a path that is not a known link. Browsers get an HTML page while
+/// everything else gets JSON.
+#[derive(Responder)]
+enum NotFound {
+    Html(RawHtml<String>),
+    Json(Json<NotFoundMessage>),
+}
+
+/// The response to a path that is handled by the links. A path without a link of its own
+/// that has links under it gets an index of them, as HTML for browsers and JSON otherwise.
+#[derive(Responder)]
+enum PathResponse {
+    Redirect(LinkRedirect),
+    IndexHtml(RawHtml<String>),
+    IndexJson(Json<ApiResponse<LinkIndex>>),
+}
+
+/// Gets a value indicating whether the client would rather have an HTML page than JSON.
+fn prefers_html(accept: Option<&Accept>) -> bool {
+    accept.is_some_and(|accept| accept.preferred().media_type().is_html())
+}
 
 #[catch(404)]
-fn not_found(req: &Request) -> Json<StatusMessage> {
-    Json(StatusMessage {
+fn not_found(req: &Request) -> NotFound {
+    let uri_path = req.uri().path();
+    let path = uri_path.url_decode_lossy();
+
+    // Pages under the reserved namespace are never links, so there is nothing to suggest.
+    let suggestions = match req.rocket().state::<SharedRoutes>() {
+        Some(routes) if !path.starts_with("/_/") => suggest::suggest(&routes.load(), &path),
+        _ => Suggestions::default(),
+    };
+
+    if prefers_html(req.accept()) {
+        return NotFound::Html(RawHtml(html::not_found(&path, &suggestions)));
+    }
+
+    NotFound::Json(Json(NotFoundMessage {
         status: "error".to_string(),
         message: format!("'{}' is not a known link.", req.uri()),
-    })
+        suggestions,
+    }))
 }
 
 /// A route that returns a 200 status code and a short json message. This is used to
 /// confirm that the web server is receiving requests but not performing any specific
-/// operation.
+/// operation. Links loaded from a git repository add the commit they were loaded from.
 #[get("/heartbeat")]
-async fn heartbeat() -> Json<StatusMessage> {
-    Json(StatusMessage {
+async fn heartbeat(routes: &State<SharedRoutes>) -> Json<Heartbeat> {
+    Json(Heartbeat {
         status: "ok".to_string(),
         message: "The server is running".to_string(),
+        commit: routes.commit(),
     })
 }
 
+/// A route that reports how many links are being served and whether the last reload of
+/// the links file succeeded. A failed reload keeps the previous routes active, so this is
+/// the place to look when a change to the links file does not seem to take effect.
+#[get("/status")]
+async fn status(routes: &State<SharedRoutes>) -> Json<RoutesStatus> {
+    Json(routes.status())
+}
+
 /// A debug route (this only compiles when using the debug profile, so it doesn't exist in
 /// production) to retrieve the current running configuration of the application. This shows
 /// how to retrieve state managed by the application
@@ -46,36 +117,91 @@ async fn show_configs(configs: &State<AppConfig>) -> Json<&AppConfig> {
     Json(configs)
 }
 
-#[get("/<path..>")]
-fn path(path: PathBuf, routes_map: &State<Routes>) -> Option<Redirect> {
-    let mut current = Some(path.as_path());
+/// Sends the reserved namespace to the link directory.
+#[get("/_")]
+fn reserved_root() -> Redirect {
+    Redirect::to(html::DIRECTORY_PATH)
+}
 
-    while current.is_some() {
-        let forward = routes_map.fetch(current?.to_str().unwrap());
+/// A browsable page listing every registered link. The links can be filtered by key prefix
+/// and by tag, and sorted by any column, all through the query string.
+#[get("/_/links?<prefix>&<tag>&<sort>&<order>")]
+fn directory(
+    prefix: Option<&str>,
+    tag: Option<&str>,
+    sort: Option<&str>,
+    order: Option<&str>,
+    routes: &State<SharedRoutes>,
+) -> RawHtml<String> {
+    let query = DirectoryQuery {
+        prefix: prefix.filter(|p| !p.is_empty()),
+        tag: tag.filter(|t| !t.is_empty()),
+        sort: SortColumn::parse(sort),
+        descending: order == Some("desc"),
+    };
 
-        if forward.is_some() {
-            let afterimage = path.strip_prefix(current.unwrap()).unwrap();
+    RawHtml(html::directory(&routes.load(), &query))
+}
 
-            let afterimage = if afterimage == Path::new("") {
-                afterimage.to_str().unwrap().to_string()
-            } else {
-                format!("/{}", afterimage.to_str().unwrap())
-            };
+/// The permalink of a single link, showing its target and metadata.
+#[get("/_/links/<key..>")]
+fn link_details(key: PathBuf, routes: &State<SharedRoutes>) -> Option<RawHtml<String>> {
+    let key = key.to_str()?;
+    let routes_map = routes.load();
+    let link = routes_map.fetch(key)?;
 
-            return forward.map(|x| x + &afterimage).map(Redirect::temporary);
-        }
-        current = current?.parent();
+    Some(RawHtml(html::link_details(key, link)))
+}
+
+/// Redirects to the link registered for the longest matching prefix of the path. Targets
+/// with placeholders have them filled from the rest of the path and the query string,
+/// while any other target gets the rest of the path inserted into its path. The query
+/// string of the request is merged into the target according to the link's query policy,
+/// and the redirect uses the status code and cache headers configured for the link.
+///
+/// Pattern links are only tried, in declared order, when no exact or prefix link matches.
+/// When nothing matches but there are links under the path, an index of them is returned
+/// instead, unless the links file disables the index for the path.
+#[get("/<path..>")]
+fn path(
+    path: PathBuf,
+    uri: &Origin<'_>,
+    accept: Option<&Accept>,
+    routes: &State<SharedRoutes>,
+) -> Option<PathResponse> {
+    let path = path.to_str()?;
+    let routes_map = routes.load();
+
+    if let Some(resolution) = routes_map.resolve(path) {
+        let query = uri
+            .query()
+            .map(|query| link::query_params(query.as_str()))
+            .unwrap_or_default();
+
+        return Some(PathResponse::Redirect(LinkRedirect::new(
+            resolution.link,
+            resolution.destination(&query),
+        )));
+    }
+
+    let links = routes_map.index(path)?;
+    if prefers_html(accept) {
+        return Some(PathResponse::IndexHtml(RawHtml(html::index(path, &links))));
     }
 
-    None
+    Some(PathResponse::IndexJson(Json(ApiResponse::ok(
+        format!("'{}' is not a link, listing the links under it.", path),
+        LinkIndex::new(path, &links),
+    ))))
 }
 
 /// Constructs the rocket that will be used based on the configuration passed to this function.
 /// This will then be used by the `rocket()` function to launch the application.
 ///
 /// The configuration passed in will be made available to routes using the `&State<AppConfig>`
-/// type as a parameter in the function.
-fn build_rocket(configs: AppConfig, registered_routes: Routes) -> Rocket<Build> {
+/// type as a parameter in the function. The routes are managed as a `SharedRoutes` so
+/// that they can be replaced while the rocket is running.
+fn build_rocket(configs: AppConfig, registered_routes: SharedRoutes) -> Rocket<Build> {
     info!("Building rocket...");
     let ship = rocket::build();
 
@@ -90,7 +216,14 @@ fn build_rocket(configs: AppConfig, registered_routes: Routes) -> Rocket<Build>
     };
 
     #[allow(unused_mut)]
-    let mut routes = routes![heartbeat, path];
+    let mut routes = routes![
+        heartbeat,
+        status,
+        reserved_root,
+        directory,
+        link_details,
+        path
+    ];
 
     // Since `show_configs` doesn't exist when compiling the release profile,
     // we need to use the same macro under this scope to prevent the scope from being
@@ -108,8 +241,49 @@ fn build_rocket(configs: AppConfig, registered_routes: Routes) -> Rocket<Build>
         routes.append(&mut debug_routes);
     };
 
+    let reloader = Reloader::new(configs.clone(), registered_routes.clone());
+
+    // A store that cannot be opened stops the rocket from igniting. Stores other than
+    // the links files load their links into the routes while igniting, and keep them
+    // in sync from then on.
+    let store = store::open(&configs, &reloader);
+    let kind = configs.store();
+    let synced_routes = registered_routes.clone();
+
     debug!("Mounting state and routes...");
-    ship.attach(AdHoc::on_ignite("logging ignite", |rocket| async {
+    ship.attach(AdHoc::try_on_ignite(
+        "link store",
+        move |rocket| async move {
+            let store = match store {
+                Ok(store) => store,
+                Err(err) => {
+                    error!("Could not open the {:?} link store: {}", kind, err);
+                    return Err(rocket);
+                }
+            };
+
+            if kind != StoreKind::File {
+                let changes = store.subscribe();
+                if let Err(err) = store::refresh(store.as_ref(), &synced_routes).await {
+                    error!(
+                        "Could not load the links from the {:?} link store: {}",
+                        kind, err
+                    );
+                    return Err(rocket);
+                }
+
+                info!(
+                    "Serving {} links from the {:?} link store",
+                    synced_routes.load().len(),
+                    kind
+                );
+                rocket::tokio::spawn(store::sync_routes(store.clone(), synced_routes, changes));
+            }
+
+            Ok(rocket.manage(store))
+        },
+    ))
+    .attach(AdHoc::on_ignite("logging ignite", |rocket| async {
         info!("Ignition complete! Launching rocket...");
         rocket
     }))
@@ -121,82 +295,222 @@ fn build_rocket(configs: AppConfig, registered_routes: Routes) -> Rocket<Build>
     }))
     .manage(configs)
     .manage(registered_routes)
+    .manage(reloader)
     .mount("/", routes)
+    .mount(api::BASE, api::routes())
+    .mount(admin::BASE, admin::routes())
+    .register(admin::BASE, admin::catchers())
     .register("/", catchers![not_found])
 }
 
-async fn create_rocket_from(configs: AppConfig) -> Rocket<Build> {
-    info!("Building routes...");
-    let links_file = configs.links_file();
-    let config_file =
-        fs::File::open(links_file).unwrap_or_else(|_| panic!("Unable to open {}", links_file));
+async fn create_rocket_from(configs: AppConfig, routes: SharedRoutes) -> Rocket<Build> {
+    let ship = build_rocket(configs, routes);
+
+    info!("Rocket build complete!");
+    ship
+}
 
-    debug!("Finished reading data from {}, parsing...", links_file);
+/// A watcher that only reports changes, leaving out files being opened and read, which
+/// includes the links files being read by every reload.
+struct ChangeWatcher(RecommendedWatcher);
+
+impl Watcher for ChangeWatcher {
+    fn new<F: notify::EventHandler>(
+        mut event_handler: F,
+        config: notify::Config,
+    ) -> notify::Result<Self> {
+        let watcher = RecommendedWatcher::new(
+            move |res: notify::Result<Event>| {
+                if res.as_ref().map_or(true, |event| !event.kind.is_access()) {
+                    event_handler.handle_event(res);
+                }
+            },
+            config,
+        )?;
 
-    let routes: Routes = serde_yaml::from_reader(config_file)
-        .unwrap_or_else(|_| panic!("Unable to parse {}", links_file));
+        Ok(Self(watcher))
+    }
 
-    debug!("Finished parsing {}", links_file);
-    let ship = build_rocket(configs.clone(), routes);
+    fn watch(&mut self, path: &Path, recursive_mode: notify::RecursiveMode) -> notify::Result<()> {
+        self.0.watch(path, recursive_mode)
+    }
 
-    info!("Rocket build complete!");
-    ship
+    fn unwatch(&mut self, path: &Path) -> notify::Result<()> {
+        self.0.unwatch(path)
+    }
+
+    fn kind() -> notify::WatcherKind {
+        RecommendedWatcher::kind()
+    }
 }
 
-fn async_watcher() -> notify::Result<(RecommendedWatcher, Receiver<Event>)> {
-    let (tx, rx) = channel(1);
-
-    // Automatically select the best implementation for your platform.
-    // You can also access each implementation directly e.g. INotifyWatcher.
-    let watcher = RecommendedWatcher::new(
-        move |res: notify::Result<Event>| {
-            if let Ok(event) = res {
-                debug!("Received event: {:?}", event);
-                if event.kind.is_modify() && !tx.is_closed() {
-                    tx.blocking_send(event).unwrap_or_else(|err| {
+/// Watches for changes, sending the paths that changed in batches once no more changes
+/// happened for `debounce`, so that an editor writing a file several times in a row
+/// only causes one reload.
+fn async_watcher(
+    debounce: Duration,
+) -> notify::Result<(Debouncer<ChangeWatcher>, UnboundedReceiver<Vec<PathBuf>>)> {
+    // The channel is unbounded, since watching another directory waits on the thread
+    // that sends the events, which must not be waiting for room in the channel.
+    let (tx, rx) = unbounded_channel();
+
+    let debouncer = new_debouncer_opt(
+        DebouncerConfig::default().with_timeout(debounce),
+        move |res: DebounceEventResult| match res {
+            Ok(events) => {
+                debug!("Received events: {:?}", events);
+                let paths = events.into_iter().map(|event| event.path).collect();
+                if !tx.is_closed() {
+                    tx.send(paths).unwrap_or_else(|err| {
                         error!("Could not send event: {}", err);
                     });
                 }
             }
+            Err(err) => error!("Could not watch the links files: {}", err),
         },
-        notify::Config::default().with_compare_contents(true),
     )?;
 
-    Ok((watcher, rx))
+    Ok((debouncer, rx))
 }
 
-async fn shutdown_on_event(shutdown: rocket::Shutdown, configs: AppConfig, tx: Sender<bool>) {
-    let (mut watcher, mut rx) = async_watcher().unwrap();
+/// How often directories that could not be watched are tried again, such as the
+/// directory of a links file that does not exist yet.
+const REARM_INTERVAL: Duration = Duration::from_secs(10);
 
-    if configs.watch() {
-        info!(
-            "File watching is enabled! Watching {} for changes...",
-            configs.links_file()
-        );
+/// Watches the directories of the links files and reloads them every time they change.
+/// The rocket keeps serving the previous routes while the new ones are being parsed, and
+/// keeps them if the new links file turns out to be invalid.
+async fn reload_on_event(configs: AppConfig, reloader: Reloader) {
+    let (mut debouncer, mut rx) = async_watcher(configs.debounce()).unwrap();
+    let mut watched = HashSet::new();
+    rearm(debouncer.watcher(), &mut watched, configs.links_file());
 
-        watcher
-            .watch(
-                Path::new(configs.links_file()),
-                notify::RecursiveMode::NonRecursive,
-            )
-            .unwrap_or_else(|_| error!("Could not watch links file"));
+    let mut interval = rocket::tokio::time::interval(REARM_INTERVAL);
+    interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+
+    loop {
+        let paths = rocket::tokio::select! {
+            paths = rx.recv() => match paths {
+                Some(paths) => paths,
+                None => break,
+            },
+            _ = interval.tick() => {
+                // A links file may have appeared while its directory was not watched.
+                if rearm(debouncer.watcher(), &mut watched, configs.links_file()) {
+                    reloader.reload(Trigger::Watch).await;
+                }
+                continue;
+            }
+        };
+
+        debug!("Received reload event for {:?}", paths);
+
+        // The watch on a directory ends when it is removed, so a directory that changed
+        // itself is watched again, in case it was removed and created again.
+        for path in &paths {
+            watched.remove(path);
+        }
+
+        let links_file = configs.links_file();
+        if paths
+            .iter()
+            .any(|path| loader::affects_links(links_file, path))
+        {
+            reloader.reload(Trigger::Watch).await;
+        }
+
+        // Replacing a symlink can move a links file to another directory.
+        rearm(debouncer.watcher(), &mut watched, links_file);
+    }
+}
+
+/// Reloads the links files every time the process receives `SIGHUP`.
+#[cfg(unix)]
+async fn reload_on_hangup(reloader: Reloader) {
+    use rocket::tokio::signal::unix::{signal, SignalKind};
+
+    let mut hangups = match signal(SignalKind::hangup()) {
+        Ok(hangups) => hangups,
+        Err(err) => return error!("Could not listen for SIGHUP: {}", err),
+    };
+
+    while hangups.recv().await.is_some() {
+        info!("Received SIGHUP! Reloading routes...");
+        reloader.reload(Trigger::Signal).await;
+    }
+}
 
-        if let Some(event) = rx.recv().await {
-            debug!("Received shutdown event: {:?}", event);
-            info!("Links file changed! Requesting shutdown...");
-            // Notify the main thread that we should reload
-            tx.send(true).await.unwrap();
+/// Watches the directories the links files are in that are not watched yet, and stops
+/// watching the ones they are no longer in. Returns whether a directory started being
+/// watched.
+fn rearm(watcher: &mut dyn Watcher, watched: &mut HashSet<PathBuf>, sources: &str) -> bool {
+    let paths = loader::watch_paths(sources);
 
-            shutdown.notify();
+    watched.retain(|path| {
+        if paths.contains(path) {
+            return true;
+        }
+
+        debug!("No longer watching {}", path.display());
+        let _ = watcher.unwatch(path);
+        false
+    });
+
+    let mut armed = false;
+    for path in paths {
+        if watched.contains(&path) {
+            continue;
+        }
+
+        match watcher.watch(&path, notify::RecursiveMode::NonRecursive) {
+            Ok(()) => {
+                debug!("Watching {} for changes", path.display());
+                watched.insert(path);
+                armed = true;
+            }
+            Err(err) => warn!(
+                "Could not watch {}, trying again in {}s: {}",
+                path.display(),
+                REARM_INTERVAL.as_secs(),
+                err
+            ),
         }
     }
+
+    armed
 }
 
 #[rocket::main]
-async fn main() {
+async fn main() -> ExitCode {
+    let result = match Cli::parse().command() {
+        Command::Serve(args) => {
+            serve(args).await;
+            return ExitCode::SUCCESS;
+        }
+        Command::Validate(links) => cli::validate(&links),
+        Command::Resolve { links, path } => cli::resolve(&links, &path),
+        Command::List { links, format } => cli::list(&links, format),
+    };
+
+    match result {
+        Ok(output) => {
+            // The output is often piped, so a reader that stops early is not an error.
+            let _ = writeln!(std::io::stdout(), "{}", output);
+            ExitCode::SUCCESS
+        }
+        Err(err) => {
+            eprintln!("{}", err);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+/// Serves the links, configured from the environment with `args` overriding it.
+async fn serve(args: ServeArgs) {
     #[cfg(debug_assertions)]
     println!("Building configuration...");
-    let configs = AppConfig::build().expect("Could not build configuration from environment");
+    let mut configs = AppConfig::build().expect("Could not build configuration from environment");
+    args.apply(&mut configs);
 
     #[cfg(debug_assertions)]
     println!("Building logger...");
@@ -230,11 +544,8 @@ async fn main() {
 
     debug!("Logger configuration finished!");
 
-    loop {
-        let configs = configs.clone();
-
-        println!(
-            r#"
+    println!(
+        r#"
   _____       _      _       _        
  / ____|     | |    (_)     | |       
 | |  __  ___ | |     _ _ __ | | _____ 
@@ -243,35 +554,101 @@ async fn main() {
  \_____|\___/|______|_|_| |_|_|\_\___/
  ==================================================                                     
         "#
-        );
+    );
 
-        info!("Initializing application...");
-        let ship = create_rocket_from(configs.clone())
-            .await
-            .ignite()
-            .await
-            .unwrap();
+    info!("Initializing application...");
+    info!("Building routes...");
+    let routes = match configs.store() {
+        // The links of an SQLite database are loaded from it once it is opened.
+        StoreKind::Sqlite => SharedRoutes::new(Routes::with_links(HashMap::new())),
+        StoreKind::File | StoreKind::Memory => {
+            let routes =
+                loader::load_routes(configs.links_file(), configs.conflicts(), configs.format())
+                    .unwrap_or_else(|err| panic!("{}", err));
+            let routes = SharedRoutes::new(routes);
+            routes.mark_loaded(loader::content_hash(configs.links_file()).ok());
+            routes
+        }
+    };
 
-        let shutdown = ship.shutdown();
+    let ship = create_rocket_from(configs.clone(), routes)
+        .await
+        .ignite()
+        .await
+        .unwrap();
+    let reloader = ship.state::<Reloader>().unwrap().clone();
+
+    // The links files are reloaded by the tasks below, while every other store keeps
+    // the routes in sync with the links it holds.
+    let files = configs.store() == StoreKind::File;
+    if !files && (configs.watch() || configs.remote_url().is_some() || configs.git_repo().is_some())
+    {
+        warn!("Watching links files, remote links and git repositories are ignored unless the links are stored in links files");
+    }
 
-        let (tx, mut rx) = channel(1);
+    #[cfg(unix)]
+    let hangup_task = files.then(|| rocket::tokio::spawn(reload_on_hangup(reloader.clone())));
 
-        let watcher_task = rocket::tokio::spawn(async move {
-            shutdown_on_event(shutdown, configs, tx).await;
-        });
+    let remote_task = match configs.remote_url().filter(|_| files) {
+        Some(url) => {
+            let source = RemoteSource::new(url, configs.format());
+            let mut poller = RemotePoller::new(source, reloader.clone(), configs.remote_interval());
 
-        ship.launch().await.unwrap();
+            // The remote links are fetched before launching, so that they are served
+            // from the start. Links that cannot be fetched yet are polled for again.
+            info!("Fetching remote links from {}...", url);
+            let _ = poller.poll().await;
+            Some(rocket::tokio::spawn(poller.run()))
+        }
+        None => None,
+    };
 
-        // We send the reload signal before the shutdown one,
-        // so if the reload receiver is empty we know that
-        // it was not because of changes to the links file
-        if rx.try_recv().is_err() {
-            break;
+    let git_task = match configs.git_repo().filter(|_| files) {
+        Some(repo) => {
+            let source = GitSource::new(
+                repo,
+                configs.git_ref(),
+                configs.git_paths(),
+                configs.format(),
+            )
+            .with_fetch(configs.git_fetch());
+            let mut poller = GitPoller::new(source, reloader.clone(), configs.git_interval());
+
+            // Like the remote links, the links of the git repository are read before
+            // launching.
+            info!("Reading links from {} at {}...", repo, configs.git_ref());
+            let _ = poller.poll().await;
+            Some(rocket::tokio::spawn(poller.run()))
         }
+        None => None,
+    };
 
+    let watcher_task = if files && configs.watch() {
+        Some(rocket::tokio::spawn(reload_on_event(configs, reloader)))
+    } else {
+        None
+    };
+
+    ship.launch().await.unwrap();
+
+    if let Some(watcher_task) = watcher_task {
         debug!("Shutting down watcher...");
         watcher_task.abort();
-        info!("Requesting service reload...\n\n\n")
+    }
+
+    if let Some(remote_task) = remote_task {
+        debug!("Shutting down remote poller...");
+        remote_task.abort();
+    }
+
+    if let Some(git_task) = git_task {
+        debug!("Shutting down git poller...");
+        git_task.abort();
+    }
+
+    #[cfg(unix)]
+    if let Some(hangup_task) = hangup_task {
+        hangup_task.abort();
     }
 
     info!("Service 'golinks' successfully shut down");
@@ -283,8 +660,13 @@ mod tests {
     use std::collections::HashMap;
     use std::time::Duration;
 
-    use rocket::http::{ContentType, Status};
+    use golinks::link::Link;
+    use golinks::models::{IndexSettings, LoadFailure, Routes};
+    use golinks::pattern::Pattern;
+
+    use rocket::http::{ContentType, Header, Status};
     use rocket::local::blocking::{Client, LocalResponse};
+    use rocket::serde::json::serde_json;
 
     /// Creates a test client using the specified configuration
     fn scaffold_client_with(configs: AppConfig) -> Client {
@@ -292,8 +674,66 @@ mod tests {
             ("test".to_string(), "https://example.com".to_string()),
             ("e/x".to_string(), "https://example.com".to_string()),
             ("e".to_string(), "https://differentexample.com".to_string()),
+            (
+                "gh".to_string(),
+                "https://github.com/{1}/{2}/pulls".to_string(),
+            ),
+            (
+                "jira".to_string(),
+                "https://jira.example.com/browse/PROJ-{*}".to_string(),
+            ),
+            (
+                "search".to_string(),
+                "https://google.com/search?q={q}".to_string(),
+            ),
+            (
+                "manual".to_string(),
+                "https://manual.example.com/?lang=en#top".to_string(),
+            ),
+            (
+                "infra/dash".to_string(),
+                "https://dash.example.com".to_string(),
+            ),
+            (
+                "infra/oncall".to_string(),
+                "https://oncall.example.com".to_string(),
+            ),
+            (
+                "private/payroll".to_string(),
+                "https://payroll.example.com".to_string(),
+            ),
         ]);
-        let routes = Routes::with_routes(route_map);
+        let status_links: HashMap<String, Link> = serde_yaml::from_str(
+            r#"
+            moved: { url: "https://example.com/moved", status: 301, max_age: 86400 }
+            found: { url: "https://example.com/found", status: 302 }
+            other: { url: "https://example.com/other", status: 303 }
+            permanent:
+              url: "https://example.com/permanent"
+              status: 308
+              cache_control: "public, max-age=31536000, immutable"
+            "#,
+        )
+        .unwrap();
+        let mut links: HashMap<String, Link> = route_map
+            .into_iter()
+            .map(|(key, url)| (key, Link::new(url)))
+            .collect();
+        links.extend(status_links);
+
+        let patterns = vec![
+            Pattern::regex(r"pr/(\d+)", "https://github.com/org/repo/pull/{1}").unwrap(),
+            Pattern::glob("docs/*/latest", "https://docs.example.com/{1}").unwrap(),
+            Pattern::regex("e/.*", "https://patternexample.com").unwrap(),
+        ];
+        let indexes = IndexSettings {
+            disabled: vec!["private".to_string()],
+        };
+        let routes = SharedRoutes::new(
+            Routes::with_links(links)
+                .with_patterns(patterns)
+                .with_indexes(indexes),
+        );
         Client::tracked(build_rocket(configs, routes)).expect("valid rocket instance")
     }
 
@@ -343,6 +783,87 @@ mod tests {
         );
     }
 
+    /// Test that the heartbeat and the status name the git commit the links were loaded
+    /// from, and only when they were loaded from a git repository.
+    #[test]
+    fn test_heartbeat_commit() {
+        let routes = SharedRoutes::new(Routes::with_routes(HashMap::new()));
+        let client = Client::tracked(build_rocket(AppConfig::default(), routes.clone()))
+            .expect("valid rocket instance");
+
+        let heartbeat: serde_json::Value = client.get("/heartbeat").dispatch().into_json().unwrap();
+        assert_eq!(heartbeat["status"], "ok");
+        assert!(heartbeat.get("commit").is_none());
+
+        let sha = "3f2a9c1b0d4e5f60718293a4b5c6d7e8f9012345";
+        routes.set_commit(Some(sha.to_string()));
+
+        let heartbeat: serde_json::Value = client.get("/heartbeat").dispatch().into_json().unwrap();
+        assert_eq!(heartbeat["commit"], sha);
+        let status: serde_json::Value = client.get("/status").dispatch().into_json().unwrap();
+        assert_eq!(status["commit"], sha);
+    }
+
+    /// Test that links changed in a store other than the links files are served, and
+    /// that a store that cannot be opened stops the rocket from igniting.
+    #[test]
+    fn test_link_store() {
+        let database = std::env::temp_dir().join(format!("golinks-{}-main.db", std::process::id()));
+        let _ = std::fs::remove_file(&database);
+
+        let mut configs = AppConfig::default();
+        configs.set_store(StoreKind::Sqlite);
+        configs.set_database(database.to_str().unwrap());
+        configs.set_admin_token("s3cret");
+
+        let routes = SharedRoutes::new(Routes::with_routes(HashMap::new()));
+        let client =
+            Client::tracked(build_rocket(configs.clone(), routes)).expect("valid rocket instance");
+        assert_eq!(client.get("/docs").dispatch().status(), Status::NotFound);
+
+        let response = client
+            .post("/_/admin/links")
+            .header(Header::new("Authorization", "Bearer s3cret"))
+            .header(ContentType::JSON)
+            .body(r#"{"key": "docs", "link": "https://docs.example.com"}"#)
+            .dispatch();
+        assert_eq!(response.status(), Status::Created);
+
+        // The routes follow the store in the background.
+        let mut response = client.get("/docs").dispatch();
+        for _ in 0..100 {
+            if response.status() != Status::NotFound {
+                break;
+            }
+            std::thread::sleep(Duration::from_millis(10));
+            response = client.get("/docs").dispatch();
+        }
+        assert_eq!(response.status(), Status::TemporaryRedirect);
+        drop(response);
+
+        let response = client
+            .post("/_/admin/reload")
+            .header(Header::new("Authorization", "Bearer s3cret"))
+            .dispatch();
+        assert_eq!(response.status(), Status::Conflict);
+
+        // The links are loaded from the database when the rocket ignites.
+        let routes = SharedRoutes::new(Routes::with_routes(HashMap::new()));
+        let _client = Client::tracked(build_rocket(configs.clone(), routes.clone()))
+            .expect("valid rocket instance");
+        assert_eq!(routes.load().len(), 1);
+
+        configs.set_database(std::env::temp_dir().to_str().unwrap());
+        let routes = SharedRoutes::new(Routes::with_routes(HashMap::new()));
+        match Client::tracked(build_rocket(configs, routes)) {
+            Ok(_) => panic!("The rocket ignited without its link store"),
+            Err(err) => assert!(matches!(
+                err.kind(),
+                rocket::error::ErrorKind::FailedFairings(_)
+            )),
+        }
+    }
+
     /// Test that a request returns a header with the duration of the request if
     /// profiling is enabled, and that the duration is smaller than 1 ms (if debug)
     /// or smaller than 200 μs (if release).
@@ -459,6 +980,450 @@ mod tests {
         );
     }
 
+    /// Test that replacing the shared routes takes effect on the running rocket
+    /// without rebuilding it.
+    #[test]
+    fn test_routes_replaced_in_place() {
+        let routes = SharedRoutes::new(Routes::with_routes(HashMap::from([(
+            "test".to_string(),
+            "https://example.com".to_string(),
+        )])));
+        let client = Client::tracked(build_rocket(AppConfig::default(), routes.clone()))
+            .expect("valid rocket instance");
+
+        let response = client.get("/test").dispatch();
+        assert_eq!(
+            response.headers().get_one("Location"),
+            Some("https://example.com")
+        );
+
+        routes.replace(Routes::with_routes(HashMap::from([(
+            "test".to_string(),
+            "https://differentexample.com".to_string(),
+        )])));
+
+        let response = client.get("/test").dispatch();
+        assert_eq!(response.status(), Status::TemporaryRedirect);
+        assert_eq!(
+            response.headers().get_one("Location"),
+            Some("https://differentexample.com")
+        );
+    }
+
+    /// Test that the status endpoint reports a failed reload while the previous
+    /// routes keep being served, and that a successful reload clears it.
+    #[test]
+    fn test_status_after_failed_reload() {
+        let routes = SharedRoutes::new(Routes::with_routes(HashMap::from([(
+            "test".to_string(),
+            "https://example.com".to_string(),
+        )])));
+        let client = Client::tracked(build_rocket(AppConfig::default(), routes.clone()))
+            .expect("valid rocket instance");
+
+        let status: serde_json::Value = client.get("/status").dispatch().into_json().unwrap();
+        assert_eq!(status["status"], "ok");
+        assert_eq!(status["routes"], 1);
+
+        routes.record_failure(LoadFailure {
+            message: "Unable to parse links.yaml".to_string(),
+            line: Some(3),
+            column: Some(5),
+            at: chrono::Utc::now().to_rfc3339(),
+            hash: None,
+        });
+
+        let status: serde_json::Value = client.get("/status").dispatch().into_json().unwrap();
+        assert_eq!(status["status"], "degraded");
+        assert_eq!(status["last_failure"]["line"], 3);
+
+        let response = client.get("/test").dispatch();
+        assert_eq!(
+            response.headers().get_one("Location"),
+            Some("https://example.com")
+        );
+
+        routes.replace(Routes::with_routes(HashMap::new()));
+
+        let status: serde_json::Value = client.get("/status").dispatch().into_json().unwrap();
+        assert_eq!(status["status"], "ok");
+        assert_eq!(status["routes"], 0);
+    }
+
+    /// Waits up to a few seconds for the watcher to serve `url` for the link `test`.
+    async fn wait_for_reload(routes: &SharedRoutes, url: &str) -> bool {
+        for _ in 0..50 {
+            if routes
+                .load()
+                .fetch("test")
+                .is_some_and(|link| link.url == url)
+            {
+                return true;
+            }
+            rocket::tokio::time::sleep(Duration::from_millis(100)).await;
+        }
+        false
+    }
+
+    fn watched_links(dir: &std::path::Path) -> (AppConfig, SharedRoutes) {
+        let mut configs = AppConfig::default();
+        configs.set_links_file(dir.join("links.yaml").to_str().unwrap());
+        configs.set_debounce_ms(50);
+        let routes = loader::load_routes(configs.links_file(), configs.conflicts(), None).unwrap();
+        (configs, SharedRoutes::new(routes))
+    }
+
+    fn links_yaml(url: &str) -> String {
+        format!("routes:\n  test: {}\n", url)
+    }
+
+    /// Test that the watcher picks up a ConfigMap update, which swaps the `..data`
+    /// symlink instead of modifying the links file, and keeps picking up later ones.
+    #[rocket::async_test]
+    async fn test_reload_on_configmap_swap() {
+        use std::os::unix::fs::symlink;
+
+        let dir = std::env::temp_dir().join(format!("golinks-{}-configmap", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(dir.join("..v1")).unwrap();
+        std::fs::write(
+            dir.join("..v1/links.yaml"),
+            links_yaml("https://v1.example.com"),
+        )
+        .unwrap();
+        symlink("..v1", dir.join("..data")).unwrap();
+        symlink("..data/links.yaml", dir.join("links.yaml")).unwrap();
+
+        let (configs, routes) = watched_links(&dir);
+        let watcher = rocket::tokio::spawn(reload_on_event(
+            configs.clone(),
+            Reloader::new(configs, routes.clone()),
+        ));
+        rocket::tokio::time::sleep(Duration::from_millis(200)).await;
+
+        for (previous, version) in [("..v1", "..v2"), ("..v2", "..v3")] {
+            let url = format!("https://{}.example.com", version.trim_start_matches('.'));
+            std::fs::create_dir_all(dir.join(version)).unwrap();
+            std::fs::write(dir.join(version).join("links.yaml"), links_yaml(&url)).unwrap();
+            symlink(version, dir.join("..data_tmp")).unwrap();
+            std::fs::rename(dir.join("..data_tmp"), dir.join("..data")).unwrap();
+            std::fs::remove_dir_all(dir.join(previous)).unwrap();
+
+            assert!(
+                wait_for_reload(&routes, &url).await,
+                "{} was not loaded",
+                version
+            );
+        }
+
+        watcher.abort();
+    }
+
+    /// Test that the watcher picks up editors saving to a new file and renaming it over
+    /// the links file, and links files that are deleted and created again.
+    #[rocket::async_test]
+    async fn test_reload_on_rename_and_recreate() {
+        let dir = std::env::temp_dir().join(format!("golinks-{}-rename", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        let links = dir.join("links.yaml");
+        std::fs::write(&links, links_yaml("https://v1.example.com")).unwrap();
+
+        let (configs, routes) = watched_links(&dir);
+        let watcher = rocket::tokio::spawn(reload_on_event(
+            configs.clone(),
+            Reloader::new(configs, routes.clone()),
+        ));
+        rocket::tokio::time::sleep(Duration::from_millis(200)).await;
+
+        std::fs::write(
+            dir.join(".links.yaml.swp"),
+            links_yaml("https://v2.example.com"),
+        )
+        .unwrap();
+        std::fs::rename(dir.join(".links.yaml.swp"), &links).unwrap();
+        assert!(wait_for_reload(&routes, "https://v2.example.com").await);
+
+        std::fs::remove_file(&links).unwrap();
+        std::fs::write(&links, links_yaml("https://v3.example.com")).unwrap();
+        assert!(wait_for_reload(&routes, "https://v3.example.com").await);
+
+        watcher.abort();
+    }
+
+    /// Test that the routes are only swapped when the links actually change, and not
+    /// when the links file is touched or only its comments change.
+    #[rocket::async_test]
+    async fn test_reload_skips_unchanged_links() {
+        let dir = std::env::temp_dir().join(format!("golinks-{}-unchanged", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        let links = dir.join("links.yaml");
+        std::fs::write(&links, links_yaml("https://v1.example.com")).unwrap();
+
+        let (configs, routes) = watched_links(&dir);
+        let loaded_at = routes.status().loaded_at;
+        let watcher = rocket::tokio::spawn(reload_on_event(
+            configs.clone(),
+            Reloader::new(configs, routes.clone()),
+        ));
+        rocket::tokio::time::sleep(Duration::from_millis(200)).await;
+
+        std::fs::write(&links, links_yaml("https://v1.example.com")).unwrap();
+        std::fs::write(
+            &links,
+            format!("# Links\n{}", links_yaml("https://v1.example.com")),
+        )
+        .unwrap();
+        rocket::tokio::time::sleep(Duration::from_millis(500)).await;
+        assert_eq!(routes.status().loaded_at, loaded_at);
+
+        std::fs::write(&links, links_yaml("https://v2.example.com")).unwrap();
+        assert!(wait_for_reload(&routes, "https://v2.example.com").await);
+        assert_ne!(routes.status().loaded_at, loaded_at);
+
+        watcher.abort();
+    }
+
+    /// Test that positional placeholders in a target are filled with the path
+    /// segments that follow the link instead of having them appended.
+    #[test]
+    fn test_template_segments() {
+        let client = scaffold_client();
+        let response = client.get("/gh/cryptaliagy/golinks").dispatch();
+
+        assert_eq!(response.status(), Status::TemporaryRedirect);
+        assert_eq!(
+            response.headers().get_one("Location"),
+            Some("https://github.com/cryptaliagy/golinks/pulls")
+        );
+
+        let response = client.get("/jira/1234").dispatch();
+
+        assert_eq!(
+            response.headers().get_one("Location"),
+            Some("https://jira.example.com/browse/PROJ-1234")
+        );
+    }
+
+    /// Test that named placeholders in a target are filled with the matching
+    /// query parameter, percent-encoded.
+    #[test]
+    fn test_template_query() {
+        let client = scaffold_client();
+        let response = client.get("/search?q=rust%20lang%26more").dispatch();
+
+        assert_eq!(response.status(), Status::TemporaryRedirect);
+        assert_eq!(
+            response.headers().get_one("Location"),
+            Some("https://google.com/search?q=rust%20lang%26more")
+        );
+    }
+
+    /// Test that pattern links redirect using their captures when no exact or
+    /// prefix link matches.
+    #[test]
+    fn test_patterns() {
+        let client = scaffold_client();
+        let response = client.get("/pr/42").dispatch();
+
+        assert_eq!(response.status(), Status::TemporaryRedirect);
+        assert_eq!(
+            response.headers().get_one("Location"),
+            Some("https://github.com/org/repo/pull/42")
+        );
+
+        let response = client.get("/docs/rocket/latest").dispatch();
+
+        assert_eq!(
+            response.headers().get_one("Location"),
+            Some("https://docs.example.com/rocket")
+        );
+
+        let response = client.get("/pr/not-a-number").dispatch();
+
+        assert_eq!(response.status(), Status::NotFound);
+    }
+
+    /// Test that exact and prefix links take precedence over pattern links.
+    #[test]
+    fn test_pattern_precedence() {
+        let client = scaffold_client();
+        let response = client.get("/e/l/ample").dispatch();
+
+        assert_eq!(
+            response.headers().get_one("Location"),
+            Some("https://differentexample.com/l/ample")
+        );
+    }
+
+    /// Test that the query string of the request is passed through to the target.
+    #[test]
+    fn test_query_preserved() {
+        let client = scaffold_client();
+        let response = client.get("/e/x/ample?q=foo").dispatch();
+
+        assert_eq!(response.status(), Status::TemporaryRedirect);
+        assert_eq!(
+            response.headers().get_one("Location"),
+            Some("https://example.com/ample?q=foo")
+        );
+    }
+
+    /// Test that the rest of the path is inserted into the path of a target that has
+    /// a query string and a fragment, and that the query strings are merged.
+    #[test]
+    fn test_target_with_query_and_fragment() {
+        let client = scaffold_client();
+        let response = client.get("/manual/guide?lang=fr&page=2").dispatch();
+
+        assert_eq!(response.status(), Status::TemporaryRedirect);
+        assert_eq!(
+            response.headers().get_one("Location"),
+            Some("https://manual.example.com/guide?lang=fr&page=2#top")
+        );
+    }
+
+    /// Test that links redirect with the status code they are configured with, and
+    /// that plain links keep redirecting with a 307 and no cache headers.
+    #[test]
+    fn test_redirect_status_codes() {
+        let client = scaffold_client();
+        let cases = [
+            (
+                "/moved",
+                Status::MovedPermanently,
+                "https://example.com/moved",
+            ),
+            ("/found", Status::Found, "https://example.com/found"),
+            ("/other", Status::SeeOther, "https://example.com/other"),
+            ("/test", Status::TemporaryRedirect, "https://example.com"),
+            (
+                "/permanent",
+                Status::PermanentRedirect,
+                "https://example.com/permanent",
+            ),
+        ];
+
+        for (path, status, location) in cases {
+            let response = client.get(path).dispatch();
+
+            assert_eq!(response.status(), status, "{}", path);
+            assert_eq!(response.headers().get_one("Location"), Some(location));
+        }
+
+        let response = client.get("/test").dispatch();
+        assert_eq!(response.headers().get_one("Cache-Control"), None);
+    }
+
+    /// Test that links send the cache headers they are configured with.
+    #[test]
+    fn test_redirect_cache_control() {
+        let client = scaffold_client();
+
+        let response = client.get("/moved/sub").dispatch();
+        assert_eq!(
+            response.headers().get_one("Location"),
+            Some("https://example.com/moved/sub")
+        );
+        assert_eq!(
+            response.headers().get_one("Cache-Control"),
+            Some("max-age=86400")
+        );
+
+        let response = client.get("/permanent").dispatch();
+        assert_eq!(
+            response.headers().get_one("Cache-Control"),
+            Some("public, max-age=31536000, immutable")
+        );
+    }
+
+    /// Test that the link directory lists the links as HTML and can be filtered.
+    #[test]
+    fn test_directory() {
+        let client = scaffold_client();
+        let response = client.get("/_/links").dispatch();
+
+        assert_eq!(response.status(), Status::Ok);
+        assert_eq!(response.content_type(), Some(ContentType::HTML));
+
+        let body = response.into_string().unwrap();
+        assert!(body.contains(r#"<tr id="e/x">"#));
+        assert!(body.contains("https://github.com/{1}/{2}/pulls"));
+
+        let body = client
+            .get("/_/links?prefix=e/&tag=")
+            .dispatch()
+            .into_string()
+            .unwrap();
+        assert!(body.contains(r#"<tr id="e/x">"#));
+        assert!(!body.contains(r#"<tr id="test">"#));
+    }
+
+    /// Test that every link has a permalink in the directory, and that the reserved
+    /// namespace never falls through to the links.
+    #[test]
+    fn test_directory_permalink() {
+        let client = scaffold_client();
+        let response = client.get("/_/links/e/x").dispatch();
+
+        assert_eq!(response.status(), Status::Ok);
+        assert!(response
+            .into_string()
+            .unwrap()
+            .contains("https://example.com"));
+
+        let response = client.get("/_/links/missing").dispatch();
+        assert_eq!(response.status(), Status::NotFound);
+
+        let response = client.get("/_").dispatch();
+        assert_eq!(response.status(), Status::SeeOther);
+        assert_eq!(response.headers().get_one("Location"), Some("/_/links"));
+    }
+
+    /// Test that a path with links under it but no link of its own lists those links.
+    #[test]
+    fn test_prefix_index() {
+        let client = scaffold_client();
+        let response = client.get("/infra").dispatch();
+
+        assert_eq!(response.status(), Status::Ok);
+        assert_eq!(
+            response.content_type(),
+            Some(ContentType::new("application", "json"))
+        );
+
+        let body: serde_json::Value = response.into_json().unwrap();
+        assert_eq!(body["data"]["prefix"], "infra");
+        assert_eq!(body["data"]["links"][0]["key"], "infra/dash");
+        assert_eq!(body["data"]["links"][1]["key"], "infra/oncall");
+
+        let response = client
+            .get("/infra/")
+            .header(Header::new("Accept", "text/html"))
+            .dispatch();
+
+        assert_eq!(response.status(), Status::Ok);
+        assert_eq!(response.content_type(), Some(ContentType::HTML));
+        assert!(response
+            .into_string()
+            .unwrap()
+            .contains(r#"<a href="/infra/oncall">infra/oncall</a>"#));
+    }
+
+    /// Test that a prefix whose index is disabled is still not found, while its links
+    /// keep working.
+    #[test]
+    fn test_prefix_index_disabled() {
+        let client = scaffold_client();
+
+        let response = client.get("/private").dispatch();
+        assert_eq!(response.status(), Status::NotFound);
+
+        let response = client.get("/private/payroll").dispatch();
+        assert_eq!(response.status(), Status::TemporaryRedirect);
+    }
+
     /// Test that a path that is not registered returns a 404 status code and a
     /// JSON response.
     #[test]
@@ -473,6 +1438,40 @@ mod tests {
         );
     }
 
+    /// Test that the JSON not-found response suggests similar links and the links
+    /// under the requested path.
+    #[test]
+    fn test_not_found_suggestions() {
+        let client = scaffold_client();
+        let response = client.get("/tset").dispatch();
+
+        assert_eq!(response.status(), Status::NotFound);
+
+        let body: serde_json::Value = response.into_json().unwrap();
+        assert_eq!(body["status"], "error");
+        assert_eq!(body["suggestions"]["similar"][0], "test");
+    }
+
+    /// Test that browsers get an HTML not-found page with suggestions.
+    #[test]
+    fn test_not_found_html() {
+        let client = scaffold_client();
+        let response = client
+            .get("/jirra")
+            .header(Header::new(
+                "Accept",
+                "text/html,application/xhtml+xml,application/xml;q=0.9,*/*;q=0.8",
+            ))
+            .dispatch();
+
+        assert_eq!(response.status(), Status::NotFound);
+        assert_eq!(response.content_type(), Some(ContentType::HTML));
+        assert!(response
+            .into_string()
+            .unwrap()
+            .contains(r#"<a href="/jira">jira</a>"#));
+    }
+
     /// Test that a multipath that is not registered returns a 404 status code and a
     /// JSON response.
     #[test]
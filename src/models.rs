@@ -1,12 +1,22 @@
 use std::collections::HashMap;
+use std::fmt;
+use std::sync::{Arc, RwLock};
 use std::time::SystemTime;
 
+use arc_swap::{ArcSwap, Guard};
 use log::info;
 use rocket::fairing::{Fairing, Info, Kind};
-use rocket::http::Header;
-use rocket::serde::{Deserialize, Serialize};
+use rocket::http::{Header, Status};
+use rocket::response::{self, Responder};
+use rocket::serde::de::{self, MapAccess, Visitor};
+use rocket::serde::{Deserialize, Deserializer, Serialize};
 use rocket::{Data, Request, Response};
+use tokio::sync::broadcast;
 
+use crate::link::Link;
+use crate::pattern::Pattern;
+use crate::suggest::Suggestions;
+use crate::trie::SegmentTrie;
 use crate::utils;
 
 /// Fairing for timing requests.
@@ -25,23 +35,834 @@ pub struct StatusMessage {
     pub message: String,
 }
 
-/// Struct for deserializing routes
-#[derive(Debug, Serialize, Deserialize)]
+/// Struct for serializing the heartbeat, which names the git commit the links are
+/// served from when they are loaded from a git repository.
+#[derive(Serialize)]
 #[serde(crate = "rocket::serde")]
+pub struct Heartbeat {
+    pub status: String,
+    pub message: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub commit: Option<String>,
+}
+
+/// Responder for redirecting to the destination of a link with the status code and
+/// cache headers configured for it.
+#[derive(Debug)]
+pub struct LinkRedirect {
+    status: Status,
+    location: String,
+    cache_control: Option<String>,
+}
+
+impl LinkRedirect {
+    /// Creates a redirect to `location` using the settings of `link`.
+    pub fn new(link: &Link, location: String) -> Self {
+        Self {
+            status: Status::new(link.status.code()),
+            location,
+            cache_control: link.cache_control(),
+        }
+    }
+}
+
+impl<'r> Responder<'r, 'static> for LinkRedirect {
+    fn respond_to(self, _: &'r Request<'_>) -> response::Result<'static> {
+        let mut response = Response::build();
+        response
+            .status(self.status)
+            .raw_header("Location", self.location);
+
+        if let Some(cache_control) = self.cache_control {
+            response.raw_header("Cache-Control", cache_control);
+        }
+
+        response.ok()
+    }
+}
+
+/// Which kind of rule a path was resolved with.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(crate = "rocket::serde", rename_all = "lowercase")]
+pub enum MatchRule {
+    /// The path is the key of a link.
+    Exact,
+    /// The path is a descendant of the key of a link.
+    Prefix,
+    /// The path matched a pattern link.
+    Pattern,
+}
+
+impl fmt::Display for MatchRule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            MatchRule::Exact => "exact",
+            MatchRule::Prefix => "prefix",
+            MatchRule::Pattern => "pattern",
+        };
+        f.write_str(name)
+    }
+}
+
+/// The outcome of resolving a path against the registered links. It borrows the link
+/// and the path it matched, and only builds the URL the path redirects to when asked.
+#[derive(Debug)]
+pub struct Resolution<'a> {
+    /// The key of the matched link, or the source of the matched pattern.
+    pub key: &'a str,
+    pub link: &'a Link,
+    pub rule: MatchRule,
+    /// The rest of the path after the key of the matched link, or the whole path for a
+    /// pattern.
+    rest: &'a str,
+    pattern: Option<&'a Pattern>,
+}
+
+impl Resolution<'_> {
+    /// Builds the URL the path redirects to, given the query parameters of the request
+    /// as they were sent.
+    pub fn destination(&self, query: &[&str]) -> String {
+        match self.pattern {
+            Some(pattern) => pattern
+                .redirect(self.rest, query)
+                .expect("The path matched the pattern"),
+            None => {
+                let remainder: Vec<&str> = self
+                    .rest
+                    .split('/')
+                    .filter(|segment| !segment.is_empty())
+                    .collect();
+                self.link.destination(&remainder, query)
+            }
+        }
+    }
+}
+
+/// The first path segment reserved for the pages served by golinks itself. Links can
+/// never be registered under it.
+pub const RESERVED_SEGMENT: &str = "_";
+
+/// Gets a value indicating whether `path` is under the reserved namespace.
+pub fn is_reserved(path: &str) -> bool {
+    path.trim_start_matches('/').split('/').next() == Some(RESERVED_SEGMENT)
+}
+
+/// Struct for serializing the response to a path that is not a known link.
+#[derive(Serialize)]
+#[serde(crate = "rocket::serde")]
+pub struct NotFoundMessage {
+    pub status: String,
+    pub message: String,
+    pub suggestions: Suggestions,
+}
+
+/// Struct for serializing the response of an API endpoint. It extends `StatusMessage`
+/// with the requested data.
+#[derive(Serialize)]
+#[serde(crate = "rocket::serde")]
+pub struct ApiResponse<T> {
+    pub status: String,
+    pub message: String,
+    pub data: T,
+}
+
+impl<T> ApiResponse<T> {
+    /// Creates a successful response carrying `data`.
+    pub fn ok(message: impl Into<String>, data: T) -> Self {
+        Self {
+            status: "ok".to_string(),
+            message: message.into(),
+            data,
+        }
+    }
+
+    /// Creates a failed response carrying `data`.
+    pub fn error(message: impl Into<String>, data: T) -> Self {
+        Self {
+            status: "error".to_string(),
+            message: message.into(),
+            data,
+        }
+    }
+}
+
+/// Struct for deserializing the settings of the index pages shown for prefixes that have
+/// links under them but no link of their own.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(crate = "rocket::serde", deny_unknown_fields)]
+pub struct IndexSettings {
+    /// Prefixes whose index pages are turned off, along with the index pages of every
+    /// prefix nested under them.
+    #[serde(default)]
+    pub disabled: Vec<String>,
+}
+
+impl IndexSettings {
+    /// Gets a value indicating whether the index page of `prefix` is turned off.
+    pub fn is_disabled(&self, prefix: &str) -> bool {
+        self.disabled.iter().any(|disabled| {
+            let disabled = disabled.trim_matches('/');
+            prefix
+                .strip_prefix(disabled)
+                .is_some_and(|rest| rest.is_empty() || rest.starts_with('/'))
+        })
+    }
+}
+
+/// How a link defined in more than one links file is handled.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(crate = "rocket::serde", rename_all = "kebab-case")]
+pub enum ConflictPolicy {
+    /// The links files are rejected.
+    #[default]
+    Error,
+    /// The definition from the file loaded first is used.
+    FirstWins,
+    /// The definition from the file loaded last is used.
+    LastWins,
+}
+
+/// The format a links file is written in.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(crate = "rocket::serde", rename_all = "lowercase")]
+pub enum LinksFormat {
+    Yaml,
+    Json,
+    Toml,
+}
+
+impl LinksFormat {
+    /// Detects the format of a links file from its extension.
+    pub fn from_path(path: &str) -> Option<Self> {
+        let extension = std::path::Path::new(path).extension()?.to_str()?;
+        match extension.to_ascii_lowercase().as_str() {
+            "yaml" | "yml" => Some(LinksFormat::Yaml),
+            "json" => Some(LinksFormat::Json),
+            "toml" => Some(LinksFormat::Toml),
+            _ => None,
+        }
+    }
+}
+
+/// A link that was defined in more than one links file.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Conflict {
+    pub key: String,
+    /// The file the link was first defined in.
+    pub first: Option<String>,
+    /// The file the link was defined in again.
+    pub second: Option<String>,
+}
+
+/// Struct for deserializing a links file.
+#[derive(Deserialize)]
+#[serde(crate = "rocket::serde")]
+struct RoutesFile {
+    #[serde(default)]
+    namespace: Option<String>,
+
+    #[serde(deserialize_with = "unique_links")]
+    routes: HashMap<String, Link>,
+
+    #[serde(default)]
+    patterns: Vec<Pattern>,
+
+    #[serde(default)]
+    indexes: IndexSettings,
+}
+
+/// Deserializes the links of a links file, rejecting keys that are defined more than once,
+/// since only the last definition would be used otherwise.
+fn unique_links<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<HashMap<String, Link>, D::Error> {
+    struct UniqueLinks;
+
+    impl<'de> Visitor<'de> for UniqueLinks {
+        type Value = HashMap<String, Link>;
+
+        fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            f.write_str("a map of keys to links")
+        }
+
+        fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Self::Value, A::Error> {
+            let mut links = HashMap::with_capacity(map.size_hint().unwrap_or_default());
+            while let Some(key) = map.next_key::<String>()? {
+                if links.contains_key(&key) {
+                    return Err(de::Error::custom(format!(
+                        "link '{}' is defined more than once",
+                        key
+                    )));
+                }
+                let link = map.next_value()?;
+                links.insert(key, link);
+            }
+            Ok(links)
+        }
+    }
+
+    deserializer.deserialize_map(UniqueLinks)
+}
+
+/// The links being served. The keys are indexed by segment when the routes are loaded,
+/// so that resolving a path is a single walk down that index.
+#[derive(Debug, Deserialize)]
+#[serde(crate = "rocket::serde", from = "RoutesFile")]
 pub struct Routes {
-    routes: HashMap<String, String>,
+    routes: SegmentTrie<(String, Link)>,
+    patterns: Vec<Pattern>,
+    indexes: IndexSettings,
+}
+
+/// A links file: its links, and the namespace its header mounts them under.
+#[derive(Debug, Deserialize)]
+#[serde(crate = "rocket::serde", from = "RoutesFile")]
+pub struct LinksFile {
+    /// The prefix every key of the file is mounted under, such as `infra`.
+    pub namespace: Option<String>,
+    /// The links of the file, relative to its namespace.
+    pub routes: Routes,
+}
+
+impl From<RoutesFile> for LinksFile {
+    fn from(file: RoutesFile) -> Self {
+        LinksFile {
+            namespace: file.namespace,
+            routes: Routes::with_links(file.routes)
+                .with_patterns(file.patterns)
+                .with_indexes(file.indexes),
+        }
+    }
+}
+
+impl From<RoutesFile> for Routes {
+    fn from(file: RoutesFile) -> Self {
+        LinksFile::from(file).routes
+    }
 }
 
 impl Routes {
-    /// Fetches the URL for a given link.
-    pub fn fetch(&self, link: &str) -> Option<String> {
-        self.routes.get(link).cloned()
+    /// Fetches the link registered for a given key.
+    pub fn fetch(&self, link: &str) -> Option<&Link> {
+        self.routes.get(link).map(|(_, link)| link)
+    }
+
+    /// Iterates over the registered links and their keys, in no particular order.
+    pub fn links(&self) -> impl Iterator<Item = (&str, &Link)> {
+        self.routes.values().map(|(key, link)| (key.as_str(), link))
+    }
+
+    /// Gets the pattern links, in declared order.
+    pub fn patterns(&self) -> &[Pattern] {
+        &self.patterns
     }
 
-    /// Creates a new `Routes` from an existing `HashMap`.
+    /// Creates a new `Routes` from an existing `HashMap` of keys to URLs.
     #[allow(dead_code)]
     pub fn with_routes(routes: HashMap<String, String>) -> Self {
-        Self { routes }
+        Self::with_links(
+            routes
+                .into_iter()
+                .map(|(key, url)| (key, Link::new(url)))
+                .collect(),
+        )
+    }
+
+    /// Creates a new `Routes` from an existing `HashMap` of keys to links.
+    pub fn with_links(routes: HashMap<String, Link>) -> Self {
+        Self {
+            routes: routes
+                .into_iter()
+                .map(|(key, link)| (key.clone(), (key, link)))
+                .collect(),
+            patterns: Vec::new(),
+            indexes: IndexSettings::default(),
+        }
+    }
+
+    /// Adds pattern links, which are tried in order after the exact and prefix links.
+    pub fn with_patterns(mut self, patterns: Vec<Pattern>) -> Self {
+        self.patterns.extend(patterns);
+        self
+    }
+
+    /// Gets the settings of the index pages.
+    pub fn indexes(&self) -> &IndexSettings {
+        &self.indexes
+    }
+
+    /// Records `source` as the links file every link was loaded from.
+    pub fn with_source(mut self, source: &str) -> Self {
+        let links = self
+            .routes
+            .values_mut()
+            .map(|(_, link)| link)
+            .chain(self.patterns.iter_mut().map(Pattern::link_mut));
+
+        for link in links {
+            link.source = Some(source.to_string());
+        }
+        self
+    }
+
+    /// Mounts every link under `namespace`, so that the key `dash` becomes `infra/dash`
+    /// in the `infra` namespace. Patterns and disabled indexes are mounted the same way.
+    pub fn with_namespace(self, namespace: &str) -> Result<Self, String> {
+        let namespace = namespace.trim_matches('/');
+        if namespace.is_empty() || namespace.contains(['*', '?']) {
+            return Err(format!("Invalid namespace '{}'", namespace));
+        }
+
+        let mount = |key: &str| match key {
+            "" => namespace.to_string(),
+            key => format!("{}/{}", namespace, key),
+        };
+
+        Ok(Self {
+            routes: self
+                .routes
+                .into_values()
+                .map(|(key, link)| (mount(&key), (mount(&key), link)))
+                .collect(),
+            patterns: self
+                .patterns
+                .into_iter()
+                .map(|pattern| pattern.with_prefix(namespace))
+                .collect::<Result<_, _>>()?,
+            indexes: IndexSettings {
+                disabled: self
+                    .indexes
+                    .disabled
+                    .iter()
+                    .map(|prefix| mount(prefix.trim_matches('/')))
+                    .collect(),
+            },
+        })
+    }
+
+    /// Adds the links, patterns and index settings of `other`, which was loaded after
+    /// these routes. Links registered in both are kept or replaced according to `policy`,
+    /// with the first definition kept when conflicts are an error, and are returned so
+    /// they can be reported.
+    pub fn merge(&mut self, other: Routes, policy: ConflictPolicy) -> Vec<Conflict> {
+        let mut conflicts = Vec::new();
+
+        for (key, link) in other.routes.into_values() {
+            if let Some((_, existing)) = self.routes.get(&key) {
+                conflicts.push(Conflict {
+                    key: key.clone(),
+                    first: existing.source.clone(),
+                    second: link.source.clone(),
+                });
+
+                if policy != ConflictPolicy::LastWins {
+                    continue;
+                }
+            }
+
+            self.routes.insert(&key.clone(), (key, link));
+        }
+
+        self.patterns.extend(other.patterns);
+        self.indexes.disabled.extend(other.indexes.disabled);
+
+        conflicts.sort_by(|a, b| a.key.cmp(&b.key));
+        conflicts
+    }
+
+    /// Sets which prefixes get no index page.
+    pub fn with_indexes(mut self, indexes: IndexSettings) -> Self {
+        self.indexes = indexes;
+        self
+    }
+
+    /// Lists the links nested under `prefix`, sorted by key, for its index page.
+    ///
+    /// There is no index for the root, for a prefix without any links under it, or for a
+    /// prefix whose index is disabled in the links file. The index is only meant for
+    /// paths that do not resolve, since a link for the prefix or one of its ancestors
+    /// would otherwise redirect.
+    pub fn index(&self, prefix: &str) -> Option<Vec<(&str, &Link)>> {
+        let prefix = prefix.trim_matches('/');
+        if prefix.is_empty() || self.indexes.is_disabled(prefix) {
+            return None;
+        }
+
+        let mut links: Vec<(&str, &Link)> = self
+            .links()
+            .filter(|(key, _)| {
+                key.strip_prefix(prefix)
+                    .is_some_and(|rest| rest.starts_with('/'))
+            })
+            .collect();
+
+        if links.is_empty() {
+            return None;
+        }
+
+        links.sort_by_key(|(key, _)| *key);
+        Some(links)
+    }
+
+    /// Resolves a path to the link it redirects to.
+    ///
+    /// The link registered for the longest prefix of the path wins, with an exact match
+    /// being the longest possible prefix. Pattern links are only tried, in declared order,
+    /// when no prefix matches. Paths under the reserved namespace never resolve, so that
+    /// no link or pattern can shadow the pages served by golinks itself.
+    ///
+    /// Resolving does not allocate, other than for the captures of a pattern link.
+    pub fn resolve<'a>(&'a self, path: &'a str) -> Option<Resolution<'a>> {
+        let path = path.trim_matches('/');
+        if is_reserved(path) {
+            return None;
+        }
+
+        if let Some(((key, link), rest)) = self.routes.longest_prefix(path) {
+            return Some(Resolution {
+                key,
+                link,
+                rule: if rest.trim_matches('/').is_empty() {
+                    MatchRule::Exact
+                } else {
+                    MatchRule::Prefix
+                },
+                rest,
+                pattern: None,
+            });
+        }
+
+        self.patterns
+            .iter()
+            .find(|pattern| pattern.is_match(path))
+            .map(|pattern| Resolution {
+                key: pattern.source(),
+                link: pattern.link(),
+                rule: MatchRule::Pattern,
+                rest: path,
+                pattern: Some(pattern),
+            })
+    }
+
+    /// Checks that no link is registered under the reserved namespace, and that no
+    /// pattern link starts with it.
+    pub fn validate(&self) -> Result<(), String> {
+        let mut reserved: Vec<&str> = self
+            .links()
+            .map(|(key, _)| key)
+            .filter(|key| is_reserved(key))
+            .collect();
+        reserved.sort();
+        reserved.extend(
+            self.patterns
+                .iter()
+                .filter(|pattern| pattern.starts_with_segment(RESERVED_SEGMENT))
+                .map(Pattern::source),
+        );
+
+        if reserved.is_empty() {
+            return Ok(());
+        }
+
+        Err(format!(
+            "Links under '{}/' are reserved, found: {}",
+            RESERVED_SEGMENT,
+            reserved.join(", ")
+        ))
+    }
+
+    /// Compares these routes with `newer`, finding the keys of the links that were
+    /// added, removed or changed. Pattern links are compared by pattern, and since the
+    /// first matching pattern wins, the order they are tried in is compared as well.
+    pub fn diff<'a>(&'a self, newer: &'a Routes) -> RoutesDiff {
+        let mut diff = RoutesDiff::default();
+
+        for (key, link) in newer.links() {
+            match self.fetch(key) {
+                None => diff.added.push(key.to_string()),
+                Some(previous) if previous != link => diff.changed.push(key.to_string()),
+                Some(_) => {}
+            }
+        }
+        diff.removed.extend(
+            self.links()
+                .filter(|(key, _)| newer.fetch(key).is_none())
+                .map(|(key, _)| key.to_string()),
+        );
+
+        let find = |routes: &'_ Routes, pattern: &Pattern| {
+            routes
+                .patterns
+                .iter()
+                .find(|other| other.kind() == pattern.kind() && other.source() == pattern.source())
+                .map(|other| other.link().clone())
+        };
+        for pattern in &newer.patterns {
+            match find(self, pattern) {
+                None => diff.added.push(pattern.source().to_string()),
+                Some(previous) if previous != *pattern.link() => {
+                    diff.changed.push(pattern.source().to_string())
+                }
+                Some(_) => {}
+            }
+        }
+        for pattern in &self.patterns {
+            if find(newer, pattern).is_none() {
+                diff.removed.push(pattern.source().to_string());
+            }
+        }
+
+        let kept = |routes: &'a Routes, other: &'a Routes| {
+            routes
+                .patterns
+                .iter()
+                .filter(move |pattern| find(other, pattern).is_some())
+                .map(|pattern| (pattern.kind(), pattern.source()))
+        };
+        diff.reordered = !kept(self, newer).eq(kept(newer, self));
+        diff.indexes = self.indexes != newer.indexes;
+
+        diff.added.sort();
+        diff.removed.sort();
+        diff.changed.sort();
+        diff
+    }
+
+    /// Gets the number of registered links, including pattern links.
+    pub fn len(&self) -> usize {
+        self.routes.len() + self.patterns.len()
+    }
+
+    /// Gets a value indicating whether there are no registered links.
+    pub fn is_empty(&self) -> bool {
+        self.routes.is_empty() && self.patterns.is_empty()
+    }
+}
+
+/// The keys of the links that differ between two versions of the routes.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize)]
+#[serde(crate = "rocket::serde")]
+pub struct RoutesDiff {
+    pub added: Vec<String>,
+    pub removed: Vec<String>,
+    pub changed: Vec<String>,
+    /// Whether the pattern links found in both versions are tried in another order.
+    pub reordered: bool,
+    /// Whether the settings of the index pages changed.
+    pub indexes: bool,
+}
+
+impl RoutesDiff {
+    /// The most keys listed for each kind of difference when describing it.
+    const LISTED: usize = 10;
+
+    /// Gets a value indicating whether nothing differs.
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty()
+            && self.removed.is_empty()
+            && self.changed.is_empty()
+            && !self.reordered
+            && !self.indexes
+    }
+}
+
+impl fmt::Display for RoutesDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let kinds = [
+            ("added", &self.added),
+            ("removed", &self.removed),
+            ("changed", &self.changed),
+        ];
+
+        for (i, (kind, keys)) in kinds.into_iter().enumerate() {
+            if i > 0 {
+                f.write_str(", ")?;
+            }
+            write!(f, "{} {}", keys.len(), kind)?;
+
+            if !keys.is_empty() {
+                let listed = keys.len().min(Self::LISTED);
+                write!(f, " ({}", keys[..listed].join(", "))?;
+                if keys.len() > listed {
+                    write!(f, " and {} more", keys.len() - listed)?;
+                }
+                f.write_str(")")?;
+            }
+        }
+
+        if self.reordered {
+            f.write_str(", patterns reordered")?;
+        }
+        if self.indexes {
+            f.write_str(", index settings changed")?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Details of a reload that was rejected because the links file was invalid.
+#[derive(Clone, Debug, Serialize)]
+#[serde(crate = "rocket::serde")]
+pub struct LoadFailure {
+    pub message: String,
+    pub line: Option<usize>,
+    pub column: Option<usize>,
+    pub at: String,
+    /// The content hash of the links files that were rejected.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub hash: Option<String>,
+}
+
+/// Struct for serializing the state of the active routes.
+#[derive(Clone, Debug, Serialize)]
+#[serde(crate = "rocket::serde")]
+pub struct RoutesStatus {
+    pub status: String,
+    pub message: String,
+    pub routes: usize,
+    pub loaded_at: String,
+    /// The content hash of the links files the active routes were loaded from.
+    pub hash: Option<String>,
+    /// The git commit the active routes were loaded from, if they are loaded from a git
+    /// repository.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub commit: Option<String>,
+    pub last_failure: Option<LoadFailure>,
+}
+
+struct RoutesState {
+    loaded_at: String,
+    hash: Option<String>,
+    commit: Option<String>,
+    last_failure: Option<LoadFailure>,
+}
+
+struct SharedRoutesInner {
+    routes: ArcSwap<Routes>,
+    state: RwLock<RoutesState>,
+    changes: broadcast::Sender<RoutesDiff>,
+}
+
+/// The most changes of the routes kept for subscribers that have not received them yet.
+pub const CHANGES_CAPACITY: usize = 64;
+
+/// A shared snapshot of the active `Routes` that can be swapped atomically.
+///
+/// Clones are handles to the same snapshot, which lets the file watcher
+/// replace the routes while request handlers keep serving the previous ones.
+#[derive(Clone)]
+pub struct SharedRoutes {
+    inner: Arc<SharedRoutesInner>,
+}
+
+impl SharedRoutes {
+    /// Creates a new `SharedRoutes` serving the given routes.
+    pub fn new(routes: Routes) -> Self {
+        Self {
+            inner: Arc::new(SharedRoutesInner {
+                routes: ArcSwap::from_pointee(routes),
+                state: RwLock::new(RoutesState {
+                    loaded_at: chrono::Utc::now().to_rfc3339(),
+                    hash: None,
+                    commit: None,
+                    last_failure: None,
+                }),
+                changes: broadcast::channel(CHANGES_CAPACITY).0,
+            }),
+        }
+    }
+
+    /// Gets the currently active routes.
+    pub fn load(&self) -> Guard<Arc<Routes>> {
+        self.inner.routes.load()
+    }
+
+    /// Atomically replaces the active routes. Requests that are already
+    /// holding the previous snapshot finish with it.
+    pub fn replace(&self, routes: Routes) {
+        self.replace_loaded(routes, None);
+    }
+
+    /// Atomically replaces the active routes with the ones loaded from links
+    /// files with the content hash `hash`.
+    pub fn replace_loaded(&self, routes: Routes, hash: Option<String>) {
+        let previous = self.inner.routes.swap(Arc::new(routes));
+        if self.inner.changes.receiver_count() > 0 {
+            let changes = previous.diff(&self.load());
+            if !changes.is_empty() {
+                let _ = self.inner.changes.send(changes);
+            }
+        }
+
+        let mut state = self.inner.state.write().unwrap();
+        state.loaded_at = chrono::Utc::now().to_rfc3339();
+        state.hash = hash;
+        state.last_failure = None;
+    }
+
+    /// Records that the active routes match the links files with the content
+    /// hash `hash`, such as when the files changed without the links in them
+    /// changing. Clears any rejected reload.
+    pub fn mark_loaded(&self, hash: Option<String>) {
+        let mut state = self.inner.state.write().unwrap();
+        state.hash = hash;
+        state.last_failure = None;
+    }
+
+    /// Subscribes to the changes of the active routes, which are sent every time
+    /// the routes are replaced with routes that differ from them.
+    pub fn subscribe(&self) -> broadcast::Receiver<RoutesDiff> {
+        self.inner.changes.subscribe()
+    }
+
+    /// Gets the content hash of the links files the active routes were loaded
+    /// from, if known.
+    pub fn hash(&self) -> Option<String> {
+        self.inner.state.read().unwrap().hash.clone()
+    }
+
+    /// Records the git commit the active routes were loaded from.
+    pub fn set_commit(&self, commit: Option<String>) {
+        self.inner.state.write().unwrap().commit = commit;
+    }
+
+    /// Gets the git commit the active routes were loaded from, if known.
+    pub fn commit(&self) -> Option<String> {
+        self.inner.state.read().unwrap().commit.clone()
+    }
+
+    /// Records that a reload was rejected. The active routes are left untouched
+    /// until the next successful call to `replace`.
+    pub fn record_failure(&self, failure: LoadFailure) {
+        self.inner.state.write().unwrap().last_failure = Some(failure);
+    }
+
+    /// Gets a summary of the active routes and the outcome of the last reload.
+    pub fn status(&self) -> RoutesStatus {
+        let state = self.inner.state.read().unwrap();
+        let routes = self.load().len();
+
+        let (status, message) = match &state.last_failure {
+            None => ("ok", format!("Serving {} links", routes)),
+            Some(_) => (
+                "degraded",
+                format!(
+                    "The last reload failed, still serving {} links from the previous load",
+                    routes
+                ),
+            ),
+        };
+
+        RoutesStatus {
+            status: status.to_string(),
+            message,
+            routes,
+            loaded_at: state.loaded_at.clone(),
+            hash: state.hash.clone(),
+            commit: state.commit.clone(),
+            last_failure: state.last_failure.clone(),
+        }
     }
 }
 
@@ -79,3 +900,122 @@ impl Fairing for RequestTimer {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn routes(links: &[(&str, &str)]) -> Routes {
+        Routes::with_routes(
+            links
+                .iter()
+                .map(|(key, url)| (key.to_string(), url.to_string()))
+                .collect(),
+        )
+    }
+
+    #[test]
+    fn test_diff() {
+        let previous = routes(&[
+            ("a", "https://a.com"),
+            ("b", "https://b.com"),
+            ("c", "https://c.com"),
+        ])
+        .with_patterns(vec![
+            Pattern::glob("docs/*", "https://docs.com/{1}").unwrap()
+        ]);
+        let newer = routes(&[
+            ("a", "https://a.com"),
+            ("c", "https://c.org"),
+            ("d", "https://d.com"),
+        ])
+        .with_patterns(vec![
+            Pattern::glob("docs/*", "https://docs.org/{1}").unwrap()
+        ]);
+
+        let diff = previous.diff(&newer);
+
+        assert_eq!(diff.added, vec!["d"]);
+        assert_eq!(diff.removed, vec!["b"]);
+        assert_eq!(diff.changed, vec!["c", "docs/*"]);
+        assert_eq!(
+            diff.to_string(),
+            "1 added (d), 1 removed (b), 2 changed (c, docs/*)"
+        );
+        assert!(newer.diff(&newer).is_empty());
+    }
+
+    #[test]
+    fn test_diff_patterns_order_and_indexes() {
+        let docs = || Pattern::glob("docs/*", "https://docs.com/{1}").unwrap();
+        let all = || Pattern::glob("**", "https://search.com/{1}").unwrap();
+        let previous = routes(&[]).with_patterns(vec![docs(), all()]);
+
+        let diff = previous.diff(&routes(&[]).with_patterns(vec![all(), docs()]));
+        assert!(diff.reordered);
+        assert!(!diff.is_empty());
+        assert_eq!(
+            diff.to_string(),
+            "0 added, 0 removed, 0 changed, patterns reordered"
+        );
+
+        let diff = previous.diff(&routes(&[]).with_patterns(vec![all()]));
+        assert!(!diff.reordered);
+        assert_eq!(diff.removed, vec!["docs/*"]);
+
+        let newer = routes(&[])
+            .with_patterns(vec![docs(), all()])
+            .with_indexes(IndexSettings {
+                disabled: vec!["hr".to_string()],
+            });
+        let diff = previous.diff(&newer);
+        assert!(diff.indexes);
+        assert_eq!(
+            diff.to_string(),
+            "0 added, 0 removed, 0 changed, index settings changed"
+        );
+    }
+
+    #[test]
+    fn test_reserved_namespace() {
+        let routes = routes(&[("", "https://root.com"), ("docs", "https://docs.com")])
+            .with_patterns(vec![Pattern::regex(".*", "https://any.com").unwrap()]);
+
+        assert!(routes.validate().is_ok());
+        assert!(routes.resolve("_/admin/reload").is_none());
+        assert!(routes.resolve("/_/status").is_none());
+        assert!(routes.resolve("_").is_none());
+        assert_eq!(
+            routes.resolve("_docs").map(|resolution| resolution.key),
+            Some("")
+        );
+
+        let routes = routes.with_patterns(vec![
+            Pattern::glob("_*/status", "https://status.com").unwrap(),
+            Pattern::glob("docs/*", "https://docs.com/{1}").unwrap(),
+        ]);
+        assert_eq!(
+            routes.validate().unwrap_err(),
+            "Links under '_/' are reserved, found: _*/status"
+        );
+    }
+
+    #[test]
+    fn test_diff_lists_a_few_keys() {
+        let newer = routes(
+            &(0..12)
+                .map(|i| (format!("k{:02}", i), "https://example.com".to_string()))
+                .collect::<Vec<_>>()
+                .iter()
+                .map(|(key, url)| (key.as_str(), url.as_str()))
+                .collect::<Vec<_>>(),
+        );
+
+        let diff = routes(&[]).diff(&newer);
+
+        assert_eq!(
+            diff.to_string(),
+            "12 added (k00, k01, k02, k03, k04, k05, k06, k07, k08, k09 and 2 more), 0 removed, 0 changed"
+        );
+    }
+}
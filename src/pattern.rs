@@ -0,0 +1,341 @@
+use regex::Regex;
+use regex_syntax::hir::literal::{ExtractKind, Extractor};
+use rocket::serde::{Deserialize, Serialize};
+
+use crate::link::Link;
+use crate::template::TemplateContext;
+
+/// The syntax a pattern link was written in.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PatternKind {
+    /// A regular expression that must match the whole path.
+    Regex,
+    /// A glob where `*` matches within a single segment, `**` matches across
+    /// segments and `?` matches a single character. Every wildcard is a capture.
+    Glob,
+}
+
+/// Struct for deserializing a pattern link, before it is compiled.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(crate = "rocket::serde")]
+pub struct PatternDef {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    regex: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    glob: Option<String>,
+    #[serde(flatten)]
+    link: Link,
+}
+
+/// A compiled pattern link. Captures from the pattern are made available to the
+/// placeholders of the target.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(crate = "rocket::serde", try_from = "PatternDef", into = "PatternDef")]
+pub struct Pattern {
+    kind: PatternKind,
+    source: String,
+    regex: Regex,
+    link: Link,
+}
+
+impl Pattern {
+    /// Compiles a regex pattern link. The regex is anchored to the whole path.
+    pub fn regex(source: &str, link: impl Into<Link>) -> Result<Self, String> {
+        let regex = Regex::new(&format!("^(?:{})$", source))
+            .map_err(|err| format!("Invalid regex pattern '{}': {}", source, err))?;
+
+        Ok(Self {
+            kind: PatternKind::Regex,
+            source: source.to_string(),
+            regex,
+            link: link.into(),
+        })
+    }
+
+    /// Compiles a glob pattern link.
+    pub fn glob(source: &str, link: impl Into<Link>) -> Result<Self, String> {
+        let regex = Regex::new(&glob_to_regex(source))
+            .map_err(|err| format!("Invalid glob pattern '{}': {}", source, err))?;
+
+        Ok(Self {
+            kind: PatternKind::Glob,
+            source: source.to_string(),
+            regex,
+            link: link.into(),
+        })
+    }
+
+    /// Gets the syntax the pattern was written in.
+    pub fn kind(&self) -> PatternKind {
+        self.kind
+    }
+
+    /// Gets the pattern as it was written in the links file.
+    pub fn source(&self) -> &str {
+        &self.source
+    }
+
+    /// Gets a value indicating whether the literal text the pattern starts with can match
+    /// `segment` as the first segment of a path. Patterns that start with a wildcard are
+    /// not considered, since any path could start with what they match.
+    pub fn starts_with_segment(&self, segment: &str) -> bool {
+        let Ok(hir) = regex_syntax::parse(self.regex.as_str()) else {
+            return false;
+        };
+        let prefixes = Extractor::new().kind(ExtractKind::Prefix).extract(&hir);
+        let under = format!("{}/", segment);
+
+        prefixes.literals().is_some_and(|literals| {
+            literals.iter().any(|literal| {
+                let literal = literal.as_bytes();
+                !literal.is_empty()
+                    && (literal.starts_with(under.as_bytes())
+                        || under.as_bytes().starts_with(literal)
+                        || literal == segment.as_bytes())
+            })
+        })
+    }
+
+    /// Gets the link that matching paths are redirected to.
+    pub fn link(&self) -> &Link {
+        &self.link
+    }
+
+    /// Gets the link that matching paths are redirected to, for the loader to fill in.
+    pub(crate) fn link_mut(&mut self) -> &mut Link {
+        &mut self.link
+    }
+
+    /// Mounts the pattern under `prefix`, so that it only matches paths under the prefix.
+    /// Captures keep their numbers, since the prefix is matched literally.
+    pub fn with_prefix(self, prefix: &str) -> Result<Self, String> {
+        let inner = self.regex.as_str();
+        let inner = inner.strip_prefix('^').unwrap_or(inner);
+        let inner = inner.strip_suffix('$').unwrap_or(inner);
+
+        let regex = Regex::new(&format!("^{}/{}$", regex::escape(prefix), inner))
+            .map_err(|err| format!("Invalid pattern '{}/{}': {}", prefix, self.source, err))?;
+
+        let source = match self.kind {
+            // Keep an alternation from spilling out of the prefix if the source is compiled again.
+            PatternKind::Regex if self.source.contains('|') => {
+                format!("{}/(?:{})", regex::escape(prefix), self.source)
+            }
+            PatternKind::Regex => format!("{}/{}", regex::escape(prefix), self.source),
+            PatternKind::Glob => format!("{}/{}", prefix, self.source),
+        };
+
+        Ok(Self {
+            source,
+            regex,
+            ..self
+        })
+    }
+
+    /// Gets a value indicating whether `path` matches the pattern.
+    pub fn is_match(&self, path: &str) -> bool {
+        self.regex.is_match(path)
+    }
+
+    /// Builds the destination for `path` if it matches the pattern.
+    pub fn redirect(&self, path: &str, query: &[&str]) -> Option<String> {
+        let captures = self.regex.captures(path)?;
+
+        let segments = captures
+            .iter()
+            .skip(1)
+            .map(|capture| capture.map_or("", |c| c.as_str()))
+            .collect();
+
+        let named = self
+            .regex
+            .capture_names()
+            .flatten()
+            .filter_map(|name| captures.name(name).map(|c| (name, c.as_str())))
+            .collect();
+
+        let context = TemplateContext {
+            segments,
+            named,
+            captures: true,
+            ..Default::default()
+        };
+
+        Some(self.link.render(context, query))
+    }
+}
+
+impl TryFrom<PatternDef> for Pattern {
+    type Error = String;
+
+    fn try_from(def: PatternDef) -> Result<Self, Self::Error> {
+        match (def.regex, def.glob) {
+            (Some(regex), None) => Pattern::regex(&regex, def.link),
+            (None, Some(glob)) => Pattern::glob(&glob, def.link),
+            (Some(regex), Some(glob)) => Err(format!(
+                "Pattern link '{}' must specify only one of `regex` or `glob`, found glob '{}'",
+                regex, glob
+            )),
+            (None, None) => Err(format!(
+                "Pattern link to '{}' must specify one of `regex` or `glob`",
+                def.link.url
+            )),
+        }
+    }
+}
+
+impl From<Pattern> for PatternDef {
+    fn from(pattern: Pattern) -> Self {
+        let (regex, glob) = match pattern.kind {
+            PatternKind::Regex => (Some(pattern.source), None),
+            PatternKind::Glob => (None, Some(pattern.source)),
+        };
+
+        PatternDef {
+            regex,
+            glob,
+            link: pattern.link,
+        }
+    }
+}
+
+/// Converts a glob to an anchored regex with a capture group for every wildcard.
+pub(crate) fn glob_to_regex(glob: &str) -> String {
+    let mut regex = String::from("^");
+    let mut chars = glob.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '*' if chars.peek() == Some(&'*') => {
+                chars.next();
+                regex.push_str("(.*)");
+            }
+            '*' => regex.push_str("([^/]*)"),
+            '?' => regex.push_str("([^/])"),
+            c => regex.push_str(&regex::escape(&c.to_string())),
+        }
+    }
+
+    regex.push('$');
+    regex
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_regex_is_anchored() {
+        let pattern = Pattern::regex(r"pr/(\d+)", "https://github.com/org/repo/pull/{1}").unwrap();
+
+        assert_eq!(
+            pattern.redirect("pr/42", &[]),
+            Some("https://github.com/org/repo/pull/42".to_string())
+        );
+        assert_eq!(pattern.redirect("pr/42/files", &[]), None);
+        assert_eq!(pattern.redirect("old/pr/42", &[]), None);
+    }
+
+    #[test]
+    fn test_regex_named_captures() {
+        let pattern = Pattern::regex(
+            r"(?P<team>[a-z]+)/oncall",
+            "https://oncall.example.com/{team}?tz={tz}",
+        )
+        .unwrap();
+
+        assert_eq!(
+            pattern.redirect("infra/oncall", &["tz=UTC"]),
+            Some("https://oncall.example.com/infra?tz=UTC".to_string())
+        );
+    }
+
+    #[test]
+    fn test_glob() {
+        let pattern = Pattern::glob("docs/*/latest", "https://docs.example.com/{1}").unwrap();
+
+        assert_eq!(
+            pattern.redirect("docs/rocket/latest", &[]),
+            Some("https://docs.example.com/rocket".to_string())
+        );
+        assert_eq!(pattern.redirect("docs/a/b/latest", &[]), None);
+
+        let pattern = Pattern::glob("src/**", "https://github.com/org/repo/{1}").unwrap();
+
+        assert_eq!(
+            pattern.redirect("src/a/b.rs", &[]),
+            Some("https://github.com/org/repo/a/b.rs".to_string())
+        );
+    }
+
+    #[test]
+    fn test_starts_with_segment() {
+        let matches = |pattern: Result<Pattern, String>| pattern.unwrap().starts_with_segment("_");
+
+        assert!(matches(Pattern::glob("_/admin/*", "https://x.com")));
+        assert!(matches(Pattern::glob("_*/status", "https://x.com")));
+        assert!(matches(Pattern::glob("_", "https://x.com")));
+        assert!(matches(Pattern::regex("docs/.*|_/.*", "https://x.com")));
+        assert!(matches(Pattern::regex("(?i)_/STATUS", "https://x.com")));
+        assert!(!matches(Pattern::glob("_docs/*", "https://x.com")));
+        assert!(!matches(Pattern::glob("docs/_/*", "https://x.com")));
+        assert!(!matches(Pattern::glob("**", "https://x.com")));
+        assert!(!matches(Pattern::regex("[a-z]+/(\\d+)", "https://x.com")));
+    }
+
+    #[test]
+    fn test_glob_escapes_regex_characters() {
+        let pattern = Pattern::glob("v1.0/*", "https://example.com/{1}").unwrap();
+
+        assert!(pattern.redirect("v1.0/x", &[]).is_some());
+        assert!(pattern.redirect("v1x0/x", &[]).is_none());
+    }
+
+    #[test]
+    fn test_with_prefix() {
+        let pattern = Pattern::regex(r"pr/(\d+)|issue/(\d+)", "https://example.com/{1}{2}")
+            .unwrap()
+            .with_prefix("v1.0")
+            .unwrap();
+
+        assert_eq!(pattern.source(), r"v1\.0/(?:pr/(\d+)|issue/(\d+))");
+        assert_eq!(
+            pattern.redirect("v1.0/issue/7", &[]).as_deref(),
+            Some("https://example.com/7")
+        );
+        assert!(pattern.redirect("issue/7", &[]).is_none());
+        assert!(pattern.redirect("v1x0/pr/7", &[]).is_none());
+
+        let pattern = Pattern::glob("docs/*", "https://docs.example.com/{1}")
+            .unwrap()
+            .with_prefix("infra")
+            .unwrap();
+
+        assert_eq!(pattern.source(), "infra/docs/*");
+        assert_eq!(
+            pattern.redirect("infra/docs/setup", &[]).as_deref(),
+            Some("https://docs.example.com/setup")
+        );
+    }
+
+    #[test]
+    fn test_deserialize_with_link_settings() {
+        let patterns: Vec<Pattern> = serde_yaml::from_str(
+            "- glob: 'docs/*'\n  url: https://docs.example.com/{1}?v=1\n  query: drop\n",
+        )
+        .unwrap();
+
+        assert_eq!(patterns[0].kind(), PatternKind::Glob);
+        assert_eq!(
+            patterns[0].redirect("docs/a", &["q=x"]),
+            Some("https://docs.example.com/a?v=1".to_string())
+        );
+    }
+
+    #[test]
+    fn test_invalid_pattern_reports_key() {
+        let err = Pattern::regex("pr/(", "https://example.com").unwrap_err();
+
+        assert!(err.contains("'pr/('"));
+    }
+}
@@ -0,0 +1,784 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock};
+
+use log::{debug, error, info, warn};
+use rocket::serde::json::serde_json;
+use rocket::serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use tokio::sync::Mutex;
+
+use crate::config::AppConfig;
+use crate::edit::{self, EditError, LinkEdit};
+use crate::link::Link;
+use crate::loader::{self, LinksDocument};
+use crate::models::{LoadFailure, Routes, RoutesDiff, SharedRoutes};
+use crate::store::{self, StoredLink};
+
+/// What asked for the links files to be reloaded.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Trigger {
+    /// The file watcher saw a links file change.
+    Watch,
+    /// The process received `SIGHUP`.
+    Signal,
+    /// An admin asked for a reload through the admin endpoint.
+    Admin,
+    /// The remote links changed.
+    Remote,
+    /// The tracked ref of the git repository moved to another commit.
+    Git,
+    /// A link is about to be changed, and the links files changed since they were
+    /// loaded.
+    Edit,
+}
+
+impl fmt::Display for Trigger {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Trigger::Watch => f.write_str("file change"),
+            Trigger::Signal => f.write_str("SIGHUP"),
+            Trigger::Admin => f.write_str("admin request"),
+            Trigger::Remote => f.write_str("remote change"),
+            Trigger::Git => f.write_str("new commit"),
+            Trigger::Edit => f.write_str("links file change before an edit"),
+        }
+    }
+}
+
+/// What a reload did.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(crate = "rocket::serde", rename_all = "lowercase")]
+pub enum ReloadStatus {
+    /// The links changed and the new routes are being served.
+    Reloaded,
+    /// The links files were loaded, but the links in them did not change.
+    Unchanged,
+    /// The links files were not loaded, since their contents did not change.
+    Skipped,
+    /// The links files were rejected and the previous routes are still served.
+    Failed,
+}
+
+/// Struct for serializing the outcome of a reload.
+#[derive(Clone, Debug, Serialize)]
+#[serde(crate = "rocket::serde")]
+pub struct ReloadOutcome {
+    pub status: ReloadStatus,
+    pub message: String,
+    /// The number of links being served after the reload.
+    pub routes: usize,
+    /// The content hash of the links files that were loaded.
+    pub hash: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub changes: Option<RoutesDiff>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub failure: Option<LoadFailure>,
+}
+
+/// Links the active routes were loaded with from somewhere other than the links files,
+/// which are loaded again along with the links files by every reload.
+#[derive(Clone, Debug, Default)]
+struct Loaded {
+    /// The links files read from a git repository, along with the commit they were
+    /// read at.
+    git: Option<(String, Vec<LinksDocument>)>,
+    remote: Option<LinksDocument>,
+}
+
+impl Loaded {
+    fn documents(&self) -> impl Iterator<Item = &LinksDocument> {
+        self.git
+            .iter()
+            .flat_map(|(_, documents)| documents)
+            .chain(&self.remote)
+    }
+}
+
+/// Links that were newly fetched from somewhere other than the links files.
+enum Update {
+    Git {
+        commit: String,
+        documents: Vec<LinksDocument>,
+    },
+    Remote(LinksDocument),
+}
+
+/// The revision of the links files, along with the revision each link was last changed
+/// at. Links files do not keep revisions, so they are kept in a revisions file next to
+/// them, which lets revisions keep increasing across restarts.
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(crate = "rocket::serde")]
+struct Revisions {
+    revision: u64,
+    links: HashMap<String, LinkRevision>,
+}
+
+/// The revision a link was last changed at.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(crate = "rocket::serde")]
+struct LinkRevision {
+    revision: u64,
+    /// The hash of the contents of the link at the revision, which tells whether the
+    /// link changed while the service was not running.
+    hash: String,
+}
+
+impl Revisions {
+    /// Reads the revisions kept in the revisions file at `path`. Without a revisions
+    /// file, revisions start from `store::initial_revision`, so that they are still
+    /// greater than the ones handed out before.
+    fn read(path: Option<&Path>) -> Self {
+        let start = || Revisions {
+            revision: store::initial_revision(),
+            links: HashMap::new(),
+        };
+        let Some(path) = path else {
+            return start();
+        };
+
+        match fs::read_to_string(path) {
+            Ok(contents) => serde_json::from_str(&contents).unwrap_or_else(|err| {
+                warn!("Ignoring the revisions in {}: {}", path.display(), err);
+                start()
+            }),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => start(),
+            Err(err) => {
+                warn!(
+                    "Could not read the revisions in {}: {}",
+                    path.display(),
+                    err
+                );
+                start()
+            }
+        }
+    }
+
+    /// Moves to the next revision if the links of `routes` differ from the links the
+    /// revisions were last updated with, or if a link was `changed` through an edit.
+    /// Links that were added or changed are changed at the new revision. Returns whether
+    /// the revision moved.
+    fn update(&mut self, routes: &Routes, changed: Option<&str>) -> bool {
+        let next = self.revision + 1;
+        let hashes: HashMap<&str, String> = routes
+            .links()
+            .map(|(key, link)| (key, link_hash(link)))
+            .collect();
+
+        let count = self.links.len();
+        self.links
+            .retain(|key, _| hashes.contains_key(key.as_str()));
+        let mut moved = self.links.len() != count;
+
+        for (key, hash) in hashes {
+            let unchanged = self
+                .links
+                .get(key)
+                .is_some_and(|link| link.hash == hash && changed != Some(key));
+            if !unchanged {
+                let link = LinkRevision {
+                    revision: next,
+                    hash,
+                };
+                self.links.insert(key.to_string(), link);
+                moved = true;
+            }
+        }
+
+        if moved {
+            self.revision = next;
+        }
+        moved
+    }
+}
+
+/// Hashes how a link is served, leaving out the links file it was loaded from.
+fn link_hash(link: &Link) -> String {
+    let link = Link {
+        source: None,
+        ..link.clone()
+    };
+    let contents = serde_json::to_string(&link).unwrap_or_default();
+    Sha256::digest(contents.as_bytes())
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+/// Replaces the revisions file at `path` with `contents`, through a temporary file like
+/// the links files.
+fn write_revisions(path: &Path, contents: &str) -> io::Result<()> {
+    let name = path
+        .file_name()
+        .and_then(|name| name.to_str())
+        .unwrap_or("revisions");
+    let temporary = path.with_file_name(format!("{}.{}.tmp", name, std::process::id()));
+
+    let written = fs::write(&temporary, contents).and_then(|_| fs::rename(&temporary, path));
+    if written.is_err() {
+        let _ = fs::remove_file(&temporary);
+    }
+    written
+}
+
+/// Reloads the links files into the shared routes. Every way of reloading goes through
+/// the same reloader, which validates the links files before swapping them in and only
+/// runs one reload at a time.
+#[derive(Clone)]
+pub struct Reloader {
+    configs: AppConfig,
+    routes: SharedRoutes,
+    /// Held for the whole of a reload. Guards the links the active routes were loaded
+    /// with from git and remote sources.
+    lock: Arc<Mutex<Loaded>>,
+    /// Only changed while holding `lock`.
+    revisions: Arc<RwLock<Revisions>>,
+    /// The revisions file, if the links are loaded from links files.
+    revisions_path: Option<PathBuf>,
+}
+
+impl Reloader {
+    /// Creates a reloader loading the links files of `configs` into `routes`. The links
+    /// of `routes` that changed since the revisions file was last written are moved to a
+    /// new revision.
+    pub fn new(configs: AppConfig, routes: SharedRoutes) -> Self {
+        let revisions_path = loader::revisions_path(configs.links_file());
+        let mut revisions = Revisions::read(revisions_path.as_deref());
+        if revisions.update(&routes.load(), None) {
+            if let Some(path) = &revisions_path {
+                let contents = serde_json::to_string(&revisions).unwrap_or_default();
+                if let Err(err) = write_revisions(path, &contents) {
+                    error!(
+                        "Could not write the revisions to {}: {}",
+                        path.display(),
+                        err
+                    );
+                }
+            }
+        }
+
+        Self {
+            configs,
+            routes,
+            lock: Arc::new(Mutex::new(Loaded::default())),
+            revisions: Arc::new(RwLock::new(revisions)),
+            revisions_path,
+        }
+    }
+
+    /// Gets the routes the links files are loaded into.
+    pub fn routes(&self) -> &SharedRoutes {
+        &self.routes
+    }
+
+    /// Gets the revision of the links of the active routes, which increases every time
+    /// they change.
+    pub fn revision(&self) -> u64 {
+        self.revisions.read().unwrap().revision
+    }
+
+    /// Gets the revision the link registered for `key` was last changed at.
+    pub fn revision_of(&self, key: &str) -> Option<u64> {
+        let revisions = self.revisions.read().unwrap();
+        revisions.links.get(key).map(|link| link.revision)
+    }
+
+    /// Gets the revision every link was last changed at.
+    pub fn revisions(&self) -> HashMap<String, u64> {
+        let revisions = self.revisions.read().unwrap();
+        revisions
+            .links
+            .iter()
+            .map(|(key, link)| (key.clone(), link.revision))
+            .collect()
+    }
+
+    /// Moves the revisions to the links of the active routes, along with the link
+    /// `changed` by an edit, and writes them back to the revisions file. Must be called
+    /// while holding `lock`.
+    async fn update_revisions(&self, changed: Option<&str>) {
+        let contents = {
+            let mut revisions = self.revisions.write().unwrap();
+            if !revisions.update(&self.routes.load(), changed) {
+                return;
+            }
+            serde_json::to_string(&*revisions).unwrap_or_default()
+        };
+        let Some(path) = self.revisions_path.clone() else {
+            return;
+        };
+
+        let written = tokio::task::spawn_blocking({
+            let path = path.clone();
+            move || write_revisions(&path, &contents)
+        })
+        .await
+        .unwrap_or_else(|err| std::panic::resume_unwind(err.into_panic()));
+        if let Err(err) = written {
+            error!(
+                "Could not write the revisions to {}: {}",
+                path.display(),
+                err
+            );
+        }
+    }
+
+    /// Gets the remote links the active routes were loaded with, if any.
+    pub async fn remote(&self) -> Option<LinksDocument> {
+        self.lock.lock().await.remote.clone()
+    }
+
+    /// Loads the links files along with newly fetched remote links, swapping the new
+    /// routes in if they are valid and differ from the active ones. The remote links are
+    /// kept for later reloads once they were accepted, while rejected remote links are
+    /// dropped in favour of the ones accepted before.
+    pub async fn reload_remote(&self, document: LinksDocument) -> ReloadOutcome {
+        self.load(Trigger::Remote, Some(Update::Remote(document)))
+            .await
+    }
+
+    /// Loads the links files along with the links files read from a git repository at
+    /// `commit`, like `reload_remote`. The commit is reported by the status of the routes
+    /// once the links were accepted.
+    pub async fn reload_git(&self, commit: String, documents: Vec<LinksDocument>) -> ReloadOutcome {
+        self.load(Trigger::Git, Some(Update::Git { commit, documents }))
+            .await
+    }
+
+    /// Reloads the links files, swapping the new routes in if they are valid and differ
+    /// from the active ones, and logs the outcome.
+    ///
+    /// Reloads triggered by the watcher are skipped when the links files hold the same
+    /// contents as the active routes or, after a rejected reload, as the rejected files
+    /// so that they are not reported again. Reloads asked for by hand always load the
+    /// links files.
+    pub async fn reload(&self, trigger: Trigger) -> ReloadOutcome {
+        self.load(trigger, None).await
+    }
+
+    /// Changes a link in the links files and swaps the changed routes in once the links
+    /// file was written back. Edits hold the same lock as reloads, so they apply one at a
+    /// time and never interleave with a reload. The watcher skips the change, since the
+    /// active routes already match the links files.
+    ///
+    /// With `expected`, the link is only changed if it is still at that revision. Links
+    /// files changed since they were loaded are reloaded first, so that a link changed
+    /// in them is at a new revision before the change is checked against it.
+    pub async fn edit(
+        &self,
+        edit: LinkEdit,
+        expected: Option<u64>,
+    ) -> Result<StoredLink, EditError> {
+        let mut loaded = self.lock.lock().await;
+        self.load_locked(&mut loaded, Trigger::Edit, None).await;
+
+        let key = edit.key().trim_matches('/').to_string();
+        let previous = self.revision_of(&key);
+        if let (Some(expected), Some(actual)) = (expected, previous) {
+            if actual != expected {
+                return Err(EditError::Conflict {
+                    key,
+                    expected,
+                    actual,
+                });
+            }
+        }
+
+        let links_file = self.configs.links_file().to_string();
+        let conflicts = self.configs.conflicts();
+        let format = self.configs.format();
+        let extra: Vec<LinksDocument> = loaded.documents().cloned().collect();
+        let applied = tokio::task::spawn_blocking(move || {
+            edit::apply(&links_file, format, conflicts, &extra, &edit)
+        })
+        .await
+        .unwrap_or_else(|err| std::panic::resume_unwind(err.into_panic()))?;
+
+        info!(
+            "Changed the link '{}' in {}, now serving {} links",
+            applied.key,
+            applied.path,
+            applied.routes.len()
+        );
+        self.routes
+            .replace_loaded(applied.routes, Some(applied.hash));
+        self.update_revisions(Some(&key)).await;
+
+        // A removed link is reported at the revision it was last changed at.
+        let revision = self.revision_of(&key).or(previous).unwrap_or_default();
+        Ok(StoredLink {
+            link: applied.link,
+            revision,
+        })
+    }
+
+    async fn load(&self, trigger: Trigger, update: Option<Update>) -> ReloadOutcome {
+        let mut loaded = self.lock.lock().await;
+        self.load_locked(&mut loaded, trigger, update).await
+    }
+
+    /// Reloads the links files while holding `lock`, which guards `loaded`.
+    async fn load_locked(
+        &self,
+        loaded: &mut Loaded,
+        trigger: Trigger,
+        update: Option<Update>,
+    ) -> ReloadOutcome {
+        let mut candidate = loaded.clone();
+        match update {
+            Some(Update::Git { commit, documents }) => candidate.git = Some((commit, documents)),
+            Some(Update::Remote(document)) => candidate.remote = Some(document),
+            None => {}
+        }
+
+        let skip: Vec<String> = match trigger {
+            // After a rejected reload, going back to the active contents still has
+            // to clear the failure.
+            Trigger::Watch => match self.routes.status().last_failure {
+                Some(failure) => failure.hash.into_iter().collect(),
+                None => self.routes.hash().into_iter().collect(),
+            },
+            // Only links files that changed since they were loaded are reloaded.
+            Trigger::Edit => self.routes.hash().into_iter().collect(),
+            Trigger::Signal | Trigger::Admin | Trigger::Remote | Trigger::Git => Vec::new(),
+        };
+
+        let links_file = self.configs.links_file().to_string();
+        let conflicts = self.configs.conflicts();
+        let format = self.configs.format();
+        let extra: Vec<LinksDocument> = candidate.documents().cloned().collect();
+        let parsed = tokio::task::spawn_blocking(move || {
+            let mut documents = match loader::read_documents(&links_file, format) {
+                Ok(documents) => documents,
+                Err(err) => return (None, Some(Err(err))),
+            };
+
+            let sources = std::iter::once(links_file.as_str())
+                .chain(extra.iter().map(|document| document.path.as_str()))
+                .filter(|source| !source.is_empty())
+                .collect::<Vec<_>>()
+                .join(",");
+            documents.extend(extra);
+
+            let hash = loader::documents_hash(&documents);
+            if skip.contains(&hash) {
+                return (Some(hash), None);
+            }
+            let parsed = loader::load_documents(&documents, conflicts, &sources);
+            (Some(hash), Some(parsed))
+        })
+        .await;
+
+        let active = self.routes.load().len();
+        let outcome = match parsed {
+            Ok((hash, None)) => ReloadOutcome {
+                status: ReloadStatus::Skipped,
+                message: "The links files did not change".to_string(),
+                routes: active,
+                hash,
+                changes: None,
+                failure: None,
+            },
+            Ok((hash, Some(Ok((routes, warnings))))) => {
+                for warning in warnings {
+                    warn!("{}", warning);
+                }
+                self.routes
+                    .set_commit(candidate.git.as_ref().map(|(commit, _)| commit.clone()));
+                *loaded = candidate;
+
+                let changes = self.routes.load().diff(&routes);
+                if changes.is_empty() {
+                    self.routes.mark_loaded(hash.clone());
+                    ReloadOutcome {
+                        status: ReloadStatus::Unchanged,
+                        message: format!(
+                            "The links did not change, still serving {} links",
+                            active
+                        ),
+                        routes: active,
+                        hash,
+                        changes: None,
+                        failure: None,
+                    }
+                } else {
+                    let count = routes.len();
+                    self.routes.replace_loaded(routes, hash.clone());
+                    self.update_revisions(None).await;
+                    ReloadOutcome {
+                        status: ReloadStatus::Reloaded,
+                        message: format!("Now serving {} links: {}", count, changes),
+                        routes: count,
+                        hash,
+                        changes: Some(changes),
+                        failure: None,
+                    }
+                }
+            }
+            Ok((hash, Some(Err(err)))) => {
+                let failure = LoadFailure {
+                    hash: hash.clone(),
+                    ..(&err).into()
+                };
+                self.routes.record_failure(failure.clone());
+                ReloadOutcome {
+                    status: ReloadStatus::Failed,
+                    message: format!("Still serving the previous {} links. {}", active, err),
+                    routes: active,
+                    hash,
+                    changes: None,
+                    failure: Some(failure),
+                }
+            }
+            Err(err) => ReloadOutcome {
+                status: ReloadStatus::Failed,
+                message: format!("Still serving the previous {} links. {}", active, err),
+                routes: active,
+                hash: None,
+                changes: None,
+                failure: None,
+            },
+        };
+
+        let hash = outcome.hash.as_deref().unwrap_or("unknown");
+        match outcome.status {
+            ReloadStatus::Reloaded => info!(
+                "Routes reloaded after {} (hash {})! {}",
+                trigger, hash, outcome.message
+            ),
+            ReloadStatus::Unchanged => {
+                info!(
+                    "Routes reloaded after {} (hash {}). {}",
+                    trigger, hash, outcome.message
+                )
+            }
+            ReloadStatus::Skipped => debug!(
+                "Skipped reloading routes after {} (hash {}). {}",
+                trigger, hash, outcome.message
+            ),
+            ReloadStatus::Failed => error!(
+                "Could not reload routes after {} (hash {}). {}",
+                trigger, hash, outcome.message
+            ),
+        }
+
+        outcome
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use std::path::PathBuf;
+
+    use crate::link::Link;
+
+    /// Writes a links file with a single link to `url`.
+    fn write_links(path: &PathBuf, url: &str) {
+        fs::write(path, format!("routes:\n  test: {}\n", url)).expect("Could not write links file");
+    }
+
+    fn reloader(name: &str) -> (Reloader, SharedRoutes, PathBuf) {
+        let path = std::env::temp_dir().join(format!("golinks-{}-{}", std::process::id(), name));
+        write_links(&path, "https://v1.example.com");
+        let _ = fs::remove_file(loader::revisions_path(path.to_str().unwrap()).unwrap());
+
+        let mut configs = AppConfig::default();
+        configs.set_links_file(path.to_str().unwrap());
+
+        let routes = SharedRoutes::new(
+            loader::load_routes(configs.links_file(), configs.conflicts(), None).unwrap(),
+        );
+        routes.mark_loaded(loader::content_hash(configs.links_file()).ok());
+
+        (Reloader::new(configs, routes.clone()), routes, path)
+    }
+
+    #[rocket::async_test]
+    async fn test_reload() {
+        let (reloader, routes, path) = reloader("reload.yaml");
+
+        let outcome = reloader.reload(Trigger::Watch).await;
+        assert_eq!(outcome.status, ReloadStatus::Skipped);
+        assert_eq!(outcome.hash, routes.hash());
+
+        let outcome = reloader.reload(Trigger::Signal).await;
+        assert_eq!(outcome.status, ReloadStatus::Unchanged);
+        assert_eq!(outcome.routes, 1);
+
+        write_links(&path, "https://v2.example.com");
+        let outcome = reloader.reload(Trigger::Watch).await;
+        assert_eq!(outcome.status, ReloadStatus::Reloaded);
+        assert_eq!(outcome.changes.unwrap().changed, vec!["test"]);
+        assert_eq!(outcome.hash, routes.hash());
+        assert_eq!(
+            routes.load().fetch("test").unwrap().url,
+            "https://v2.example.com"
+        );
+    }
+
+    #[rocket::async_test]
+    async fn test_reload_index_settings() {
+        let (reloader, routes, path) = reloader("reload-indexes.yaml");
+
+        fs::write(
+            &path,
+            "routes:\n  test: https://v1.example.com\nindexes:\n  disabled: [test]\n",
+        )
+        .unwrap();
+        let outcome = reloader.reload(Trigger::Watch).await;
+        assert_eq!(outcome.status, ReloadStatus::Reloaded);
+        assert!(outcome.changes.unwrap().indexes);
+        assert_eq!(outcome.hash, routes.hash());
+    }
+
+    #[rocket::async_test]
+    async fn test_revisions_survive_restarts() {
+        let (reloader, _, path) = reloader("restart.yaml");
+        let revision = reloader.revision_of("test");
+        let links = reloader.revision();
+
+        // A reloader for the same links files, as after a restart.
+        let restart = || {
+            let mut configs = AppConfig::default();
+            configs.set_links_file(path.to_str().unwrap());
+            let routes = loader::load_routes(configs.links_file(), configs.conflicts(), None);
+            Reloader::new(configs, SharedRoutes::new(routes.unwrap()))
+        };
+        let restarted = restart();
+
+        assert_eq!(restarted.revision_of("test"), revision);
+        assert_eq!(restarted.revision(), links);
+
+        // The ETag of the link is its revision in the store.
+        let store = store::FileStore::new(restarted);
+        let stored = store::LinkStore::get(&store, "test").await.unwrap();
+        assert_eq!(stored.map(|stored| stored.revision), revision);
+
+        // Links changed while the service was stopped move to a new revision.
+        write_links(&path, "https://v2.example.com");
+        let restarted = restart();
+        assert_eq!(restarted.revision(), links + 1);
+        assert_eq!(restarted.revision_of("test"), Some(links + 1));
+    }
+
+    #[rocket::async_test]
+    async fn test_reload_failure() {
+        let (reloader, routes, path) = reloader("reload-failure.yaml");
+        let hash = routes.hash();
+
+        fs::write(&path, "routes: [\n").unwrap();
+        let outcome = reloader.reload(Trigger::Watch).await;
+        assert_eq!(outcome.status, ReloadStatus::Failed);
+        assert_eq!(outcome.failure.unwrap().hash, outcome.hash);
+        assert_eq!(routes.status().status, "degraded");
+        assert_eq!(routes.hash(), hash);
+
+        // The same rejected files are not reported again by the watcher, but are when
+        // a reload is asked for.
+        let outcome = reloader.reload(Trigger::Watch).await;
+        assert_eq!(outcome.status, ReloadStatus::Skipped);
+        let outcome = reloader.reload(Trigger::Admin).await;
+        assert_eq!(outcome.status, ReloadStatus::Failed);
+
+        // Putting the previous links back clears the failure without swapping routes.
+        write_links(&path, "https://v1.example.com");
+        let loaded_at = routes.status().loaded_at;
+        let outcome = reloader.reload(Trigger::Watch).await;
+        assert_eq!(outcome.status, ReloadStatus::Unchanged);
+        assert_eq!(routes.status().status, "ok");
+        assert_eq!(routes.status().loaded_at, loaded_at);
+    }
+
+    #[rocket::async_test]
+    async fn test_edit() {
+        let (reloader, routes, path) = reloader("edit.yaml");
+        let initial = reloader.revision_of("test").unwrap();
+        let links = reloader.revision();
+
+        let edits = (0..8).map(|i| {
+            let reloader = reloader.clone();
+            rocket::tokio::spawn(async move {
+                let link = Link::new(format!("https://{}.example.com", i));
+                reloader
+                    .edit(LinkEdit::Create(format!("link{}", i), link), None)
+                    .await
+            })
+        });
+        for edit in edits.collect::<Vec<_>>() {
+            edit.await.unwrap().expect("Could not create link");
+        }
+
+        // Every edit was written back, none of them overwriting another.
+        assert_eq!(routes.load().len(), 9);
+        assert_eq!(reloader.revision(), links + 8);
+        assert_eq!(reloader.revision_of("test"), Some(initial));
+        assert_eq!(
+            routes.hash(),
+            loader::content_hash(path.to_str().unwrap()).ok()
+        );
+        let reloaded = loader::load_routes(path.to_str().unwrap(), Default::default(), None);
+        assert_eq!(reloaded.unwrap().len(), 9);
+
+        // The watcher sees the edited links file as already loaded.
+        let outcome = reloader.reload(Trigger::Watch).await;
+        assert_eq!(outcome.status, ReloadStatus::Skipped);
+
+        // Changes made against a revision the link moved past are rejected.
+        let update = |url: &str| LinkEdit::Update("test".to_string(), Link::new(url));
+        let updated = reloader
+            .edit(update("https://v2.example.com"), Some(initial))
+            .await
+            .unwrap();
+        assert_eq!(updated.revision, links + 9);
+        assert_eq!(reloader.revision_of("test"), Some(links + 9));
+        let result = reloader
+            .edit(update("https://v3.example.com"), Some(initial))
+            .await;
+        assert!(
+            matches!(result, Err(EditError::Conflict { actual, .. }) if actual == updated.revision),
+            "{:?}",
+            result
+        );
+
+        // Reloading changed links files moves the changed links to a new revision, even
+        // when they go back to the contents they had before.
+        write_links(&path, "https://v1.example.com");
+        let outcome = reloader.reload(Trigger::Admin).await;
+        assert_eq!(outcome.status, ReloadStatus::Reloaded);
+        assert_eq!(reloader.revision(), links + 10);
+        assert_eq!(reloader.revision_of("test"), Some(links + 10));
+
+        let result = reloader.edit(update("not a url"), None).await;
+        assert!(matches!(result, Err(EditError::Load(_))), "{:?}", result);
+        assert_eq!(
+            routes.load().fetch("test").unwrap().url,
+            "https://v1.example.com"
+        );
+
+        // A link changed in the links file before the watcher reloaded it is at a new
+        // revision by the time a change is checked against it.
+        write_links(&path, "https://v4.example.com");
+        let result = reloader
+            .edit(update("https://v5.example.com"), Some(links + 10))
+            .await;
+        assert!(
+            matches!(result, Err(EditError::Conflict { actual, .. }) if actual == links + 11),
+            "{:?}",
+            result
+        );
+        assert_eq!(
+            routes.load().fetch("test").unwrap().url,
+            "https://v4.example.com"
+        );
+        let updated = reloader
+            .edit(update("https://v5.example.com"), Some(links + 11))
+            .await
+            .unwrap();
+        assert_eq!(updated.revision, links + 12);
+    }
+}
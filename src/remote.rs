@@ -0,0 +1,567 @@
+use std::fmt;
+use std::time::Duration;
+
+use log::{debug, info, warn};
+
+use crate::loader::LinksDocument;
+use crate::models::LinksFormat;
+use crate::reload::{ReloadOutcome, Reloader};
+
+/// How long to wait for the remote links before giving up on a poll, in seconds.
+const REQUEST_TIMEOUT: u64 = 30;
+
+/// The longest time to wait between polls after polls failed, unless the poll interval
+/// is longer.
+const MAX_BACKOFF: Duration = Duration::from_secs(15 * 60);
+
+/// Errors that can occur while fetching the remote links.
+#[derive(Debug)]
+pub enum RemoteError {
+    /// The request could not be sent, or the response could not be read.
+    Request { url: String, source: minreq::Error },
+    /// The server responded with an error.
+    Status {
+        url: String,
+        code: i32,
+        reason: String,
+    },
+}
+
+impl fmt::Display for RemoteError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RemoteError::Request { url, source } => {
+                write!(f, "Unable to fetch {}: {}", url, source)
+            }
+            RemoteError::Status { url, code, reason } => {
+                write!(f, "Unable to fetch {}: {} {}", url, code, reason)
+            }
+        }
+    }
+}
+
+impl std::error::Error for RemoteError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            RemoteError::Request { source, .. } => Some(source),
+            RemoteError::Status { .. } => None,
+        }
+    }
+}
+
+/// Links fetched from a remote source, along with the validators the server sent with
+/// them so that the next poll only downloads them again when they changed.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RemoteDocument {
+    pub document: LinksDocument,
+    pub etag: Option<String>,
+    pub last_modified: Option<String>,
+}
+
+/// The result of polling the remote links.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Fetched {
+    Modified(RemoteDocument),
+    /// The server reported that the links did not change since they were last fetched.
+    NotModified,
+}
+
+/// A links document served over HTTP(S).
+#[derive(Clone, Debug)]
+pub struct RemoteSource {
+    url: String,
+    format: Option<LinksFormat>,
+}
+
+impl RemoteSource {
+    /// Creates a source for the links at `url`, read in `format`, or in the format
+    /// detected from the response.
+    pub fn new(url: &str, format: Option<LinksFormat>) -> Self {
+        Self {
+            url: url.to_string(),
+            format,
+        }
+    }
+
+    /// Fetches the links. When `previous` is given, the request is conditional on the
+    /// links having changed since then.
+    pub fn fetch(&self, previous: Option<&RemoteDocument>) -> Result<Fetched, RemoteError> {
+        debug!("Fetching {}...", self.url);
+
+        let mut request = minreq::get(&self.url).with_timeout(REQUEST_TIMEOUT);
+        if let Some(etag) = previous.and_then(|previous| previous.etag.as_ref()) {
+            request = request.with_header("If-None-Match", etag);
+        }
+        if let Some(modified) = previous.and_then(|previous| previous.last_modified.as_ref()) {
+            request = request.with_header("If-Modified-Since", modified);
+        }
+
+        let request_error = |source| RemoteError::Request {
+            url: self.url.clone(),
+            source,
+        };
+
+        let response = request.send().map_err(request_error)?;
+        match response.status_code {
+            304 => return Ok(Fetched::NotModified),
+            200..=299 => {}
+            code => {
+                return Err(RemoteError::Status {
+                    url: self.url.clone(),
+                    code,
+                    reason: response.reason_phrase,
+                })
+            }
+        }
+
+        let content_type = response.headers.get("content-type").map(String::as_str);
+        let format = self
+            .format
+            .or_else(|| content_type.and_then(format_of_content_type))
+            .or_else(|| format_of_url(&self.url))
+            .unwrap_or(LinksFormat::Yaml);
+
+        Ok(Fetched::Modified(RemoteDocument {
+            document: LinksDocument {
+                path: self.url.clone(),
+                namespace: None,
+                format,
+                contents: response.as_str().map_err(request_error)?.to_string(),
+            },
+            etag: response.headers.get("etag").cloned(),
+            last_modified: response.headers.get("last-modified").cloned(),
+        }))
+    }
+}
+
+/// Detects the format of links from the media type they were served with.
+fn format_of_content_type(content_type: &str) -> Option<LinksFormat> {
+    let media_type = content_type.split(';').next()?.trim().to_ascii_lowercase();
+    let subtype = media_type.rsplit(['/', '+']).next()?;
+
+    match subtype {
+        "yaml" | "x-yaml" => Some(LinksFormat::Yaml),
+        "json" => Some(LinksFormat::Json),
+        "toml" => Some(LinksFormat::Toml),
+        _ => None,
+    }
+}
+
+/// Detects the format of links from the extension of the path of the URL they were
+/// served from.
+fn format_of_url(url: &str) -> Option<LinksFormat> {
+    let url = url::Url::parse(url).ok()?;
+    LinksFormat::from_path(url.path())
+}
+
+/// How long to wait before polling again. Every failed poll in a row doubles the
+/// wait, up to `MAX_BACKOFF`.
+#[derive(Clone, Copy, Debug)]
+pub struct Backoff {
+    interval: Duration,
+    failures: u32,
+}
+
+impl Backoff {
+    /// Creates a backoff polling every `interval` while polls succeed.
+    pub fn new(interval: Duration) -> Self {
+        Self {
+            interval,
+            failures: 0,
+        }
+    }
+
+    /// Records that a poll succeeded.
+    pub fn succeed(&mut self) {
+        self.failures = 0;
+    }
+
+    /// Records that a poll failed.
+    pub fn fail(&mut self) {
+        self.failures = self.failures.saturating_add(1);
+    }
+
+    /// Gets how long to wait before the next poll.
+    pub fn delay(&self) -> Duration {
+        let factor = 2u32.saturating_pow(self.failures);
+        self.interval
+            .saturating_mul(factor)
+            .min(MAX_BACKOFF.max(self.interval))
+    }
+}
+
+/// Polls the remote links, reloading the links with them every time they change.
+pub struct RemotePoller {
+    source: RemoteSource,
+    reloader: Reloader,
+    /// The links last fetched, even if they were rejected, so that they are not
+    /// downloaded again until they change.
+    latest: Option<RemoteDocument>,
+    backoff: Backoff,
+}
+
+impl RemotePoller {
+    /// Creates a poller fetching the links of `source` every `interval`, and loading
+    /// them with `reloader`.
+    pub fn new(source: RemoteSource, reloader: Reloader, interval: Duration) -> Self {
+        Self {
+            source,
+            reloader,
+            latest: None,
+            backoff: Backoff::new(interval),
+        }
+    }
+
+    /// Polls the remote links once, returning the outcome of reloading them if they
+    /// changed. The active routes are kept when the links cannot be fetched.
+    pub async fn poll(&mut self) -> Result<Option<ReloadOutcome>, RemoteError> {
+        let source = self.source.clone();
+        let latest = self.latest.clone();
+        let fetched = tokio::task::spawn_blocking(move || source.fetch(latest.as_ref()))
+            .await
+            .unwrap_or_else(|err| std::panic::resume_unwind(err.into_panic()));
+
+        let fetched = match fetched {
+            Ok(fetched) => fetched,
+            Err(err) => {
+                self.backoff.fail();
+                warn!(
+                    "{}. Trying again in {}s",
+                    err,
+                    self.backoff.delay().as_secs()
+                );
+                return Err(err);
+            }
+        };
+        self.backoff.succeed();
+
+        let remote = match fetched {
+            Fetched::Modified(remote) => remote,
+            Fetched::NotModified => {
+                debug!("{} did not change", self.source.url);
+                return Ok(None);
+            }
+        };
+
+        // Servers that do not support conditional requests send the same links again.
+        let changed = self
+            .latest
+            .as_ref()
+            .is_none_or(|latest| latest.document != remote.document);
+        let document = remote.document.clone();
+        self.latest = Some(remote);
+        if !changed {
+            debug!("{} did not change", self.source.url);
+            return Ok(None);
+        }
+
+        info!("{} changed! Reloading routes...", self.source.url);
+        Ok(Some(self.reloader.reload_remote(document).await))
+    }
+
+    /// Polls the remote links forever, waiting for the poll interval between polls, or
+    /// longer after polls failed.
+    pub async fn run(mut self) {
+        loop {
+            rocket::tokio::time::sleep(self.backoff.delay()).await;
+            let _ = self.poll().await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use std::io::{BufRead, BufReader, Write};
+    use std::net::TcpListener;
+    use std::sync::{Arc, Mutex};
+    use std::thread;
+
+    use crate::config::AppConfig;
+    use crate::loader;
+    use crate::models::SharedRoutes;
+    use crate::reload::ReloadStatus;
+
+    /// What the stub server responds with.
+    struct Served {
+        status: u16,
+        content_type: &'static str,
+        body: String,
+        etag: Option<&'static str>,
+        last_modified: Option<&'static str>,
+    }
+
+    /// A local HTTP server serving one links document, which answers conditional
+    /// requests like a real server would.
+    struct Stub {
+        url: String,
+        served: Arc<Mutex<Served>>,
+        requests: Arc<Mutex<Vec<String>>>,
+    }
+
+    impl Stub {
+        fn start(path: &str, served: Served) -> Self {
+            let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+            let url = format!("http://{}{}", listener.local_addr().unwrap(), path);
+            let served = Arc::new(Mutex::new(served));
+            let requests = Arc::new(Mutex::new(Vec::new()));
+
+            let (serving, requested) = (served.clone(), requests.clone());
+            thread::spawn(move || {
+                for stream in listener.incoming() {
+                    let mut stream = stream.unwrap();
+                    let mut request = String::new();
+                    for line in BufReader::new(&stream).lines() {
+                        let line = line.unwrap();
+                        if line.is_empty() {
+                            break;
+                        }
+                        request.push_str(&line.to_ascii_lowercase());
+                        request.push('\n');
+                    }
+
+                    let served = serving.lock().unwrap();
+                    let matches = |header: &str, value: Option<&str>| {
+                        value.is_some_and(|value| {
+                            request.contains(&format!("{}: {}", header, value.to_ascii_lowercase()))
+                        })
+                    };
+                    let not_modified = matches("if-none-match", served.etag)
+                        || (served.etag.is_none()
+                            && matches("if-modified-since", served.last_modified));
+
+                    let mut response = match not_modified {
+                        true => "HTTP/1.1 304 Not Modified\r\n".to_string(),
+                        false => format!(
+                            "HTTP/1.1 {} Stub\r\nContent-Type: {}\r\n",
+                            served.status, served.content_type
+                        ),
+                    };
+                    for (header, value) in [
+                        ("ETag", served.etag),
+                        ("Last-Modified", served.last_modified),
+                    ] {
+                        if let Some(value) = value {
+                            response.push_str(&format!("{}: {}\r\n", header, value));
+                        }
+                    }
+                    let body = if not_modified {
+                        ""
+                    } else {
+                        served.body.as_str()
+                    };
+                    response.push_str(&format!(
+                        "Content-Length: {}\r\nConnection: close\r\n\r\n{}",
+                        body.len(),
+                        body
+                    ));
+                    stream.write_all(response.as_bytes()).unwrap();
+
+                    requested.lock().unwrap().push(request);
+                }
+            });
+
+            Self {
+                url,
+                served,
+                requests,
+            }
+        }
+
+        fn serve(&self, status: u16, body: &str, etag: Option<&'static str>) {
+            let mut served = self.served.lock().unwrap();
+            served.status = status;
+            served.body = body.to_string();
+            served.etag = etag;
+        }
+
+        fn last_request(&self) -> String {
+            self.requests.lock().unwrap().last().cloned().unwrap()
+        }
+    }
+
+    fn served(body: &str) -> Served {
+        Served {
+            status: 200,
+            content_type: "application/yaml",
+            body: body.to_string(),
+            etag: Some("\"v1\""),
+            last_modified: Some("Wed, 21 Oct 2026 07:28:00 GMT"),
+        }
+    }
+
+    #[test]
+    fn test_fetch() {
+        let stub = Stub::start("/links", served("routes:\n  test: https://example.com\n"));
+        let source = RemoteSource::new(&stub.url, None);
+
+        let remote = match source.fetch(None).unwrap() {
+            Fetched::Modified(remote) => remote,
+            Fetched::NotModified => panic!("Expected the links to be sent"),
+        };
+        assert_eq!(remote.document.path, stub.url);
+        assert_eq!(remote.document.format, LinksFormat::Yaml);
+        assert_eq!(
+            remote.document.contents,
+            "routes:\n  test: https://example.com\n"
+        );
+        assert_eq!(remote.etag.as_deref(), Some("\"v1\""));
+        assert!(!stub.last_request().contains("if-none-match"));
+
+        assert_eq!(source.fetch(Some(&remote)).unwrap(), Fetched::NotModified);
+        let request = stub.last_request();
+        assert!(request.contains("if-none-match: \"v1\""));
+        assert!(request.contains("if-modified-since: wed, 21 oct 2026 07:28:00 gmt"));
+
+        stub.serve(200, "{\"routes\": {}}", None);
+        {
+            let mut served = stub.served.lock().unwrap();
+            served.content_type = "application/json; charset=utf-8";
+            served.last_modified = Some("Thu, 22 Oct 2026 07:28:00 GMT");
+        }
+        let remote = match source.fetch(Some(&remote)).unwrap() {
+            Fetched::Modified(remote) => remote,
+            Fetched::NotModified => panic!("Expected the new links to be sent"),
+        };
+        assert_eq!(remote.document.format, LinksFormat::Json);
+        assert_eq!(remote.etag, None);
+
+        // Without an ETag, the server falls back to the modification time.
+        assert_eq!(source.fetch(Some(&remote)).unwrap(), Fetched::NotModified);
+
+        stub.serve(503, "", None);
+        let err = source.fetch(None).unwrap_err();
+        assert!(matches!(err, RemoteError::Status { code: 503, .. }));
+    }
+
+    #[test]
+    fn test_fetch_unreachable() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/links.yaml", listener.local_addr().unwrap());
+        drop(listener);
+
+        let err = RemoteSource::new(&url, None).fetch(None).unwrap_err();
+        assert!(matches!(err, RemoteError::Request { .. }));
+        assert!(err
+            .to_string()
+            .starts_with(&format!("Unable to fetch {}", url)));
+    }
+
+    #[test]
+    fn test_format() {
+        assert_eq!(
+            format_of_content_type("application/yaml"),
+            Some(LinksFormat::Yaml)
+        );
+        assert_eq!(
+            format_of_content_type("text/x-yaml"),
+            Some(LinksFormat::Yaml)
+        );
+        assert_eq!(
+            format_of_content_type("application/problem+json; charset=utf-8"),
+            Some(LinksFormat::Json)
+        );
+        assert_eq!(
+            format_of_content_type("application/TOML"),
+            Some(LinksFormat::Toml)
+        );
+        assert_eq!(format_of_content_type("text/plain"), None);
+
+        assert_eq!(
+            format_of_url("https://example.com/links.toml?ref=main"),
+            Some(LinksFormat::Toml)
+        );
+        assert_eq!(format_of_url("https://example.com/links"), None);
+    }
+
+    #[test]
+    fn test_backoff() {
+        let mut backoff = Backoff::new(Duration::from_secs(60));
+        assert_eq!(backoff.delay().as_secs(), 60);
+
+        backoff.fail();
+        assert_eq!(backoff.delay().as_secs(), 120);
+        backoff.fail();
+        assert_eq!(backoff.delay().as_secs(), 240);
+        for _ in 0..40 {
+            backoff.fail();
+        }
+        assert_eq!(backoff.delay(), MAX_BACKOFF);
+
+        backoff.succeed();
+        assert_eq!(backoff.delay().as_secs(), 60);
+
+        let mut backoff = Backoff::new(Duration::from_secs(3600));
+        backoff.fail();
+        assert_eq!(backoff.delay().as_secs(), 3600);
+    }
+
+    #[rocket::async_test]
+    async fn test_poll() {
+        let path = std::env::temp_dir().join(format!("golinks-{}-remote.yaml", std::process::id()));
+        fs::write(&path, "routes:\n  local: https://local.example.com\n").unwrap();
+        let mut configs = AppConfig::default();
+        configs.set_links_file(path.to_str().unwrap());
+
+        let routes = SharedRoutes::new(
+            loader::load_routes(configs.links_file(), configs.conflicts(), None).unwrap(),
+        );
+        let reloader = Reloader::new(configs, routes.clone());
+
+        let stub = Stub::start(
+            "/links.yaml",
+            served("routes:\n  test: https://v1.example.com\n"),
+        );
+        let source = RemoteSource::new(&stub.url, None);
+        let mut poller = RemotePoller::new(source, reloader.clone(), Duration::from_secs(60));
+
+        let outcome = poller.poll().await.unwrap().unwrap();
+        assert_eq!(outcome.status, ReloadStatus::Reloaded);
+        assert_eq!(routes.load().len(), 2);
+        assert_eq!(
+            routes.load().fetch("test").unwrap().source.as_deref(),
+            Some(stub.url.as_str())
+        );
+
+        assert!(poller.poll().await.unwrap().is_none());
+
+        // Links that cannot be fetched are polled for less often, and the routes are kept.
+        stub.serve(500, "", Some("\"v2\""));
+        assert!(poller.poll().await.is_err());
+        assert_eq!(poller.backoff.delay().as_secs(), 120);
+        assert_eq!(routes.load().len(), 2);
+
+        // Invalid links are rejected without being fetched again, and the accepted ones
+        // are still loaded along with the links files.
+        stub.serve(200, "routes: [\n", Some("\"v3\""));
+        let outcome = poller.poll().await.unwrap().unwrap();
+        assert_eq!(outcome.status, ReloadStatus::Failed);
+        assert_eq!(poller.backoff.delay().as_secs(), 60);
+        assert!(poller.poll().await.unwrap().is_none());
+        assert!(stub.last_request().contains("if-none-match: \"v3\""));
+        assert_eq!(routes.status().status, "degraded");
+
+        fs::write(&path, "routes:\n  local: https://local2.example.com\n").unwrap();
+        let outcome = reloader.reload(crate::reload::Trigger::Admin).await;
+        assert_eq!(outcome.status, ReloadStatus::Reloaded);
+        assert_eq!(
+            routes.load().fetch("test").unwrap().url,
+            "https://v1.example.com"
+        );
+
+        stub.serve(
+            200,
+            "routes:\n  test: https://v2.example.com\n",
+            Some("\"v4\""),
+        );
+        let outcome = poller.poll().await.unwrap().unwrap();
+        assert_eq!(outcome.status, ReloadStatus::Reloaded);
+        assert_eq!(outcome.changes.unwrap().changed, vec!["test"]);
+        assert_eq!(
+            routes.load().fetch("test").unwrap().url,
+            "https://v2.example.com"
+        );
+        assert_eq!(reloader.remote().await.unwrap().path, stub.url);
+    }
+}
@@ -0,0 +1,485 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, Weak};
+use std::thread;
+use std::time::Duration;
+
+use log::warn;
+use rocket::serde::json::serde_json;
+use rusqlite::{params_from_iter, Connection, OptionalExtension, TransactionBehavior};
+use tokio::sync::broadcast;
+
+use crate::edit::LinkEdit;
+use crate::link::Link;
+use crate::models::{RoutesDiff, CHANGES_CAPACITY};
+use crate::store::{self, LinkStore, StoreError, StoredLink};
+
+/// Brings the schema of the database up to date, one version at a time. The version of
+/// the schema is kept in `user_version`.
+const MIGRATIONS: &[&str] = &[
+    // The table of the links, which holds every link as JSON.
+    "
+    CREATE TABLE IF NOT EXISTS links (
+        key TEXT PRIMARY KEY NOT NULL,
+        link TEXT NOT NULL
+    ) WITHOUT ROWID;
+    ",
+    // The revision every link was last changed at, and the revision of the database.
+    // Links stored before revisions were kept start at the first revision.
+    "
+    ALTER TABLE links ADD COLUMN revision INTEGER NOT NULL DEFAULT 1;
+    CREATE TABLE revision (
+        id INTEGER PRIMARY KEY CHECK (id = 1),
+        revision INTEGER NOT NULL
+    );
+    INSERT INTO revision (id, revision) SELECT 1, EXISTS (SELECT 1 FROM links);
+    ",
+];
+
+/// How often the database is checked for changes made by other processes sharing it.
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Stores the links in an SQLite database, for deployments that change links at
+/// runtime without links files to write them to. Changes made by other processes
+/// sharing the database are picked up by polling it.
+pub struct SqliteStore {
+    path: String,
+    connection: Arc<Mutex<Connection>>,
+    changes: broadcast::Sender<RoutesDiff>,
+}
+
+impl SqliteStore {
+    /// Opens the database at `path`, creating it if it does not exist yet.
+    pub fn open(path: &str) -> Result<Self, StoreError> {
+        let error = |err: rusqlite::Error| StoreError::Backend(format!("{}: {}", path, err));
+
+        let mut connection = Connection::open(path).map_err(error)?;
+        connection
+            .busy_timeout(std::time::Duration::from_secs(5))
+            .map_err(error)?;
+        migrate(&mut connection).map_err(error)?;
+
+        let store = Self {
+            path: path.to_string(),
+            connection: Arc::new(Mutex::new(connection)),
+            changes: broadcast::channel(CHANGES_CAPACITY).0,
+        };
+        let known = revisions(&store.connection.lock().unwrap()).map_err(error)?;
+        let (connection, changes) = (Arc::downgrade(&store.connection), store.changes.clone());
+        thread::spawn(move || poll(connection, changes, known));
+
+        Ok(store)
+    }
+
+    /// Runs `query` on the connection on a blocking thread, one query at a time.
+    async fn run<T: Send + 'static>(
+        &self,
+        query: impl FnOnce(&mut Connection) -> Result<T, StoreError> + Send + 'static,
+    ) -> Result<T, StoreError> {
+        let connection = self.connection.clone();
+        tokio::task::spawn_blocking(move || query(&mut connection.lock().unwrap()))
+            .await
+            .unwrap_or_else(|err| std::panic::resume_unwind(err.into_panic()))
+    }
+
+    /// Gets the links matching `condition`, which may use `parameters` as `?1` and on.
+    async fn select(
+        &self,
+        condition: &'static str,
+        parameters: Vec<String>,
+    ) -> Result<Vec<(String, StoredLink)>, StoreError> {
+        let path = self.path.clone();
+        self.run(move |connection| {
+            let error = |err: rusqlite::Error| StoreError::Backend(format!("{}: {}", path, err));
+
+            let mut statement = connection
+                .prepare(&format!(
+                    "SELECT key, link, revision FROM links WHERE {} ORDER BY key",
+                    condition
+                ))
+                .map_err(error)?;
+            let rows = statement
+                .query_map(params_from_iter(parameters), |row| {
+                    Ok((row.get(0)?, row.get::<_, String>(1)?, row.get::<_, i64>(2)?))
+                })
+                .map_err(error)?;
+
+            rows.map(|row| {
+                let (key, link, revision) = row.map_err(error)?;
+                let link = decode(&path, &link)?;
+                Ok((
+                    key,
+                    StoredLink {
+                        link,
+                        revision: revision as u64,
+                    },
+                ))
+            })
+            .collect()
+        })
+        .await
+    }
+}
+
+/// Applies the migrations the database has not been through yet.
+fn migrate(connection: &mut Connection) -> rusqlite::Result<()> {
+    let transaction = connection.transaction_with_behavior(TransactionBehavior::Immediate)?;
+    let version: i64 = transaction.query_row("PRAGMA user_version", [], |row| row.get(0))?;
+    let latest = MIGRATIONS.len() as i64;
+    for migration in MIGRATIONS.iter().skip(version as usize) {
+        transaction.execute_batch(migration)?;
+    }
+    if version < latest {
+        transaction.pragma_update(None, "user_version", latest)?;
+    }
+    transaction.commit()
+}
+
+/// Reads the revision every link was last changed at, along with the `data_version` of
+/// the connection, which changes whenever another connection changes the database.
+fn revisions(connection: &Connection) -> rusqlite::Result<(i64, HashMap<String, i64>)> {
+    let version = connection.query_row("PRAGMA data_version", [], |row| row.get(0))?;
+    let mut statement = connection.prepare("SELECT key, revision FROM links")?;
+    let revisions = statement
+        .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?
+        .collect::<rusqlite::Result<_>>()?;
+    Ok((version, revisions))
+}
+
+/// Checks the database for changes made by other processes until the store is dropped,
+/// sending the links that changed since the last check, or since `known` was read.
+/// Changes made by the store itself since then are sent again along with them.
+fn poll(
+    connection: Weak<Mutex<Connection>>,
+    changes: broadcast::Sender<RoutesDiff>,
+    (mut version, mut known): (i64, HashMap<String, i64>),
+) {
+    loop {
+        thread::sleep(POLL_INTERVAL);
+        let Some(connection) = connection.upgrade() else {
+            return;
+        };
+
+        let read = {
+            let connection = connection.lock().unwrap();
+            connection
+                .query_row("PRAGMA data_version", [], |row| row.get::<_, i64>(0))
+                .and_then(|current| match current == version {
+                    true => Ok(None),
+                    false => revisions(&connection).map(Some),
+                })
+        };
+        let (current, revisions) = match read {
+            Ok(Some(read)) => read,
+            Ok(None) => continue,
+            Err(err) => {
+                warn!("Could not check the link store for changes: {}", err);
+                continue;
+            }
+        };
+
+        let mut diff = RoutesDiff::default();
+        for (key, revision) in &revisions {
+            match known.get(key) {
+                None => diff.added.push(key.clone()),
+                Some(known) if known != revision => diff.changed.push(key.clone()),
+                Some(_) => {}
+            }
+        }
+        diff.removed.extend(
+            known
+                .keys()
+                .filter(|key| !revisions.contains_key(*key))
+                .cloned(),
+        );
+        diff.added.sort();
+        diff.removed.sort();
+        diff.changed.sort();
+
+        (version, known) = (current, revisions);
+        if !diff.is_empty() {
+            let _ = changes.send(diff);
+        }
+    }
+}
+
+/// Reads the revision of the database.
+fn revision(connection: &Connection) -> rusqlite::Result<u64> {
+    connection.query_row("SELECT revision FROM revision", [], |row| {
+        Ok(row.get::<_, i64>(0)? as u64)
+    })
+}
+
+/// Reads a link stored as JSON.
+fn decode(path: &str, link: &str) -> Result<Link, StoreError> {
+    serde_json::from_str(link)
+        .map_err(|err| StoreError::Backend(format!("{}: Invalid link {}: {}", path, link, err)))
+}
+
+#[rocket::async_trait]
+impl LinkStore for SqliteStore {
+    async fn get(&self, key: &str) -> Result<Option<StoredLink>, StoreError> {
+        let mut links = self.select("key = ?1", vec![key.to_string()]).await?;
+        Ok(links.pop().map(|(_, link)| link))
+    }
+
+    async fn list(&self) -> Result<Vec<(String, StoredLink)>, StoreError> {
+        self.select("TRUE", Vec::new()).await
+    }
+
+    async fn scan(&self, prefix: &str) -> Result<Vec<(String, StoredLink)>, StoreError> {
+        self.select("substr(key, 1, length(?1)) = ?1", vec![prefix.to_string()])
+            .await
+    }
+
+    async fn revision(&self) -> Result<u64, StoreError> {
+        let path = self.path.clone();
+        self.run(move |connection| {
+            revision(connection).map_err(|err| StoreError::Backend(format!("{}: {}", path, err)))
+        })
+        .await
+    }
+
+    async fn apply(&self, edit: LinkEdit, expected: Option<u64>) -> Result<StoredLink, StoreError> {
+        let path = self.path.clone();
+        let change = self
+            .run(move |connection| {
+                let error =
+                    |err: rusqlite::Error| StoreError::Backend(format!("{}: {}", path, err));
+
+                // The link is read and written in the same transaction, so that changes
+                // from other processes sharing the database are not overwritten.
+                let transaction = connection
+                    .transaction_with_behavior(TransactionBehavior::Immediate)
+                    .map_err(error)?;
+                let existing: Option<(String, i64)> = transaction
+                    .query_row(
+                        "SELECT link, revision FROM links WHERE key = ?1",
+                        [edit.key().trim_matches('/')],
+                        |row| Ok((row.get(0)?, row.get(1)?)),
+                    )
+                    .optional()
+                    .map_err(error)?;
+                let existing = existing
+                    .map(|(link, revision)| {
+                        let link = decode(&path, &link)?;
+                        Ok::<_, StoreError>(StoredLink {
+                            link,
+                            revision: revision as u64,
+                        })
+                    })
+                    .transpose()?;
+
+                let next = revision(&transaction).map_err(error)? + 1;
+                let links = || {
+                    let mut statement = transaction
+                        .prepare("SELECT key, link FROM links")
+                        .map_err(error)?;
+                    let rows = statement
+                        .query_map([], |row| Ok((row.get(0)?, row.get::<_, String>(1)?)))
+                        .map_err(error)?;
+                    rows.map(|row| {
+                        let (key, link) = row.map_err(error)?;
+                        Ok((key, decode(&path, &link)?))
+                    })
+                    .collect()
+                };
+                let change = store::change(edit, existing, expected, next, links)?;
+                match &change.stored {
+                    Some(link) => {
+                        let link = serde_json::to_string(link)
+                            .map_err(|err| StoreError::Backend(err.to_string()))?;
+                        transaction.execute(
+                            "INSERT INTO links (key, link, revision) VALUES (?1, ?2, ?3)
+                            ON CONFLICT (key) DO UPDATE
+                            SET link = excluded.link, revision = excluded.revision",
+                            rusqlite::params![&change.key, &link, next as i64],
+                        )
+                    }
+                    None => transaction.execute("DELETE FROM links WHERE key = ?1", [&change.key]),
+                }
+                .map_err(error)?;
+                transaction
+                    .execute("UPDATE revision SET revision = ?1", [next as i64])
+                    .map_err(error)?;
+
+                transaction.commit().map_err(error)?;
+                Ok(change)
+            })
+            .await?;
+
+        let _ = self.changes.send(change.changes);
+        Ok(change.link)
+    }
+
+    fn subscribe(&self) -> broadcast::Receiver<RoutesDiff> {
+        self.changes.subscribe()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::BTreeMap;
+    use std::fs;
+
+    use crate::link::QueryPolicy;
+
+    fn database(name: &str) -> String {
+        let path = std::env::temp_dir().join(format!("golinks-{}-{}", std::process::id(), name));
+        let _ = fs::remove_file(&path);
+        path.display().to_string()
+    }
+
+    #[rocket::async_test]
+    async fn test_changes_from_other_processes() {
+        let path = database("shared.db");
+        let store = SqliteStore::open(&path).unwrap();
+        let other = SqliteStore::open(&path).unwrap();
+        let mut changes = store.subscribe();
+
+        other
+            .upsert("docs", Link::new("https://docs.example.com"))
+            .await
+            .unwrap();
+        let changed = rocket::tokio::time::timeout(POLL_INTERVAL * 5, changes.recv())
+            .await
+            .expect("The change should be picked up")
+            .unwrap();
+        assert_eq!(changed.added, vec!["docs"]);
+        assert_eq!(
+            store.get("docs").await.unwrap().unwrap().link.url,
+            "https://docs.example.com"
+        );
+
+        other.delete("docs").await.unwrap();
+        let changed = rocket::tokio::time::timeout(POLL_INTERVAL * 5, changes.recv())
+            .await
+            .expect("The change should be picked up")
+            .unwrap();
+        assert_eq!(changed.removed, vec!["docs"]);
+    }
+
+    #[rocket::async_test]
+    async fn test_sqlite_store() {
+        let path = database("store.db");
+        let store = SqliteStore::open(&path).unwrap();
+        let mut changes = store.subscribe();
+
+        let link = Link {
+            query: QueryPolicy::Drop,
+            description: Some("The docs".to_string()),
+            tags: vec!["docs".to_string()],
+            labels: BTreeMap::from([("team".to_string(), "infra".to_string())]),
+            ..Link::new("https://docs.example.com")
+        };
+        let created = store
+            .apply(LinkEdit::Create("/docs/".to_string(), link.clone()), None)
+            .await
+            .unwrap();
+        assert_eq!(created.revision, 1);
+        assert_eq!(changes.recv().await.unwrap().added, vec!["docs"]);
+        store
+            .upsert("docs/api", Link::new("https://api.example.com"))
+            .await
+            .unwrap();
+        store
+            .upsert("docsite", Link::new("https://site.example.com"))
+            .await
+            .unwrap();
+
+        // Links and revisions are kept when the database is opened again.
+        let store = SqliteStore::open(&path).unwrap();
+        assert_eq!(store.get("docs").await.unwrap(), Some(created));
+        assert_eq!(store.list().await.unwrap().len(), 3);
+        assert_eq!(store.revision().await.unwrap(), 3);
+
+        let keys: Vec<String> = store
+            .scan("docs/")
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|(key, _)| key)
+            .collect();
+        assert_eq!(keys, vec!["docs/api"]);
+
+        let update = |url: &str| LinkEdit::Update("docs".to_string(), Link::new(url));
+        let updated = store
+            .apply(update("https://new-docs.example.com"), Some(1))
+            .await
+            .unwrap();
+        assert_eq!(updated.link.url, "https://new-docs.example.com");
+        assert_eq!(updated.revision, 4);
+        assert!(matches!(
+            store.apply(update("https://x.example.com"), Some(1)).await,
+            Err(StoreError::Conflict { actual: 4, .. })
+        ));
+
+        let deleted = store.delete("docsite").await.unwrap();
+        assert_eq!(deleted.link.url, "https://site.example.com");
+        assert_eq!(deleted.revision, 3);
+        assert!(store.get("docsite").await.unwrap().is_none());
+        assert_eq!(store.revision().await.unwrap(), 5);
+
+        assert!(matches!(
+            store.delete("docsite").await,
+            Err(StoreError::NotFound(_))
+        ));
+        assert!(matches!(
+            store
+                .apply(
+                    LinkEdit::Create("docs".to_string(), Link::new("https://x.example.com")),
+                    None
+                )
+                .await,
+            Err(StoreError::Exists(_))
+        ));
+        assert!(matches!(
+            store
+                .upsert("_/links", Link::new("https://x.example.com"))
+                .await,
+            Err(StoreError::Invalid(_))
+        ));
+        assert_eq!(store.list().await.unwrap().len(), 2);
+        assert_eq!(store.revision().await.unwrap(), 5);
+    }
+
+    #[rocket::async_test]
+    async fn test_migrate() {
+        // A database from before revisions were kept.
+        let path = database("migrate.db");
+        let connection = Connection::open(&path).unwrap();
+        connection.execute_batch(MIGRATIONS[0]).unwrap();
+        connection
+            .execute(
+                "INSERT INTO links (key, link) VALUES ('docs', ?1)",
+                [serde_json::to_string(&Link::new("https://docs.example.com")).unwrap()],
+            )
+            .unwrap();
+        drop(connection);
+
+        let store = SqliteStore::open(&path).unwrap();
+        assert_eq!(store.revision().await.unwrap(), 1);
+        assert_eq!(store.get("docs").await.unwrap().unwrap().revision, 1);
+
+        let updated = store
+            .upsert("docs", Link::new("https://new-docs.example.com"))
+            .await
+            .unwrap();
+        assert_eq!(updated.revision, 2);
+
+        // Opening an up to date database leaves it as it is.
+        let store = SqliteStore::open(&path).unwrap();
+        assert_eq!(store.get("docs").await.unwrap(), Some(updated));
+    }
+
+    #[test]
+    fn test_open_invalid() {
+        let path = database("store-dir");
+        fs::create_dir_all(&path).unwrap();
+
+        assert!(matches!(
+            SqliteStore::open(&path),
+            Err(StoreError::Backend(_))
+        ));
+    }
+}
@@ -0,0 +1,634 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::{Arc, RwLock};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use log::{debug, error};
+use rocket::serde::{Deserialize, Serialize};
+use tokio::sync::broadcast::{self, error::RecvError};
+
+use crate::config::AppConfig;
+use crate::edit::{EditError, LinkEdit};
+use crate::link::Link;
+use crate::lint::{self, Severity};
+use crate::models::{Routes, RoutesDiff, SharedRoutes, CHANGES_CAPACITY};
+use crate::reload::Reloader;
+use crate::sqlite::SqliteStore;
+
+/// Where the links are stored.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(crate = "rocket::serde", rename_all = "lowercase")]
+pub enum StoreKind {
+    /// The links files. Changes are written back to the links file of the link.
+    #[default]
+    File,
+    /// An SQLite database.
+    Sqlite,
+    /// Memory, starting with the links of the links files. Changes are lost when the
+    /// service stops.
+    Memory,
+}
+
+/// A link along with the revision of the store it was last changed at.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct StoredLink {
+    pub link: Link,
+    pub revision: u64,
+}
+
+/// Errors that can occur while reading or changing the links of a store.
+#[derive(Debug)]
+pub enum StoreError {
+    /// No link is registered for the key.
+    NotFound(String),
+    /// A link is already registered for the key.
+    Exists(String),
+    /// The link changed since the revision the change was made against.
+    Conflict {
+        key: String,
+        expected: u64,
+        actual: u64,
+    },
+    /// The link cannot be changed in the store, such as a link the file store loaded
+    /// from a remote URL.
+    ReadOnly(String),
+    /// The change would make the links invalid.
+    Invalid(String),
+    /// The store could not be read or written.
+    Backend(String),
+}
+
+impl fmt::Display for StoreError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StoreError::NotFound(key) => write!(f, "No link is registered for '{}'", key),
+            StoreError::Exists(key) => write!(f, "A link is already registered for '{}'", key),
+            StoreError::Conflict {
+                key,
+                expected,
+                actual,
+            } => write!(
+                f,
+                "The link '{}' was changed since revision {}, it is at revision {}",
+                key, expected, actual
+            ),
+            StoreError::ReadOnly(message)
+            | StoreError::Invalid(message)
+            | StoreError::Backend(message) => f.write_str(message),
+        }
+    }
+}
+
+impl std::error::Error for StoreError {}
+
+impl From<EditError> for StoreError {
+    fn from(err: EditError) -> Self {
+        match err {
+            EditError::NotFound(key) => StoreError::NotFound(key),
+            EditError::Exists(key) => StoreError::Exists(key),
+            EditError::Conflict {
+                key,
+                expected,
+                actual,
+            } => StoreError::Conflict {
+                key,
+                expected,
+                actual,
+            },
+            EditError::ReadOnly { .. }
+            | EditError::NoLinksFile(_)
+            | EditError::Unsupported { .. } => StoreError::ReadOnly(err.to_string()),
+            EditError::Load(_) => StoreError::Invalid(err.to_string()),
+            EditError::Write { .. } => StoreError::Backend(err.to_string()),
+        }
+    }
+}
+
+/// Storage for the links. The active routes are loaded from the store, and the admin
+/// endpoints change the links through it.
+///
+/// Every change to the links increases the revision of the store, and the links it
+/// changed are stored with the new revision. Changes can be made against a revision of
+/// a link, and are rejected if the link changed since.
+///
+/// Keys are given without leading or trailing slashes, and lists of links are sorted
+/// by key.
+#[rocket::async_trait]
+pub trait LinkStore: Send + Sync {
+    /// Gets the link registered for `key`.
+    async fn get(&self, key: &str) -> Result<Option<StoredLink>, StoreError>;
+
+    /// Gets every link.
+    async fn list(&self) -> Result<Vec<(String, StoredLink)>, StoreError>;
+
+    /// Gets the links with a key starting with `prefix`.
+    async fn scan(&self, prefix: &str) -> Result<Vec<(String, StoredLink)>, StoreError> {
+        let mut links = self.list().await?;
+        links.retain(|(key, _)| key.starts_with(prefix));
+        Ok(links)
+    }
+
+    /// Gets the revision of the store, which is the revision of its latest change.
+    async fn revision(&self) -> Result<u64, StoreError>;
+
+    /// Applies a change to a link, responding with the link as it is stored after the
+    /// change, or as it was stored before it was removed. With `expected`, the change is
+    /// only applied if the link is still at that revision.
+    async fn apply(&self, edit: LinkEdit, expected: Option<u64>) -> Result<StoredLink, StoreError>;
+
+    /// Replaces the link registered for `key`, adding it if there is none.
+    async fn upsert(&self, key: &str, link: Link) -> Result<StoredLink, StoreError> {
+        self.apply(LinkEdit::Upsert(key.to_string(), link), None)
+            .await
+    }
+
+    /// Removes the link registered for `key`, responding with the link that was removed.
+    async fn delete(&self, key: &str) -> Result<StoredLink, StoreError> {
+        self.apply(LinkEdit::Delete(key.to_string()), None).await
+    }
+
+    /// Subscribes to the changes of the links, which are sent every time links are
+    /// added, removed or changed.
+    fn subscribe(&self) -> broadcast::Receiver<RoutesDiff>;
+}
+
+/// A store shared by every request handler.
+pub type SharedStore = Arc<dyn LinkStore>;
+
+/// Opens the store selected by `configs`. The file store changes the links files
+/// loaded by `reloader`, and the memory store starts with the links it loaded.
+pub fn open(configs: &AppConfig, reloader: &Reloader) -> Result<SharedStore, StoreError> {
+    Ok(match configs.store() {
+        StoreKind::File => Arc::new(FileStore::new(reloader.clone())),
+        StoreKind::Memory => Arc::new(MemoryStore::new(
+            reloader
+                .routes()
+                .load()
+                .links()
+                .map(|(key, link)| (key.to_string(), link.clone()))
+                .collect(),
+        )),
+        StoreKind::Sqlite => Arc::new(SqliteStore::open(configs.database())?),
+    })
+}
+
+/// Gets the revision that stores which do not keep their revisions start at. It is the
+/// time in microseconds, so that revisions keep increasing when the service restarts
+/// and a revision from before the restart never matches a link after it.
+pub fn initial_revision() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(1, |elapsed| elapsed.as_micros() as u64)
+}
+
+/// Replaces the links of `routes` with the links of `store`, keeping its patterns and
+/// index settings.
+pub async fn refresh(store: &dyn LinkStore, routes: &SharedRoutes) -> Result<(), StoreError> {
+    let links = store
+        .list()
+        .await?
+        .into_iter()
+        .map(|(key, stored)| (key, stored.link))
+        .collect();
+    let current = routes.load();
+    let refreshed = Routes::with_links(links)
+        .with_patterns(current.patterns().to_vec())
+        .with_indexes(current.indexes().clone());
+
+    routes.replace(refreshed);
+    Ok(())
+}
+
+/// Keeps `routes` serving the links of `store`, refreshing them every time `changes`
+/// reports that the store changed. Only stores other than the file store need this,
+/// since the file store changes the routes itself.
+pub async fn sync_routes(
+    store: SharedStore,
+    routes: SharedRoutes,
+    mut changes: broadcast::Receiver<RoutesDiff>,
+) {
+    loop {
+        match changes.recv().await {
+            Ok(changes) => debug!("The links in the store changed: {}", changes),
+            // Every refresh loads all the links, so missed changes are caught up on.
+            Err(RecvError::Lagged(_)) => {}
+            Err(RecvError::Closed) => return,
+        }
+
+        if let Err(err) = refresh(store.as_ref(), &routes).await {
+            error!("Could not load the links from the store: {}", err);
+        }
+    }
+}
+
+/// What a change does to a store holding `existing` for its key.
+pub(crate) struct Change {
+    pub key: String,
+    /// The link to store, or `None` to remove the stored link.
+    pub stored: Option<Link>,
+    /// The link to respond with.
+    pub link: StoredLink,
+    pub changes: RoutesDiff,
+}
+
+/// Works out what a change does to a store holding `existing` for its key, checking
+/// that the link is still at the `expected` revision and that the links of the store,
+/// as given by `links`, are valid with the changed link. A changed link is stored at
+/// `revision`.
+pub(crate) fn change(
+    edit: LinkEdit,
+    existing: Option<StoredLink>,
+    expected: Option<u64>,
+    revision: u64,
+    links: impl FnOnce() -> Result<HashMap<String, Link>, StoreError>,
+) -> Result<Change, StoreError> {
+    let key = edit.key().trim_matches('/').to_string();
+    let one = || vec![key.clone()];
+
+    if let (Some(expected), Some(existing)) = (expected, &existing) {
+        if existing.revision != expected {
+            return Err(StoreError::Conflict {
+                key,
+                expected,
+                actual: existing.revision,
+            });
+        }
+    }
+
+    let (stored, changes) = match (edit, &existing) {
+        (LinkEdit::Create(..), Some(_)) => return Err(StoreError::Exists(key)),
+        (LinkEdit::Update(..) | LinkEdit::Delete(_), None) => {
+            return Err(StoreError::NotFound(key))
+        }
+        (LinkEdit::Delete(_), Some(_)) => (
+            None,
+            RoutesDiff {
+                removed: one(),
+                ..Default::default()
+            },
+        ),
+        (LinkEdit::Create(_, link) | LinkEdit::Update(_, link) | LinkEdit::Upsert(_, link), _) => {
+            let link = Link {
+                source: None,
+                ..link
+            };
+            let mut links = links()?;
+            links.insert(key.clone(), link.clone());
+            validate(Routes::with_links(links))?;
+
+            let changes = match existing {
+                Some(_) => RoutesDiff {
+                    changed: one(),
+                    ..Default::default()
+                },
+                None => RoutesDiff {
+                    added: one(),
+                    ..Default::default()
+                },
+            };
+            (Some(link), changes)
+        }
+    };
+
+    let link = match &stored {
+        Some(link) => StoredLink {
+            link: link.clone(),
+            revision,
+        },
+        None => existing.unwrap_or(StoredLink {
+            link: Link::default(),
+            revision,
+        }),
+    };
+    Ok(Change {
+        key,
+        stored,
+        link,
+        changes,
+    })
+}
+
+/// Checks the links a store would hold after a change the way the links files are
+/// checked when they are loaded.
+fn validate(routes: Routes) -> Result<(), StoreError> {
+    routes.validate().map_err(StoreError::Invalid)?;
+
+    let errors: Vec<String> = lint::lint(&routes, &[])
+        .into_iter()
+        .filter(|diagnostic| diagnostic.severity == Severity::Error)
+        .map(|diagnostic| diagnostic.to_string())
+        .collect();
+    if !errors.is_empty() {
+        return Err(StoreError::Invalid(errors.join("\n")));
+    }
+
+    Ok(())
+}
+
+/// Stores the links in the links files, changing them through the reloader so that
+/// changes are written back to the links files. The reloader keeps the revisions of the
+/// links, which move whenever a link changes, whether through the store or in the links
+/// files.
+pub struct FileStore {
+    reloader: Reloader,
+}
+
+impl FileStore {
+    /// Creates a store for the links files loaded by `reloader`.
+    pub fn new(reloader: Reloader) -> Self {
+        Self { reloader }
+    }
+}
+
+#[rocket::async_trait]
+impl LinkStore for FileStore {
+    async fn get(&self, key: &str) -> Result<Option<StoredLink>, StoreError> {
+        // The revision is read before the link, so that a link is never paired with a
+        // revision it was not changed at yet.
+        let revision = self.reloader.revision_of(key);
+        let link = self.reloader.routes().load().fetch(key).cloned();
+
+        Ok(link
+            .zip(revision)
+            .map(|(link, revision)| StoredLink { link, revision }))
+    }
+
+    async fn list(&self) -> Result<Vec<(String, StoredLink)>, StoreError> {
+        let revisions = self.reloader.revisions();
+        let mut links: Vec<(String, StoredLink)> = self
+            .reloader
+            .routes()
+            .load()
+            .links()
+            .filter_map(|(key, link)| {
+                let revision = *revisions.get(key)?;
+                Some((
+                    key.to_string(),
+                    StoredLink {
+                        link: link.clone(),
+                        revision,
+                    },
+                ))
+            })
+            .collect();
+        links.sort_by(|(a, _), (b, _)| a.cmp(b));
+        Ok(links)
+    }
+
+    async fn revision(&self) -> Result<u64, StoreError> {
+        Ok(self.reloader.revision())
+    }
+
+    async fn apply(&self, edit: LinkEdit, expected: Option<u64>) -> Result<StoredLink, StoreError> {
+        Ok(self.reloader.edit(edit, expected).await?)
+    }
+
+    fn subscribe(&self) -> broadcast::Receiver<RoutesDiff> {
+        self.reloader.routes().subscribe()
+    }
+}
+
+/// The links of a memory store, along with its revision.
+struct MemoryLinks {
+    links: HashMap<String, StoredLink>,
+    revision: u64,
+}
+
+/// Stores the links in memory, which is mostly useful for tests.
+pub struct MemoryStore {
+    links: RwLock<MemoryLinks>,
+    changes: broadcast::Sender<RoutesDiff>,
+}
+
+impl MemoryStore {
+    /// Creates a store holding `links`.
+    pub fn new(links: HashMap<String, Link>) -> Self {
+        let revision = initial_revision();
+        let links = links
+            .into_iter()
+            .map(|(key, link)| (key, StoredLink { link, revision }))
+            .collect();
+
+        Self {
+            links: RwLock::new(MemoryLinks { links, revision }),
+            changes: broadcast::channel(CHANGES_CAPACITY).0,
+        }
+    }
+}
+
+impl Default for MemoryStore {
+    fn default() -> Self {
+        Self::new(HashMap::new())
+    }
+}
+
+#[rocket::async_trait]
+impl LinkStore for MemoryStore {
+    async fn get(&self, key: &str) -> Result<Option<StoredLink>, StoreError> {
+        Ok(self.links.read().unwrap().links.get(key).cloned())
+    }
+
+    async fn list(&self) -> Result<Vec<(String, StoredLink)>, StoreError> {
+        let mut links: Vec<(String, StoredLink)> = self
+            .links
+            .read()
+            .unwrap()
+            .links
+            .iter()
+            .map(|(key, stored)| (key.clone(), stored.clone()))
+            .collect();
+        links.sort_by(|(a, _), (b, _)| a.cmp(b));
+        Ok(links)
+    }
+
+    async fn revision(&self) -> Result<u64, StoreError> {
+        Ok(self.links.read().unwrap().revision)
+    }
+
+    async fn apply(&self, edit: LinkEdit, expected: Option<u64>) -> Result<StoredLink, StoreError> {
+        let change = {
+            let mut state = self.links.write().unwrap();
+            let existing = state.links.get(edit.key().trim_matches('/')).cloned();
+            let links = || {
+                Ok(state
+                    .links
+                    .iter()
+                    .map(|(key, stored)| (key.clone(), stored.link.clone()))
+                    .collect())
+            };
+            let change = change(edit, existing, expected, state.revision + 1, links)?;
+
+            state.revision += 1;
+            match &change.stored {
+                Some(_) => state.links.insert(change.key.clone(), change.link.clone()),
+                None => state.links.remove(&change.key),
+            };
+            change
+        };
+
+        let _ = self.changes.send(change.changes);
+        Ok(change.link)
+    }
+
+    fn subscribe(&self) -> broadcast::Receiver<RoutesDiff> {
+        self.changes.subscribe()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[rocket::async_test]
+    async fn test_memory_store() {
+        let store = MemoryStore::new(HashMap::from([(
+            "docs".to_string(),
+            Link::new("https://docs.example.com"),
+        )]));
+        let mut changes = store.subscribe();
+        let initial = store.revision().await.unwrap();
+        assert_eq!(store.get("docs").await.unwrap().unwrap().revision, initial);
+
+        let stored = store
+            .apply(
+                LinkEdit::Create(
+                    "/docs/api/".to_string(),
+                    Link::new("https://api.example.com"),
+                ),
+                None,
+            )
+            .await
+            .unwrap();
+        assert_eq!(stored.link.url, "https://api.example.com");
+        assert_eq!(stored.revision, initial + 1);
+        assert_eq!(changes.recv().await.unwrap().added, vec!["docs/api"]);
+
+        let stored = store
+            .upsert("docs", Link::new("https://new-docs.example.com"))
+            .await
+            .unwrap();
+        assert_eq!(stored.link.url, "https://new-docs.example.com");
+        assert_eq!(stored.revision, initial + 2);
+        assert_eq!(changes.recv().await.unwrap().changed, vec!["docs"]);
+
+        let keys = |links: Vec<(String, StoredLink)>| -> Vec<String> {
+            links.into_iter().map(|(key, _)| key).collect()
+        };
+        assert_eq!(keys(store.list().await.unwrap()), vec!["docs", "docs/api"]);
+        assert_eq!(keys(store.scan("docs/").await.unwrap()), vec!["docs/api"]);
+
+        let stored = store.delete("docs/api").await.unwrap();
+        assert_eq!(stored.link.url, "https://api.example.com");
+        assert_eq!(stored.revision, initial + 1);
+        assert_eq!(changes.recv().await.unwrap().removed, vec!["docs/api"]);
+        assert!(store.get("docs/api").await.unwrap().is_none());
+        assert_eq!(store.revision().await.unwrap(), initial + 3);
+
+        assert!(matches!(
+            store.delete("docs/api").await,
+            Err(StoreError::NotFound(_))
+        ));
+        assert!(matches!(
+            store
+                .apply(
+                    LinkEdit::Create("docs".to_string(), Link::new("https://x.example.com")),
+                    None
+                )
+                .await,
+            Err(StoreError::Exists(_))
+        ));
+        assert!(matches!(
+            store
+                .upsert("_/admin", Link::new("https://x.example.com"))
+                .await,
+            Err(StoreError::Invalid(_))
+        ));
+        assert!(matches!(
+            store.upsert("broken", Link::new("not a url")).await,
+            Err(StoreError::Invalid(_))
+        ));
+        assert_eq!(store.list().await.unwrap().len(), 1);
+        assert_eq!(store.revision().await.unwrap(), initial + 3);
+    }
+
+    #[rocket::async_test]
+    async fn test_changes_validate_every_link() {
+        // The store was filled without checking its links, which every change then has
+        // to fix first, the same way a links file with an invalid link cannot be changed.
+        let store = MemoryStore::new(HashMap::from([(
+            "_/status".to_string(),
+            Link::new("https://status.example.com"),
+        )]));
+
+        let result = store
+            .upsert("docs", Link::new("https://docs.example.com"))
+            .await;
+        assert!(
+            matches!(&result, Err(StoreError::Invalid(message)) if message.contains("_/status")),
+            "{:?}",
+            result
+        );
+        assert!(store.delete("_/status").await.is_ok());
+        assert!(store
+            .upsert("docs", Link::new("https://docs.example.com"))
+            .await
+            .is_ok());
+    }
+
+    #[rocket::async_test]
+    async fn test_conflict() {
+        let store = MemoryStore::new(HashMap::from([(
+            "oncall".to_string(),
+            Link::new("https://oncall.example.com"),
+        )]));
+        let seen = store.get("oncall").await.unwrap().unwrap().revision;
+
+        let update = |url: &str| LinkEdit::Update("oncall".to_string(), Link::new(url));
+        let first = store
+            .apply(update("https://first.example.com"), Some(seen))
+            .await
+            .unwrap();
+
+        // The second change was made against the revision the first one replaced.
+        let result = store
+            .apply(update("https://second.example.com"), Some(seen))
+            .await;
+        assert!(
+            matches!(result, Err(StoreError::Conflict { actual, .. }) if actual == first.revision)
+        );
+        let result = store
+            .apply(LinkEdit::Delete("oncall".to_string()), Some(seen))
+            .await;
+        assert!(matches!(result, Err(StoreError::Conflict { .. })));
+        assert_eq!(
+            store.get("oncall").await.unwrap().unwrap().link.url,
+            "https://first.example.com"
+        );
+
+        store
+            .apply(update("https://second.example.com"), Some(first.revision))
+            .await
+            .unwrap();
+    }
+
+    #[rocket::async_test]
+    async fn test_sync_routes() {
+        let store: SharedStore = Arc::new(MemoryStore::default());
+        let routes = SharedRoutes::new(Routes::with_links(HashMap::new()));
+        let changes = store.subscribe();
+        let mut served = routes.subscribe();
+        let task = rocket::tokio::spawn(sync_routes(store.clone(), routes.clone(), changes));
+
+        store
+            .upsert("docs", Link::new("https://docs.example.com"))
+            .await
+            .unwrap();
+        assert_eq!(served.recv().await.unwrap().added, vec!["docs"]);
+        assert_eq!(
+            routes.load().fetch("docs").map(|link| link.url.as_str()),
+            Some("https://docs.example.com")
+        );
+
+        task.abort();
+    }
+}
@@ -0,0 +1,235 @@
+use std::cmp::Reverse;
+
+use rocket::serde::Serialize;
+
+use crate::models::Routes;
+
+/// The most similar links suggested for an unknown path.
+const MAX_SIMILAR: usize = 5;
+
+/// The most links under an unknown path suggested for it.
+const MAX_CHILDREN: usize = 10;
+
+/// Struct for serializing the links suggested for a path that is not a known link.
+#[derive(Debug, Default, PartialEq, Eq, Serialize)]
+#[serde(crate = "rocket::serde")]
+pub struct Suggestions {
+    /// Links that look like a typo of the path, closest first.
+    pub similar: Vec<String>,
+    /// Links that start with the path, in key order.
+    pub children: Vec<String>,
+}
+
+impl Suggestions {
+    /// Gets a value indicating whether there is nothing to suggest.
+    pub fn is_empty(&self) -> bool {
+        self.similar.is_empty() && self.children.is_empty()
+    }
+}
+
+/// Finds the links that are closest to `path` by edit distance and shared prefix, and the
+/// links that are nested under `path`.
+///
+/// Every key is compared, so the edit distance is bounded and keys whose length rules them
+/// out are skipped without computing it. This keeps suggestions cheap even with tens of
+/// thousands of links.
+pub fn suggest(routes: &Routes, path: &str) -> Suggestions {
+    let path = path.trim_matches('/');
+    if path.is_empty() {
+        return Suggestions::default();
+    }
+
+    let path_chars: Vec<char> = path.chars().collect();
+    let max_distance = (path_chars.len() / 3).clamp(1, 3);
+    let min_shared_prefix = path_chars.len().min(4);
+    let children_prefix = format!("{}/", path);
+    // A prefix with its index disabled should not have its links listed here either.
+    let list_children = !routes.indexes().is_disabled(path);
+
+    let mut distance = BoundedDistance::new(&path_chars, max_distance);
+    let mut similar = Vec::new();
+    let mut children = Vec::new();
+
+    for (key, _) in routes.links() {
+        if key.starts_with(&children_prefix) {
+            if list_children {
+                children.push(key);
+            }
+            continue;
+        }
+
+        let shared = shared_prefix(path, key);
+        let distance = distance.to(key);
+
+        if distance.is_some() || shared >= min_shared_prefix {
+            let distance = distance.unwrap_or(max_distance + 1);
+            similar.push((distance, Reverse(shared), key));
+        }
+    }
+
+    // Only the best few are needed, so avoid sorting every candidate.
+    if similar.len() > MAX_SIMILAR {
+        similar.select_nth_unstable(MAX_SIMILAR);
+        similar.truncate(MAX_SIMILAR);
+    }
+    similar.sort();
+    children.sort();
+
+    Suggestions {
+        similar: similar
+            .into_iter()
+            .map(|(_, _, key)| key.to_string())
+            .collect(),
+        children: children
+            .into_iter()
+            .take(MAX_CHILDREN)
+            .map(str::to_string)
+            .collect(),
+    }
+}
+
+/// Gets the number of leading characters `a` and `b` have in common.
+fn shared_prefix(a: &str, b: &str) -> usize {
+    a.chars().zip(b.chars()).take_while(|(x, y)| x == y).count()
+}
+
+/// Computes edit distances from a fixed string, counting insertions, deletions,
+/// substitutions and transpositions of adjacent characters as one edit each. Gives up
+/// as soon as a distance is known to be larger than `max`, and reuses its buffers
+/// between comparisons.
+struct BoundedDistance<'a> {
+    from: &'a [char],
+    max: usize,
+    before: Vec<usize>,
+    previous: Vec<usize>,
+    current: Vec<usize>,
+}
+
+impl<'a> BoundedDistance<'a> {
+    fn new(from: &'a [char], max: usize) -> Self {
+        Self {
+            from,
+            max,
+            before: vec![0; from.len() + 1],
+            previous: vec![0; from.len() + 1],
+            current: vec![0; from.len() + 1],
+        }
+    }
+
+    fn to(&mut self, other: &str) -> Option<usize> {
+        if self.from.len().abs_diff(other.chars().count()) > self.max {
+            return None;
+        }
+
+        for (j, value) in self.previous.iter_mut().enumerate() {
+            *value = j;
+        }
+
+        let mut last = None;
+        for (i, c) in other.chars().enumerate() {
+            self.current[0] = i + 1;
+            let mut row_min = self.current[0];
+
+            for (j, from) in self.from.iter().enumerate() {
+                let mut distance = (self.previous[j] + usize::from(c != *from))
+                    .min(self.previous[j + 1] + 1)
+                    .min(self.current[j] + 1);
+
+                if j > 0 && last == Some(*from) && c == self.from[j - 1] {
+                    distance = distance.min(self.before[j - 1] + 1);
+                }
+
+                self.current[j + 1] = distance;
+                row_min = row_min.min(distance);
+            }
+
+            if row_min > self.max {
+                return None;
+            }
+
+            last = Some(c);
+            std::mem::swap(&mut self.before, &mut self.previous);
+            std::mem::swap(&mut self.previous, &mut self.current);
+        }
+
+        Some(self.previous[self.from.len()]).filter(|&distance| distance <= self.max)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::IndexSettings;
+    use std::collections::HashMap;
+
+    fn routes(keys: &[&str]) -> Routes {
+        Routes::with_routes(
+            keys.iter()
+                .map(|key| (key.to_string(), "https://example.com".to_string()))
+                .collect::<HashMap<_, _>>(),
+        )
+    }
+
+    fn bounded_distance(a: &str, b: &str, max: usize) -> Option<usize> {
+        let chars: Vec<char> = a.chars().collect();
+        BoundedDistance::new(&chars, max).to(b)
+    }
+
+    #[test]
+    fn test_bounded_distance() {
+        assert_eq!(bounded_distance("github", "github", 2), Some(0));
+        assert_eq!(bounded_distance("githbu", "github", 2), Some(1));
+        assert_eq!(bounded_distance("githbu", "gitbuh", 2), Some(2));
+        assert_eq!(bounded_distance("gihub", "github", 2), Some(1));
+        assert_eq!(bounded_distance("gitlab", "github", 1), None);
+        assert_eq!(bounded_distance("g", "github", 2), None);
+    }
+
+    #[test]
+    fn test_suggest_similar() {
+        let routes = routes(&["github", "gitlab", "google", "mail"]);
+        let suggestions = suggest(&routes, "githbu");
+
+        assert_eq!(suggestions.similar, vec!["github"]);
+        assert!(suggestions.children.is_empty());
+    }
+
+    #[test]
+    fn test_suggest_children() {
+        let routes = routes(&["infra/dash", "infra/oncall", "infrastructure", "eng/x"]);
+        let suggestions = suggest(&routes, "/infra");
+
+        assert_eq!(suggestions.children, vec!["infra/dash", "infra/oncall"]);
+        assert_eq!(suggestions.similar, vec!["infrastructure"]);
+    }
+
+    #[test]
+    fn test_suggest_no_children_of_disabled_index() {
+        let routes = routes(&["hr/payroll", "hr/benefits"]).with_indexes(IndexSettings {
+            disabled: vec!["hr".to_string()],
+        });
+        let suggestions = suggest(&routes, "hr");
+
+        assert!(suggestions.children.is_empty());
+    }
+
+    #[test]
+    fn test_suggest_many_links() {
+        let keys: Vec<String> = (0..50_000)
+            .map(|i| format!("team{}/link{}", i % 100, i))
+            .collect();
+        let routes = Routes::with_routes(
+            keys.into_iter()
+                .map(|key| (key, "https://example.com".to_string()))
+                .collect(),
+        );
+
+        let suggestions = suggest(&routes, "team7/lnik7");
+
+        assert_eq!(
+            suggestions.similar.first().map(String::as_str),
+            Some("team7/link7")
+        );
+        assert!(suggestions.children.is_empty());
+    }
+}
@@ -0,0 +1,328 @@
+use percent_encoding::{utf8_percent_encode, AsciiSet, CONTROLS};
+
+/// Characters that must be escaped when a value is substituted into a path segment.
+const PATH_SEGMENT: &AsciiSet = &PATH_CAPTURE.add(b'/');
+
+/// Characters that must be escaped when a capture of a pattern is substituted into the
+/// path. Slashes are kept so that a capture spanning several segments stays a path.
+const PATH_CAPTURE: &AsciiSet = &CONTROLS
+    .add(b' ')
+    .add(b'"')
+    .add(b'#')
+    .add(b'%')
+    .add(b'<')
+    .add(b'>')
+    .add(b'?')
+    .add(b'`')
+    .add(b'{')
+    .add(b'}');
+
+/// Characters that must be escaped when a value is substituted into a query string
+/// or fragment.
+const QUERY_COMPONENT: &AsciiSet = &CONTROLS
+    .add(b' ')
+    .add(b'"')
+    .add(b'#')
+    .add(b'%')
+    .add(b'&')
+    .add(b'+')
+    .add(b'<')
+    .add(b'=')
+    .add(b'>')
+    .add(b'`')
+    .add(b'{')
+    .add(b'}');
+
+/// A piece of a link target.
+#[derive(Debug, PartialEq, Eq)]
+enum Part<'a> {
+    /// Text that is copied to the destination as-is.
+    Literal(&'a str),
+    /// `{N}`, the N-th (1-based) path segment after the matched link, or the
+    /// N-th capture group of a pattern.
+    Segment(usize),
+    /// `{*}`, every path segment after the last positional placeholder.
+    Tail,
+    /// `{name}`, the value of the `name` capture group of a pattern, or of the
+    /// `name` query parameter.
+    Query(&'a str),
+}
+
+/// The values that placeholders in a link target are substituted with.
+#[derive(Debug, Default)]
+pub struct TemplateContext<'a> {
+    /// The url-decoded path segments that follow the matched link, or the
+    /// capture groups of the matched pattern.
+    pub segments: Vec<&'a str>,
+    /// The named capture groups of the matched pattern.
+    pub named: Vec<(&'a str, &'a str)>,
+    /// The url-decoded query parameters of the request.
+    pub query: Vec<(&'a str, &'a str)>,
+    /// Whether `segments` are the capture groups of a pattern, which may span several
+    /// path segments.
+    pub captures: bool,
+}
+
+impl TemplateContext<'_> {
+    /// Gets the value of `{name}`, along with whether it is a capture of a pattern.
+    fn named_value(&self, name: &str) -> (&str, bool) {
+        if let Some((_, value)) = self.named.iter().find(|(key, _)| *key == name) {
+            return (value, true);
+        }
+
+        let value = self.query.iter().find(|(key, _)| *key == name);
+        (value.map_or("", |(_, value)| value), false)
+    }
+}
+
+/// Splits a link target into literals and placeholders. Braces that do not
+/// contain a valid placeholder are kept as literals.
+fn parse(target: &str) -> Vec<Part<'_>> {
+    let mut parts = Vec::new();
+    let mut rest = target;
+
+    while let Some(start) = rest.find('{') {
+        let Some(len) = rest[start..].find('}') else {
+            break;
+        };
+
+        let placeholder = match &rest[start + 1..start + len] {
+            "*" => Some(Part::Tail),
+            inner if !inner.is_empty() && inner.bytes().all(|b| b.is_ascii_digit()) => {
+                inner.parse().ok().filter(|&n| n > 0).map(Part::Segment)
+            }
+            inner if is_name(inner) => Some(Part::Query(inner)),
+            _ => None,
+        };
+
+        match placeholder {
+            Some(part) => {
+                if start > 0 {
+                    parts.push(Part::Literal(&rest[..start]));
+                }
+                parts.push(part);
+            }
+            None => parts.push(Part::Literal(&rest[..start + len + 1])),
+        }
+
+        rest = &rest[start + len + 1..];
+    }
+
+    if !rest.is_empty() {
+        parts.push(Part::Literal(rest));
+    }
+
+    parts
+}
+
+fn is_name(value: &str) -> bool {
+    let mut chars = value.chars();
+    chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+}
+
+/// Gets a value indicating whether the link target contains any placeholders.
+/// Targets without placeholders have the remaining path appended instead.
+pub fn has_placeholders(target: &str) -> bool {
+    parse(target)
+        .iter()
+        .any(|part| !matches!(part, Part::Literal(_)))
+}
+
+/// Gets the names used by the `{name}` placeholders of the link target.
+pub fn names(target: &str) -> Vec<&str> {
+    parse(target)
+        .into_iter()
+        .filter_map(|part| match part {
+            Part::Query(name) => Some(name),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Gets a value indicating whether the link target has placeholders that are filled
+/// from the path, rather than only from the query string.
+pub fn uses_path(target: &str) -> bool {
+    parse(target)
+        .iter()
+        .any(|part| matches!(part, Part::Segment(_) | Part::Tail))
+}
+
+/// Substitutes every placeholder in `target` with the same sample value, so that the
+/// shape of the URLs it renders to can be checked.
+pub fn sample(target: &str) -> String {
+    parse(target)
+        .into_iter()
+        .map(|part| match part {
+            Part::Literal(text) => text,
+            _ => "x",
+        })
+        .collect()
+}
+
+/// Substitutes the placeholders in `target` with the values in `context`.
+///
+/// Values are percent-encoded for the part of the URL they end up in: a placeholder
+/// before the `?` of the target is encoded as a path segment, and one after it is
+/// encoded as a query component. Captures of a pattern keep their slashes in the path,
+/// as does `{*}` between the segments it joins. Placeholders without a value are left
+/// empty.
+pub fn render(target: &str, context: &TemplateContext<'_>) -> String {
+    let parts = parse(target);
+    let consumed = parts
+        .iter()
+        .filter_map(|part| match part {
+            Part::Segment(n) => Some(*n),
+            _ => None,
+        })
+        .max()
+        .unwrap_or(0);
+
+    let mut rendered = String::with_capacity(target.len());
+    let mut in_query = false;
+
+    let set = |in_query: bool, captured: bool| match (in_query, captured) {
+        (true, _) => QUERY_COMPONENT,
+        (false, true) => PATH_CAPTURE,
+        (false, false) => PATH_SEGMENT,
+    };
+
+    for part in parts {
+        match part {
+            Part::Literal(text) => {
+                in_query = in_query || text.contains(['?', '#']);
+                rendered.push_str(text);
+            }
+            Part::Segment(n) => {
+                let value = context.segments.get(n - 1).copied().unwrap_or("");
+                rendered.extend(utf8_percent_encode(value, set(in_query, context.captures)));
+            }
+            Part::Tail => {
+                let tail = context.segments.iter().skip(consumed);
+                for (i, segment) in tail.enumerate() {
+                    if i > 0 {
+                        rendered.push('/');
+                    }
+                    rendered.extend(utf8_percent_encode(
+                        segment,
+                        set(in_query, context.captures),
+                    ));
+                }
+            }
+            Part::Query(name) => {
+                let (value, captured) = context.named_value(name);
+                rendered.extend(utf8_percent_encode(value, set(in_query, captured)));
+            }
+        }
+    }
+
+    rendered
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_has_placeholders() {
+        assert!(has_placeholders("https://github.com/{1}/{2}/pulls"));
+        assert!(has_placeholders("https://jira.example.com/browse/PROJ-{*}"));
+        assert!(has_placeholders("https://google.com/search?q={q}"));
+        assert!(!has_placeholders("https://example.com"));
+        assert!(!has_placeholders("https://example.com/{not a placeholder}"));
+        assert!(!has_placeholders("https://example.com/{0}"));
+    }
+
+    #[test]
+    fn test_names() {
+        assert_eq!(
+            names("https://example.com/{1}/{team}?q={q}&{*}"),
+            vec!["team", "q"]
+        );
+    }
+
+    #[test]
+    fn test_render_segments() {
+        let context = TemplateContext {
+            segments: vec!["cryptaliagy", "golinks"],
+            ..Default::default()
+        };
+
+        assert_eq!(
+            render("https://github.com/{1}/{2}/pulls", &context),
+            "https://github.com/cryptaliagy/golinks/pulls"
+        );
+    }
+
+    #[test]
+    fn test_render_tail() {
+        let context = TemplateContext {
+            segments: vec!["org", "a b", "c"],
+            ..Default::default()
+        };
+
+        assert_eq!(
+            render("https://example.com/{*}", &context),
+            "https://example.com/org/a%20b/c"
+        );
+        assert_eq!(
+            render("https://example.com/{1}/tree/main/{*}", &context),
+            "https://example.com/org/tree/main/a%20b/c"
+        );
+    }
+
+    #[test]
+    fn test_render_query() {
+        let context = TemplateContext {
+            segments: vec!["a/b?c"],
+            query: vec![("q", "rust & go=fun")],
+            ..Default::default()
+        };
+
+        assert_eq!(
+            render("https://example.com/{1}?q={q}&page={page}", &context),
+            "https://example.com/a%2Fb%3Fc?q=rust%20%26%20go%3Dfun&page="
+        );
+    }
+
+    #[test]
+    fn test_render_captures() {
+        let context = TemplateContext {
+            segments: vec!["docs/api v2"],
+            named: vec![("rest", "a/b")],
+            query: vec![("page", "c/d")],
+            captures: true,
+        };
+
+        assert_eq!(
+            render("https://example.com/{1}/{rest}/{page}", &context),
+            "https://example.com/docs/api%20v2/a/b/c%2Fd"
+        );
+        assert_eq!(
+            render("https://example.com/{*}", &context),
+            "https://example.com/docs/api%20v2"
+        );
+    }
+
+    #[test]
+    fn test_render_keeps_invalid_placeholders() {
+        let context = TemplateContext::default();
+
+        assert_eq!(
+            render("https://example.com/{not valid}/{", &context),
+            "https://example.com/{not valid}/{"
+        );
+    }
+
+    #[test]
+    fn test_sample() {
+        assert_eq!(
+            sample("https://{1}.example.com/{*}?q={q}&x={not valid}"),
+            "https://x.example.com/x?q=x&x={not valid}"
+        );
+        assert!(uses_path("https://example.com/{*}"));
+        assert!(!uses_path("https://example.com/?q={q}"));
+    }
+}
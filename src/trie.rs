@@ -0,0 +1,197 @@
+use std::collections::HashMap;
+
+/// A trie of keys split into their `/`-separated segments. Finding the longest key that is
+/// a prefix of a path takes a single pass over the path and does not allocate.
+///
+/// The empty key is stored at the root, so it is a prefix of every path.
+#[derive(Debug)]
+pub struct SegmentTrie<T> {
+    nodes: Vec<Node<T>>,
+    len: usize,
+}
+
+#[derive(Debug)]
+struct Node<T> {
+    value: Option<T>,
+    children: HashMap<Box<str>, usize>,
+}
+
+impl<T> Node<T> {
+    fn new() -> Self {
+        Self {
+            value: None,
+            children: HashMap::new(),
+        }
+    }
+}
+
+/// Splits a key into its segments. The empty key has no segments.
+fn segments(key: &str) -> impl Iterator<Item = &str> {
+    key.split('/').filter(move |_| !key.is_empty())
+}
+
+impl<T> SegmentTrie<T> {
+    /// Creates an empty trie.
+    pub fn new() -> Self {
+        Self {
+            nodes: vec![Node::new()],
+            len: 0,
+        }
+    }
+
+    /// Stores `value` under `key`, returning the value it replaced.
+    pub fn insert(&mut self, key: &str, value: T) -> Option<T> {
+        let mut node = 0;
+
+        for segment in segments(key) {
+            node = match self.nodes[node].children.get(segment) {
+                Some(&child) => child,
+                None => {
+                    let child = self.nodes.len();
+                    self.nodes.push(Node::new());
+                    self.nodes[node].children.insert(segment.into(), child);
+                    child
+                }
+            };
+        }
+
+        let replaced = self.nodes[node].value.replace(value);
+        if replaced.is_none() {
+            self.len += 1;
+        }
+        replaced
+    }
+
+    /// Gets the value stored under exactly `key`.
+    pub fn get(&self, key: &str) -> Option<&T> {
+        let mut node = 0;
+
+        for segment in segments(key) {
+            node = *self.nodes[node].children.get(segment)?;
+        }
+
+        self.nodes[node].value.as_ref()
+    }
+
+    /// Finds the value stored under the longest key made of whole leading segments of
+    /// `path`, along with the rest of the path after that key, without its leading `/`.
+    pub fn longest_prefix<'p>(&self, path: &'p str) -> Option<(&T, &'p str)> {
+        let mut node = 0;
+        let mut best = self.nodes[node].value.as_ref().map(|value| (value, 0));
+        let mut end = 0;
+
+        for segment in segments(path) {
+            node = match self.nodes[node].children.get(segment) {
+                Some(&child) => child,
+                None => break,
+            };
+
+            end += segment.len();
+            if let Some(value) = &self.nodes[node].value {
+                best = Some((value, end));
+            }
+            end += 1;
+        }
+
+        best.map(|(value, end)| (value, path[end..].trim_start_matches('/')))
+    }
+
+    /// Iterates over the stored values, in no particular order.
+    pub fn values(&self) -> impl Iterator<Item = &T> {
+        self.nodes.iter().filter_map(|node| node.value.as_ref())
+    }
+
+    /// Iterates mutably over the stored values, in no particular order.
+    pub fn values_mut(&mut self) -> impl Iterator<Item = &mut T> {
+        self.nodes.iter_mut().filter_map(|node| node.value.as_mut())
+    }
+
+    /// Consumes the trie, iterating over the stored values in no particular order.
+    pub fn into_values(self) -> impl Iterator<Item = T> {
+        self.nodes.into_iter().filter_map(|node| node.value)
+    }
+
+    /// Gets the number of stored values.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Gets a value indicating whether no values are stored.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+}
+
+impl<T> Default for SegmentTrie<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> FromIterator<(String, T)> for SegmentTrie<T> {
+    fn from_iter<I: IntoIterator<Item = (String, T)>>(iter: I) -> Self {
+        let mut trie = Self::new();
+        for (key, value) in iter {
+            trie.insert(&key, value);
+        }
+        trie
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn trie(keys: &[&str]) -> SegmentTrie<String> {
+        keys.iter()
+            .map(|key| (key.to_string(), key.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn test_get() {
+        let trie = trie(&["e", "e/x", ""]);
+
+        assert_eq!(trie.get("e/x").map(String::as_str), Some("e/x"));
+        assert_eq!(trie.get("").map(String::as_str), Some(""));
+        assert_eq!(trie.get("e/y"), None);
+        assert_eq!(trie.len(), 3);
+    }
+
+    #[test]
+    fn test_longest_prefix() {
+        let trie = trie(&["e", "e/x"]);
+
+        let (value, rest) = trie.longest_prefix("e/x/y/z").unwrap();
+        assert_eq!((value.as_str(), rest), ("e/x", "y/z"));
+
+        let (value, rest) = trie.longest_prefix("e/y").unwrap();
+        assert_eq!((value.as_str(), rest), ("e", "y"));
+
+        let (value, rest) = trie.longest_prefix("e").unwrap();
+        assert_eq!((value.as_str(), rest), ("e", ""));
+
+        assert!(trie.longest_prefix("ex").is_none());
+        assert!(trie.longest_prefix("").is_none());
+    }
+
+    #[test]
+    fn test_longest_prefix_matches_whole_segments() {
+        let trie = trie(&["", "a/"]);
+
+        let (value, rest) = trie.longest_prefix("a//b").unwrap();
+        assert_eq!((value.as_str(), rest), ("a/", "b"));
+
+        let (value, rest) = trie.longest_prefix("ab").unwrap();
+        assert_eq!((value.as_str(), rest), ("", "ab"));
+    }
+
+    #[test]
+    fn test_insert_replaces() {
+        let mut trie = trie(&["a"]);
+
+        assert_eq!(trie.insert("a", "b".to_string()), Some("a".to_string()));
+        assert_eq!(trie.len(), 1);
+        assert_eq!(trie.values().count(), 1);
+    }
+}
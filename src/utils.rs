@@ -1,5 +1,7 @@
 use std::time::Duration;
 
+use rocket::http::uri::Origin;
+
 /// Formats a `Duration` as a string.
 pub fn format_duration(duration: Duration) -> String {
     let secs = duration.as_secs();
@@ -14,3 +16,11 @@ pub fn format_duration(duration: Duration) -> String {
         format!("{}.{} μs", micros, nanos)
     }
 }
+
+/// Decodes the path of `uri` the same way the routes that take the rest of the path as
+/// a `PathBuf` do, percent-decoding every segment. Paths those routes refuse, such as
+/// ones with a segment that decodes to a `/`, give `None`.
+pub fn decode_path(uri: &Origin<'_>) -> Option<String> {
+    let path = uri.path().segments().to_path_buf(false).ok()?;
+    path.into_os_string().into_string().ok()
+}